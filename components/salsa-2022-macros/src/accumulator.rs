@@ -35,7 +35,23 @@ impl crate::options::AllowedOptions for Accumulator {
 
     const LRU: bool = false;
 
+    const HISTORY: bool = false;
+
+    const DURABILITY: bool = false;
+
     const CONSTRUCTOR_NAME: bool = false;
+
+    const DEBUG: bool = false;
+
+    const EQ: bool = false;
+
+    const RETURNS: bool = false;
+
+    const SERDE: bool = false;
+
+    const SHARED: bool = false;
+
+    const PER_ENTRY_DEPS: bool = false;
 }
 
 fn accumulator_contents(
@@ -113,6 +129,7 @@ fn ingredients_for_impl(
 ) -> syn::ItemImpl {
     let jar_ty = args.jar_ty();
     let debug_name = crate::literal(struct_name);
+    let dump_fmt_fn = crate::dump_fmt_fn(data_ty);
     parse_quote! {
         impl salsa::storage::IngredientsFor for #struct_name {
             type Ingredients = salsa::accumulator::AccumulatorIngredient<#data_ty>;
@@ -133,6 +150,7 @@ fn ingredients_for_impl(
                         },
                     );
                     salsa::accumulator::AccumulatorIngredient::new(index, #debug_name)
+                        .with_dump_fmt(#dump_fmt_fn)
             }
         }
     }
@@ -7,6 +7,7 @@ pub(crate) struct Configuration {
     pub(crate) backdate_fn: syn::ImplItemMethod,
     pub(crate) execute_fn: syn::ImplItemMethod,
     pub(crate) recover_fn: syn::ImplItemMethod,
+    pub(crate) fmt_key_fn: syn::ImplItemMethod,
 }
 
 impl Configuration {
@@ -20,6 +21,7 @@ impl Configuration {
             backdate_fn,
             execute_fn,
             recover_fn,
+            fmt_key_fn,
         } = self;
         parse_quote! {
             impl salsa::function::Configuration for #self_ty {
@@ -31,6 +33,7 @@ impl Configuration {
                 #backdate_fn
                 #execute_fn
                 #recover_fn
+                #fmt_key_fn
             }
         }
     }
@@ -55,9 +58,18 @@ impl quote::ToTokens for CycleRecoveryStrategy {
 }
 
 /// Returns an appropriate definition for `should_backdate_value` depending on
-/// whether this value is memoized or not.
-pub(crate) fn should_backdate_value_fn(should_backdate: bool) -> syn::ImplItemMethod {
-    if should_backdate {
+/// whether this value is memoized or not, and whether a custom `eq` function was given.
+pub(crate) fn should_backdate_value_fn(
+    should_backdate: bool,
+    eq: Option<&syn::Path>,
+) -> syn::ImplItemMethod {
+    if let Some(eq) = eq {
+        parse_quote! {
+            fn should_backdate_value(v1: &Self::Value, v2: &Self::Value) -> bool {
+                #eq(v1, v2)
+            }
+        }
+    } else if should_backdate {
         parse_quote! {
             fn should_backdate_value(v1: &Self::Value, v2: &Self::Value) -> bool {
                 salsa::function::should_backdate_value(v1, v2)
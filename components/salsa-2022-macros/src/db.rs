@@ -3,7 +3,7 @@ use syn::Token;
 
 // Source:
 //
-// #[salsa::db(Jar0, Jar1, Jar2)]
+// #[salsa::db(Jar0, Jar1, Jar2, registered(Jar3), no_snapshots)]
 // pub struct Database {
 //    storage: salsa::Storage<Self>,
 // }
@@ -30,6 +30,7 @@ pub(crate) fn db(
     let as_salsa_database_impl = as_salsa_database_impl(&input);
     let has_jars_impl = has_jars_impl(&args, &input, &storage);
     let has_jars_dyn_impl = has_jars_dyn_impl(&input, &storage);
+    let allows_snapshots_impl = allows_snapshots_impl(&args, &input);
     let per_jar_impls = per_jar_impls(&args, &input, &storage);
 
     quote! {
@@ -37,23 +38,99 @@ pub(crate) fn db(
         #as_salsa_database_impl
         #has_jars_impl
         #has_jars_dyn_impl
+        #allows_snapshots_impl
         #(#per_jar_impls)*
     }
     .into()
 }
 
 pub struct Args {
-    jar_paths: syn::punctuated::Punctuated<syn::Path, Token![,]>,
+    jar_paths: Vec<syn::Path>,
+
+    /// Jars given as `registered(...)`, which are only created by `Storage::register_jar`.
+    registered_jar_paths: Vec<syn::Path>,
+
+    /// The number of durability levels given by `durabilities = N`, if any.
+    durabilities: Option<syn::LitInt>,
+
+    /// The `no_snapshots` identifier, if given.
+    no_snapshots: Option<syn::Ident>,
 }
 
 impl syn::parse::Parse for Args {
     fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
+        let mut jar_paths = vec![];
+        let mut registered_jar_paths = vec![];
+        let mut durabilities = None;
+        let mut no_snapshots = None;
+        while !input.is_empty() {
+            if input.peek(syn::Ident) && input.peek2(syn::token::Paren) {
+                let ident: syn::Ident = input.parse()?;
+                if ident != "registered" {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("unrecognized option `{}`", ident),
+                    ));
+                }
+                let content;
+                syn::parenthesized!(content in input);
+                let paths = syn::punctuated::Punctuated::<syn::Path, Token![,]>::parse_terminated(
+                    &content,
+                )?;
+                registered_jar_paths.extend(paths);
+            } else if input.peek(syn::Ident) && input.peek2(Token![=]) {
+                let ident: syn::Ident = input.parse()?;
+                if ident != "durabilities" {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("unrecognized option `{}`", ident),
+                    ));
+                }
+                let _eq: Token![=] = input.parse()?;
+                let lit: syn::LitInt = input.parse()?;
+                lit.base10_parse::<usize>()?;
+                if let Some(old) = std::mem::replace(&mut durabilities, Some(lit)) {
+                    return Err(syn::Error::new(
+                        old.span(),
+                        "option `durabilities` provided twice",
+                    ));
+                }
+            } else if is_no_snapshots_option(input) {
+                let ident: syn::Ident = input.parse()?;
+                if let Some(old) = std::mem::replace(&mut no_snapshots, Some(ident)) {
+                    return Err(syn::Error::new(
+                        old.span(),
+                        "option `no_snapshots` provided twice",
+                    ));
+                }
+            } else {
+                jar_paths.push(input.parse()?);
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            let _comma: Token![,] = input.parse()?;
+        }
+
         Ok(Self {
-            jar_paths: syn::punctuated::Punctuated::parse_terminated(input)?,
+            jar_paths,
+            registered_jar_paths,
+            durabilities,
+            no_snapshots,
         })
     }
 }
 
+/// True if the next argument is the `no_snapshots` option (rather than a jar path).
+fn is_no_snapshots_option(input: syn::parse::ParseStream<'_>) -> bool {
+    let fork = input.fork();
+    match fork.parse::<syn::Ident>() {
+        Ok(ident) => ident == "no_snapshots" && (fork.is_empty() || fork.peek(Token![,])),
+        Err(_) => false,
+    }
+}
+
 fn find_storage_field(input: &syn::ItemStruct) -> Result<syn::Ident, &'static str> {
     let storage = "storage";
     for field in input.fields.iter() {
@@ -82,21 +159,48 @@ fn as_salsa_database_impl(input: &syn::ItemStruct) -> syn::ItemImpl {
     }
 }
 
+/// Databases declared `no_snapshots` do not implement `AllowsSnapshots`,
+/// so snapshotting their storage is a compilation error, but their jars need not be `Send + Sync`.
+fn allows_snapshots_impl(args: &Args, input: &syn::ItemStruct) -> Option<syn::ItemImpl> {
+    let db = &input.ident;
+    if args.no_snapshots.is_some() {
+        return None;
+    }
+    Some(parse_quote! {
+        impl salsa::storage::AllowsSnapshots for #db {}
+    })
+}
+
 fn has_jars_impl(args: &Args, input: &syn::ItemStruct, storage: &syn::Ident) -> syn::ItemImpl {
     let jar_paths: Vec<&syn::Path> = args.jar_paths.iter().collect();
+    let registered_jar_paths: Vec<&syn::Path> = args.registered_jar_paths.iter().collect();
     let db = &input.ident;
+    let durabilities = args
+        .durabilities
+        .as_ref()
+        .map(|n| quote! { const DURABILITIES: usize = #n; });
+    let no_snapshots = args
+        .no_snapshots
+        .as_ref()
+        .map(|_| quote! { const NO_SNAPSHOTS: bool = true; });
     parse_quote! {
         // ANCHOR: HasJars
         impl salsa::storage::HasJars for #db {
-            type Jars = (#(#jar_paths,)*);
+            type Jars = (#(#jar_paths,)* #(Option<#registered_jar_paths>,)*);
             // ANCHOR_END: HasJars
 
+            #durabilities
+
+            #no_snapshots
+
             fn jars(&self) -> (&Self::Jars, &salsa::Runtime) {
                 self.#storage.jars()
             }
 
             fn jars_mut(&mut self) -> (&mut Self::Jars, &mut salsa::Runtime) {
-                self.#storage.jars_mut()
+                let revision = self.#storage.new_revision();
+                self.#storage.report_new_revision(self, revision);
+                self.#storage.jars_mut_in_current_revision()
             }
 
             // ANCHOR: create_jars
@@ -105,6 +209,9 @@ fn has_jars_impl(args: &Args, input: &syn::ItemStruct, storage: &syn::Ident) ->
                     #(
                         <#jar_paths as salsa::jar::Jar>::create_jar(routes),
                     )*
+                    #(
+                        None::<#registered_jar_paths>,
+                    )*
                 )
             }
             // ANCHOR_END: create_jars
@@ -145,6 +252,23 @@ fn has_jars_dyn_impl(input: &syn::ItemStruct, storage: &syn::Ident) -> syn::Item
                 ingredient.origin(index.key_index())
             }
 
+            fn subtree_executed_at(
+                &self,
+                index: salsa::DatabaseKeyIndex,
+            ) -> Option<salsa::Revision> {
+                let ingredient = self.#storage.ingredient(index.ingredient_index());
+                ingredient.subtree_executed_at(index.key_index())
+            }
+
+            fn verify_from_scratch(
+                &self,
+                index: salsa::DatabaseKeyIndex,
+                value: &dyn std::any::Any,
+            ) {
+                let ingredient = self.#storage.ingredient(index.ingredient_index());
+                ingredient.verify_from_scratch(self, index.key_index(), value)
+            }
+
             fn mark_validated_output(&self, executor: salsa::DatabaseKeyIndex, output: salsa::key::DependencyIndex) {
                 let ingredient = self.#storage.ingredient(output.ingredient_index());
                 ingredient.mark_validated_output(self, executor, output.key_index());
@@ -163,18 +287,51 @@ fn has_jars_dyn_impl(input: &syn::ItemStruct, storage: &syn::Ident) -> syn::Item
                 let ingredient = self.#storage.ingredient(index.ingredient_index());
                 ingredient.fmt_index(index.key_index(), fmt)
             }
+
+            fn fmt_key(&self, index: salsa::key::DependencyIndex, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let ingredient = self.#storage.ingredient(index.ingredient_index());
+                ingredient.fmt_key(self, index.key_index(), fmt)
+            }
+
+            fn debug_name(&self, ingredient_index: salsa::IngredientIndex) -> &'static str {
+                let ingredient = self.#storage.ingredient(ingredient_index);
+                ingredient.debug_name()
+            }
+
+            fn dump(&self, out: &mut dyn std::io::Write) -> std::io::Result<()> {
+                self.#storage.dump(self, out)
+            }
         }
     }
 }
 
 fn per_jar_impls(args: &Args, input: &syn::ItemStruct, storage: &syn::Ident) -> Vec<syn::ItemImpl> {
     let db = &input.ident;
-    args.jar_paths
+    let static_jars = args.jar_paths.iter().map(|jar_path| (jar_path, false));
+    let registered_jars = args
+        .registered_jar_paths
         .iter()
+        .map(|jar_path| (jar_path, true));
+    static_jars
+        .chain(registered_jars)
         .zip(0..)
-        .flat_map(|(jar_path, jar_index)| {
+        .flat_map(|((jar_path, registered), jar_index)| {
             let jar_index = Literal::u32_unsuffixed(jar_index);
-            vec![
+
+            // Registered jars are `None` until `Storage::register_jar` is called.
+            let (jar_ref, jar_mut): (syn::Expr, syn::Expr) = if registered {
+                (
+                    parse_quote!(__jars.#jar_index.as_ref().unwrap_or_else(|| salsa::storage::unregistered_jar::<#jar_path>())),
+                    parse_quote!(__jars.#jar_index.as_mut().unwrap_or_else(|| salsa::storage::unregistered_jar::<#jar_path>())),
+                )
+            } else {
+                (
+                    parse_quote!(&__jars.#jar_index),
+                    parse_quote!(&mut __jars.#jar_index),
+                )
+            };
+
+            let mut impls = vec![
                 parse_quote! {
                     impl salsa::storage::DbWithJar<#jar_path> for #db {
                         fn as_jar_db<'db>(&'db self) -> &'db <#jar_path as salsa::jar::Jar<'db>>::DynDb
@@ -190,28 +347,40 @@ fn per_jar_impls(args: &Args, input: &syn::ItemStruct, storage: &syn::Ident) ->
                     impl salsa::storage::HasJar<#jar_path> for #db {
                         fn jar(&self) -> (&#jar_path, &salsa::Runtime) {
                             let (__jars, __runtime) = self.#storage.jars();
-                            (&__jars.#jar_index, __runtime)
+                            (#jar_ref, __runtime)
                         }
 
                         fn jar_mut(&mut self) -> (&mut #jar_path, &mut salsa::Runtime) {
-                            let (__jars, __runtime) = self.#storage.jars_mut();
-                            (&mut __jars.#jar_index, __runtime)
+                            let (__jars, __runtime) = salsa::storage::HasJars::jars_mut(self);
+                            (#jar_mut, __runtime)
                         }
                     }
                 },
 
                 parse_quote! {
                     impl salsa::storage::JarFromJars<#jar_path> for #db {
-                        fn jar_from_jars<'db>(jars: &Self::Jars) -> &#jar_path {
-                            &jars.#jar_index
+                        fn jar_from_jars<'db>(__jars: &Self::Jars) -> &#jar_path {
+                            #jar_ref
                         }
 
-                        fn jar_from_jars_mut<'db>(jars: &mut Self::Jars) -> &mut #jar_path {
+                        fn jar_from_jars_mut<'db>(__jars: &mut Self::Jars) -> &mut #jar_path {
+                            #jar_mut
+                        }
+                    }
+                },
+            ];
+
+            if registered {
+                impls.push(parse_quote! {
+                    impl salsa::storage::HasRegisteredJar<#jar_path> for #db {
+                        fn registered_jar(jars: &mut Self::Jars) -> &mut Option<#jar_path> {
                             &mut jars.#jar_index
                         }
                     }
-                }
-            ]
+                });
+            }
+
+            impls
         })
         .collect()
 }
@@ -37,7 +37,9 @@ impl InputStruct {
         let inherent_impl = self.input_inherent_impl();
         let ingredients_for_impl = self.input_ingredients();
         let as_id_impl = self.as_id_impl();
+        let as_debug_with_db_impl = self.as_debug_with_db_impl();
         let salsa_struct_in_db_impl = self.salsa_struct_in_db_impl();
+        let serde_impls = self.input_serde_impls();
 
         Ok(quote! {
             #(#config_structs)*
@@ -45,17 +47,59 @@ impl InputStruct {
             #inherent_impl
             #ingredients_for_impl
             #as_id_impl
+            #as_debug_with_db_impl
             #(#config_impls)*
             #salsa_struct_in_db_impl
+            #serde_impls
         })
     }
 
     fn validate_input(&self) -> syn::Result<()> {
         self.disallow_id_fields("input")?;
+        self.disallow_eq_fields("input")?;
+        self.disallow_shared("input")?;
+        self.disallow_per_entry_deps("input")?;
+        self.disallow_durability_getter_names()?;
 
         Ok(())
     }
 
+    /// Disallow fields whose name is that of the `<field>_durability` getter of another field.
+    fn disallow_durability_getter_names(&self) -> syn::Result<()> {
+        for ef in self.all_fields() {
+            let getter_name = format!("{}_durability", ef.name());
+            if let Some(other) = self.all_fields().find(|other| *other.name() == getter_name) {
+                return Err(syn::Error::new(
+                    other.name().span(),
+                    format!(
+                        "the field name `{getter_name}` is disallowed because it is the name of \
+                         the durability getter for field `{}`",
+                        ef.name()
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generate the `serde` impls, which read each field from its ingredient
+    /// and re-create the input with `new`.
+    fn input_serde_impls(&self) -> Option<TokenStream> {
+        let ident = self.id_ident();
+        let jar_ty = self.jar_ty();
+        let field_values = self
+            .all_field_indices()
+            .into_iter()
+            .map(|field_index| {
+                parse_quote! {
+                    <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar).#field_index.fetch(__runtime, *self)
+                }
+            })
+            .collect();
+        self.serde_impls(field_values, parse_quote!(__db))
+    }
+
     /// Generate an inherent impl with methods on the entity type.
     fn input_inherent_impl(&self) -> syn::ItemImpl {
         let ident = self.id_ident();
@@ -66,39 +110,51 @@ impl InputStruct {
         let field_indices = self.all_field_indices();
         let field_names = self.all_field_names();
         let field_tys: Vec<_> = self.all_field_tys();
-        let field_clones: Vec<_> = self.all_fields().map(SalsaField::is_clone_field).collect();
+        let field_return_modes: Vec<_> = self.all_fields().map(SalsaField::return_mode).collect();
         let get_field_names: Vec<_> = self.all_get_field_names();
-        let field_getters: Vec<syn::ImplItemMethod> = field_indices.iter().zip(&get_field_names).zip(&field_tys).zip(&field_clones).map(|(((field_index, get_field_name), field_ty), is_clone_field)|
-            if !*is_clone_field {
-                parse_quote! {
-                    pub fn #get_field_name<'db>(self, __db: &'db #db_dyn_ty) -> &'db #field_ty
-                    {
-                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
-                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                        __ingredients.#field_index.fetch(__runtime, self)
-                    }
-                }
-            } else {
-                parse_quote! {
-                    pub fn #get_field_name<'db>(self, __db: &'db #db_dyn_ty) -> #field_ty
-                    {
-                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
-                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                        __ingredients.#field_index.fetch(__runtime, self).clone()
-                    }
+        let field_getters: Vec<syn::ImplItemMethod> = field_indices.iter().zip(&get_field_names).zip(&field_tys).zip(&field_return_modes).map(|(((field_index, get_field_name), field_ty), return_mode)| {
+            let return_ty = return_mode.return_ty(&parse_quote!('db), field_ty);
+            let value = return_mode.return_value(parse_quote!(__ingredients.#field_index.fetch(__runtime, self)));
+            parse_quote! {
+                pub fn #get_field_name<'db>(self, __db: &'db #db_dyn_ty) -> #return_ty
+                {
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+                    let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
+                    #value
                 }
             }
-        )
+        })
         .collect();
 
+        let field_durabilities = self.all_field_durabilities();
         let set_field_names = self.all_set_field_names();
-        let field_setters: Vec<syn::ImplItemMethod> = field_indices.iter().zip(&set_field_names).zip(&field_tys).map(|((field_index, set_field_name), field_ty)| {
+        let field_setters: Vec<syn::ImplItemMethod> = field_indices.iter().zip(&set_field_names).zip(&field_tys).zip(&field_durabilities).map(|(((field_index, set_field_name), field_ty), durability)| {
             parse_quote! {
                 pub fn #set_field_name<'db>(self, __db: &'db mut #db_dyn_ty) -> salsa::setter::Setter<'db, #ident, #field_ty>
                 {
+                    {
+                        let (__jar, _) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(&*__db);
+                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
+                        __ingredients.#field_index.report_will_set(&*__db, self);
+                    }
                     let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar_mut(__db);
                     let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient_mut(__jar);
                     salsa::setter::Setter::new(__runtime, self, &mut __ingredients.#field_index)
+                        .with_durability(#durability)
+                }
+            }
+        })
+        .collect();
+
+        let field_durability_getters: Vec<syn::ImplItemMethod> = field_indices.iter().zip(&field_names).map(|(field_index, field_name)| {
+            let durability_name = syn::Ident::new(&format!("{}_durability", field_name), field_name.span());
+            parse_quote! {
+                /// The durability with which this field was last set.
+                pub fn #durability_name(self, __db: &#db_dyn_ty) -> salsa::Durability
+                {
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+                    let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
+                    __ingredients.#field_index.fetch_durability(__runtime, self)
                 }
             }
         })
@@ -113,7 +169,7 @@ impl InputStruct {
                     let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient_mut(__jar);
                     let __id = __ingredients.#input_index.new_input(__runtime);
                     #(
-                        __ingredients.#field_indices.store(__runtime, __id, #field_names, salsa::Durability::LOW);
+                        __ingredients.#field_indices.store(__runtime, __id, #field_names, #field_durabilities);
                     )*
                     __id
                 }
@@ -121,6 +177,8 @@ impl InputStruct {
                 #(#field_getters)*
 
                 #(#field_setters)*
+
+                #(#field_durability_getters)*
             }
         }
     }
@@ -138,6 +196,17 @@ impl InputStruct {
         let input_index: Literal = self.input_index();
         let debug_name_struct = literal(self.id_ident());
         let debug_name_fields: Vec<_> = self.all_field_names().into_iter().map(literal).collect();
+        let new_field_ingredients: Vec<_> = debug_name_fields
+            .iter()
+            .zip(&field_ty)
+            .map(|(debug_name, field_ty)| {
+                let dump_fmt_fn = crate::dump_fmt_fn(field_ty);
+                match self.history_capacity() {
+                    Some(capacity) => quote! { with_history(index, #debug_name, #capacity).with_dump_fmt(#dump_fmt_fn) },
+                    None => quote! { new(index, #debug_name).with_dump_fmt(#dump_fmt_fn) },
+                }
+            })
+            .collect();
 
         parse_quote! {
             impl salsa::storage::IngredientsFor for #ident {
@@ -170,7 +239,7 @@ impl InputStruct {
                                         &mut ingredients.#all_field_indices
                                     },
                                 );
-                                salsa::input_field::InputFieldIngredient::new(index, #debug_name_fields)
+                                salsa::input_field::InputFieldIngredient::#new_field_ingredients
                             },
                         )*
                         {
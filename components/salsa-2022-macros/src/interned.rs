@@ -37,24 +37,49 @@ impl InternedStruct {
         let data_struct = self.data_struct();
         let ingredients_for_impl = self.ingredients_for_impl();
         let as_id_impl = self.as_id_impl();
+        let as_debug_with_db_impl = self.as_debug_with_db_impl();
         let named_fields_impl = self.inherent_impl_for_named_fields();
         let salsa_struct_in_db_impl = self.salsa_struct_in_db_impl();
+        let specifiable_in_db_impl = self.specifiable_in_db_impl();
+        let serde_impls = self.interned_serde_impls();
 
         Ok(quote! {
             #id_struct
             #data_struct
             #ingredients_for_impl
             #as_id_impl
+            #as_debug_with_db_impl
             #named_fields_impl
             #salsa_struct_in_db_impl
+            #specifiable_in_db_impl
+            #serde_impls
         })
     }
 
     fn validate_interned(&self) -> syn::Result<()> {
         self.disallow_id_fields("interned")?;
+        self.disallow_eq_fields("interned")?;
+        self.disallow_input_options("interned")?;
         Ok(())
     }
 
+    /// Generate the `serde` impls, which read each field from the interned data
+    /// and re-intern it with `new`.
+    fn interned_serde_impls(&self) -> Option<TokenStream> {
+        let id_ident = self.id_ident();
+        let jar_ty = self.jar_ty();
+        let field_values = self
+            .all_field_names()
+            .into_iter()
+            .map(|field_name| {
+                parse_quote! {
+                    &<#jar_ty as salsa::storage::HasIngredientsFor< #id_ident >>::ingredient(__jar).data(__runtime, *self).#field_name
+                }
+            })
+            .collect();
+        self.serde_impls(field_values, parse_quote!(&*__db))
+    }
+
     /// If this is an interned struct, then generate methods to access each field,
     /// as well as a `new` method.
     fn inherent_impl_for_named_fields(&self) -> syn::ItemImpl {
@@ -69,21 +94,15 @@ impl InternedStruct {
                 let field_name = field.name();
                 let field_ty = field.ty();
                 let field_get_name = field.get_name();
-                if field.is_clone_field() {
-                    parse_quote! {
-                        #vis fn #field_get_name(self, db: &#db_dyn_ty) -> #field_ty {
-                            let (jar, runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
-                            let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #id_ident >>::ingredient(jar);
-                            std::clone::Clone::clone(&ingredients.data(runtime, self).#field_name)
-                        }
-                    }
-                } else {
-                    parse_quote! {
-                        #vis fn #field_get_name<'db>(self, db: &'db #db_dyn_ty) -> &'db #field_ty {
-                            let (jar, runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
-                            let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #id_ident >>::ingredient(jar);
-                            &ingredients.data(runtime, self).#field_name
-                        }
+                let return_ty = field.return_mode().return_ty(&parse_quote!('db), field_ty);
+                let value = field
+                    .return_mode()
+                    .return_value(parse_quote!(&ingredients.data(runtime, self).#field_name));
+                parse_quote! {
+                    #vis fn #field_get_name<'db>(self, db: &'db #db_dyn_ty) -> #return_ty {
+                        let (jar, runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
+                        let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #id_ident >>::ingredient(jar);
+                        #value
                     }
                 }
             })
@@ -98,9 +117,28 @@ impl InternedStruct {
                 db: &#db_dyn_ty,
                 #(#field_names: #field_tys,)*
             ) -> Self {
-                let (jar, runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
+                let (jar, _) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
+                let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #id_ident >>::ingredient(jar);
+                ingredients.intern_struct(db, #data_ident {
+                    #(#field_names,)*
+                })
+            }
+        };
+
+        let owned_constructor_name = syn::Ident::new(
+            &format!("{}_owned", constructor_name),
+            constructor_name.span(),
+        );
+        let new_owned_method: syn::ImplItemMethod = parse_quote! {
+            /// Like the constructor, but also makes the active query the owner of the value,
+            /// permitting it to `specify` tracked functions keyed on it.
+            #vis fn #owned_constructor_name(
+                db: &#db_dyn_ty,
+                #(#field_names: #field_tys,)*
+            ) -> Self {
+                let (jar, _) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
                 let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #id_ident >>::ingredient(jar);
-                ingredients.intern(runtime, #data_ident {
+                ingredients.intern_owned(db, #data_ident {
                     #(#field_names,)*
                 })
             }
@@ -111,6 +149,8 @@ impl InternedStruct {
                 #(#field_getters)*
 
                 #new_method
+
+                #new_owned_method
             }
         }
     }
@@ -123,6 +163,23 @@ impl InternedStruct {
         let debug_name = crate::literal(id_ident);
         let jar_ty = self.jar_ty();
         let data_ident = self.data_ident();
+        let members = self
+            .all_field_names()
+            .into_iter()
+            .map(|name| syn::Member::Named(name.clone()))
+            .collect();
+        let new_ingredient: syn::Expr = if self.is_shared() {
+            parse_quote! {
+                salsa::interned::InternedIngredient::new_shared(index, #debug_name, routes.shared_interners())
+            }
+        } else {
+            parse_quote! {
+                salsa::interned::InternedIngredient::new(index, #debug_name)
+            }
+        };
+        let per_entry_deps_call = self.per_entry_deps_call();
+        let dump_fmt_fn =
+            self.data_dump_fmt_fn(&parse_quote!(#data_ident), self.all_fields(), members);
         parse_quote! {
             impl salsa::storage::IngredientsFor for #id_ident {
                 type Jar = #jar_ty;
@@ -144,7 +201,9 @@ impl InternedStruct {
                             <_ as salsa::storage::HasIngredientsFor<Self>>::ingredient_mut(jar)
                         },
                     );
-                    salsa::interned::InternedIngredient::new(index, #debug_name)
+                    #new_ingredient
+                        .with_dump_fmt(#dump_fmt_fn)
+                        #per_entry_deps_call
                 }
             }
         }
@@ -166,4 +225,22 @@ impl InternedStruct {
             }
         }
     }
+
+    /// Implementation of `SpecifiableInDb`.
+    fn specifiable_in_db_impl(&self) -> syn::ItemImpl {
+        let ident = self.id_ident();
+        let jar_ty = self.jar_ty();
+        parse_quote! {
+            impl<DB> salsa::salsa_struct::SpecifiableInDb<DB> for #ident
+            where
+                DB: ?Sized + salsa::DbWithJar<#jar_ty>,
+            {
+                fn owned_by_active_query(self, db: &DB) -> bool {
+                    let (jar, runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
+                    let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor<#ident>>::ingredient(jar);
+                    ingredients.is_owned_by_active_query(runtime, self)
+                }
+            }
+        }
+    }
 }
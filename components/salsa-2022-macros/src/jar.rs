@@ -44,7 +44,23 @@ impl crate::options::AllowedOptions for Jar {
 
     const LRU: bool = false;
 
+    const HISTORY: bool = false;
+
+    const DURABILITY: bool = false;
+
     const CONSTRUCTOR_NAME: bool = false;
+
+    const DEBUG: bool = false;
+
+    const EQ: bool = false;
+
+    const RETURNS: bool = false;
+
+    const SERDE: bool = false;
+
+    const SHARED: bool = false;
+
+    const PER_ENTRY_DEPS: bool = false;
 }
 
 pub(crate) fn jar_struct_and_friends(
@@ -65,12 +81,16 @@ pub(crate) fn jar_struct_and_friends(
 
     let jar_impl = jar_impl(jar_struct, jar_trait, input);
 
+    let clone_impl = clone_impl(jar_struct, input);
+
     quote! {
         #output_struct
 
         #(#has_ingredients_for_impls)*
 
         #jar_impl
+
+        #clone_impl
     }
 }
 
@@ -125,6 +145,28 @@ pub(crate) fn jar_impl(
     // ANCHOR_END: create_jar
 }
 
+/// Generates a `Clone` impl for the jar, which is used to fork the database.
+/// Not every jar can be cloned (e.g., input fields need to be `Clone`),
+/// so the impl is conditional on the ingredients being `Clone`. The bounds are
+/// written as higher-ranked bounds because they mention no generic parameters,
+/// and trivially false bounds would otherwise be a compilation error.
+pub(crate) fn clone_impl(jar_struct: &Ident, input: &ItemStruct) -> proc_macro2::TokenStream {
+    let field_tys: Vec<_> = input.fields.iter().map(|f| &f.ty).collect();
+    let indices = (0..input.fields.len()).map(Literal::usize_unsuffixed);
+    quote! {
+        impl Clone for #jar_struct
+        where
+            #(
+                for<'salsa_fork> <#field_tys as salsa::storage::IngredientsFor>::Ingredients: Clone,
+            )*
+        {
+            fn clone(&self) -> Self {
+                Self(#(Clone::clone(&self.#indices)),*)
+            }
+        }
+    }
+}
+
 pub(crate) fn jar_struct(input: &ItemStruct) -> ItemStruct {
     let mut output_struct = input.clone();
     output_struct.fields = generate_fields(&input).into();
@@ -30,6 +30,18 @@ pub(crate) fn literal(ident: &proc_macro2::Ident) -> proc_macro2::Literal {
     proc_macro2::Literal::string(&ident.to_string())
 }
 
+/// Generates a function that formats a value of type `ty` for `salsa::debug::dump`,
+/// showing it as `..` if `ty` does not implement `Debug`.
+pub(crate) fn dump_fmt_fn(ty: &syn::Type) -> syn::Expr {
+    parse_quote! {
+        |__value: &#ty, __fmt: &mut std::fmt::Formatter<'_>| {
+            #[allow(unused_imports)]
+            use salsa::debug::helper::DumpFallback;
+            std::fmt::Debug::fmt(salsa::debug::helper::DumpDebug::<#ty>::dump_debug(__value), __fmt)
+        }
+    }
+}
+
 mod accumulator;
 mod configuration;
 mod db;
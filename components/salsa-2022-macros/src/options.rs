@@ -51,12 +51,62 @@ pub(crate) struct Options<A: AllowedOptions> {
     /// If this is `Some`, the value is the `<usize>`.
     pub lru: Option<usize>,
 
+    /// The `history = <usize>` option is used to keep prior values of the fields of an input,
+    /// so that the database can be rolled back.
+    ///
+    /// If this is `Some`, the value is the `<usize>`.
+    pub history: Option<usize>,
+
+    /// The `durability = <expr>` option is used to set the default durability
+    /// of the fields of an input: `LOW`, `MEDIUM` or `HIGH` (e.g., `durability = HIGH`),
+    /// a level (e.g., `durability = 3`), or an expression of type `salsa::Durability`.
+    ///
+    /// If this is `Some`, the value is the `<expr>`.
+    pub durability: Option<syn::Expr>,
+
     /// The `constructor = <ident>` option lets the user specify the name of
     /// the constructor of a salsa struct.
     ///
     /// If this is `Some`, the value is the `<ident>`.
     pub constructor_name: Option<syn::Ident>,
 
+    /// The `debug` option is used to signal that a `DebugWithDb` impl should be
+    /// generated for a salsa struct, showing the value of each field.
+    ///
+    /// If this is `Some`, the value is the `debug` identifier.
+    pub debug: Option<syn::Ident>,
+
+    /// The `eq = <path>` option is used to supply the function that compares an old and new
+    /// value to decide whether the value can be backdated, instead of `Eq`.
+    ///
+    /// If this is `Some`, the value is the `<path>`.
+    pub eq: Option<syn::Path>,
+
+    /// The `returns(<mode>)` option is used to select how the value is returned
+    /// (see [`ReturnMode`]).
+    ///
+    /// If this is `Some`, the value is the `returns` identifier and the mode.
+    pub returns: Option<(syn::Ident, ReturnMode)>,
+
+    /// The `serde` option is used to signal that a salsa struct can be serialized
+    /// and deserialized through the database (see `salsa::serde`).
+    ///
+    /// If this is `Some`, the value is the `serde` identifier.
+    pub serde: Option<syn::Ident>,
+
+    /// The `shared` option is used to signal that an interned struct uses a table
+    /// shared with the other databases in the process (see `salsa::interned::SharedInterners`).
+    /// Its fields must not contain ids of ingredients that are not shared.
+    ///
+    /// If this is `Some`, the value is the `shared` identifier.
+    pub shared: Option<syn::Ident>,
+
+    /// The `per_entry_deps` option is used to signal that queries reading an interned
+    /// or tracked struct depend on just that struct, rather than on its whole table.
+    ///
+    /// If this is `Some`, the value is the `per_entry_deps` identifier.
+    pub per_entry_deps: Option<syn::Ident>,
+
     /// Remember the `A` parameter, which plays no role after parsing.
     phantom: PhantomData<A>,
 }
@@ -74,6 +124,14 @@ impl<A: AllowedOptions> Default for Options<A> {
             constructor_name: Default::default(),
             phantom: Default::default(),
             lru: Default::default(),
+            history: Default::default(),
+            durability: Default::default(),
+            debug: Default::default(),
+            eq: Default::default(),
+            returns: Default::default(),
+            serde: Default::default(),
+            shared: Default::default(),
+            per_entry_deps: Default::default(),
         }
     }
 }
@@ -88,7 +146,15 @@ pub(crate) trait AllowedOptions {
     const DB: bool;
     const RECOVERY_FN: bool;
     const LRU: bool;
+    const HISTORY: bool;
+    const DURABILITY: bool;
     const CONSTRUCTOR_NAME: bool;
+    const DEBUG: bool;
+    const EQ: bool;
+    const RETURNS: bool;
+    const SERDE: bool;
+    const SHARED: bool;
+    const PER_ENTRY_DEPS: bool;
 }
 
 type Equals = syn::Token![=];
@@ -105,6 +171,20 @@ impl<A: AllowedOptions> Options<A> {
         return parse_quote! {crate::Jar};
     }
 
+    /// Returns how the value is returned: given by `returns(<mode>)`,
+    /// or by `return_ref`, or else by clone.
+    pub(crate) fn return_mode(&self) -> ReturnMode {
+        if let Some((_, mode)) = &self.returns {
+            return *mode;
+        }
+
+        if self.return_ref.is_some() {
+            return ReturnMode::Ref;
+        }
+
+        ReturnMode::Clone
+    }
+
     pub(crate) fn should_backdate(&self) -> bool {
         self.no_eq.is_none()
     }
@@ -224,6 +304,39 @@ impl<A: AllowedOptions> syn::parse::Parse for Options<A> {
                         "`lru` option not allowed here",
                     ));
                 }
+            } else if ident == "history" {
+                if A::HISTORY {
+                    let _eq = Equals::parse(input)?;
+                    let lit = syn::LitInt::parse(input)?;
+                    let value = lit.base10_parse::<usize>()?;
+                    if let Some(old) = std::mem::replace(&mut options.history, Some(value)) {
+                        return Err(syn::Error::new(
+                            old.span(),
+                            "option `history` provided twice",
+                        ));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`history` option not allowed here",
+                    ));
+                }
+            } else if ident == "durability" {
+                if A::DURABILITY {
+                    let _eq = Equals::parse(input)?;
+                    let expr = syn::Expr::parse(input)?;
+                    if let Some(old) = std::mem::replace(&mut options.durability, Some(expr)) {
+                        return Err(syn::Error::new(
+                            old.span(),
+                            "option `durability` provided twice",
+                        ));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`durability` option not allowed here",
+                    ));
+                }
             } else if ident == "constructor" {
                 if A::CONSTRUCTOR_NAME {
                     let _eq = Equals::parse(input)?;
@@ -241,6 +354,88 @@ impl<A: AllowedOptions> syn::parse::Parse for Options<A> {
                         "`constructor` option not allowed here",
                     ));
                 }
+            } else if ident == "debug" {
+                if A::DEBUG {
+                    if let Some(old) = std::mem::replace(&mut options.debug, Some(ident)) {
+                        return Err(syn::Error::new(old.span(), "option `debug` provided twice"));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`debug` option not allowed here",
+                    ));
+                }
+            } else if ident == "eq" {
+                if A::EQ {
+                    let _eq = Equals::parse(input)?;
+                    let path = syn::Path::parse(input)?;
+                    if let Some(old) = std::mem::replace(&mut options.eq, Some(path)) {
+                        return Err(syn::Error::new(old.span(), "option `eq` provided twice"));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`eq` option not allowed here",
+                    ));
+                }
+            } else if ident == "returns" {
+                if A::RETURNS {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    let mode = ReturnMode::parse(&content)?;
+                    if let Some((old, _)) =
+                        std::mem::replace(&mut options.returns, Some((ident, mode)))
+                    {
+                        return Err(syn::Error::new(
+                            old.span(),
+                            "option `returns` provided twice",
+                        ));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`returns` option not allowed here",
+                    ));
+                }
+            } else if ident == "serde" {
+                if A::SERDE {
+                    if let Some(old) = std::mem::replace(&mut options.serde, Some(ident)) {
+                        return Err(syn::Error::new(old.span(), "option `serde` provided twice"));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`serde` option not allowed here",
+                    ));
+                }
+            } else if ident == "shared" {
+                if A::SHARED {
+                    if let Some(old) = std::mem::replace(&mut options.shared, Some(ident)) {
+                        return Err(syn::Error::new(
+                            old.span(),
+                            "option `shared` provided twice",
+                        ));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`shared` option not allowed here",
+                    ));
+                }
+            } else if ident == "per_entry_deps" {
+                if A::PER_ENTRY_DEPS {
+                    if let Some(old) = std::mem::replace(&mut options.per_entry_deps, Some(ident)) {
+                        return Err(syn::Error::new(
+                            old.span(),
+                            "option `per_entry_deps` provided twice",
+                        ));
+                    }
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`per_entry_deps` option not allowed here",
+                    ));
+                }
             } else {
                 return Err(syn::Error::new(
                     ident.span(),
@@ -258,3 +453,65 @@ impl<A: AllowedOptions> syn::parse::Parse for Options<A> {
         Ok(options)
     }
 }
+
+/// How a tracked function or a field getter returns its value, selected with
+/// `returns(<mode>)`. For a value of type `V`:
+///
+/// * `clone` returns `V` (the default);
+/// * `ref` returns `&V` (the same as `return_ref`);
+/// * `deref` returns `&<V as Deref>::Target`, e.g. `&[T]` for a `Vec<T>`;
+/// * `as_ref` returns `<V as SalsaAsRef>::AsRef`, e.g. `Option<&T>` for an `Option<T>`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ReturnMode {
+    Clone,
+    Ref,
+    Deref,
+    AsRef,
+}
+
+impl ReturnMode {
+    /// The type returned for a value of type `ty` that lives for `db_lifetime`.
+    pub(crate) fn return_ty(self, db_lifetime: &syn::Lifetime, ty: &syn::Type) -> syn::Type {
+        match self {
+            ReturnMode::Clone => ty.clone(),
+            ReturnMode::Ref => parse_quote!(&#db_lifetime #ty),
+            ReturnMode::Deref => parse_quote!(&#db_lifetime <#ty as std::ops::Deref>::Target),
+            ReturnMode::AsRef => {
+                parse_quote!(<#ty as salsa::return_mode::SalsaAsRef>::AsRef<#db_lifetime>)
+            }
+        }
+    }
+
+    /// Converts `value`, an expression of type `&V`, into the returned value.
+    pub(crate) fn return_value(self, value: syn::Expr) -> syn::Expr {
+        match self {
+            ReturnMode::Clone => parse_quote!(std::clone::Clone::clone(#value)),
+            ReturnMode::Ref => value,
+            ReturnMode::Deref => parse_quote!(std::ops::Deref::deref(#value)),
+            ReturnMode::AsRef => parse_quote!(salsa::return_mode::SalsaAsRef::as_ref(#value)),
+        }
+    }
+}
+
+impl syn::parse::Parse for ReturnMode {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let ident = syn::Ident::parse_any(input)?;
+        if ident == "clone" {
+            Ok(ReturnMode::Clone)
+        } else if ident == "ref" {
+            Ok(ReturnMode::Ref)
+        } else if ident == "deref" {
+            Ok(ReturnMode::Deref)
+        } else if ident == "as_ref" {
+            Ok(ReturnMode::AsRef)
+        } else {
+            Err(syn::Error::new(
+                ident.span(),
+                format!(
+                    "unrecognized return mode `{}`, expected `clone`, `ref`, `deref` or `as_ref`",
+                    ident
+                ),
+            ))
+        }
+    }
+}
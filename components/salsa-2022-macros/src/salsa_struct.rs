@@ -26,9 +26,13 @@
 //!     * this could be optimized, particularly for interned fields
 
 use heck::ToUpperCamelCase;
-use proc_macro2::{Ident, Literal, Span};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use syn::spanned::Spanned;
 
-use crate::{configuration, options::Options};
+use crate::{
+    configuration,
+    options::{Options, ReturnMode},
+};
 
 pub(crate) struct SalsaStruct {
     args: Options<Self>,
@@ -53,7 +57,23 @@ impl crate::options::AllowedOptions for SalsaStruct {
 
     const LRU: bool = false;
 
+    const HISTORY: bool = true;
+
+    const DURABILITY: bool = true;
+
     const CONSTRUCTOR_NAME: bool = true;
+
+    const DEBUG: bool = true;
+
+    const EQ: bool = false;
+
+    const RETURNS: bool = false;
+
+    const SERDE: bool = true;
+
+    const SHARED: bool = true;
+
+    const PER_ENTRY_DEPS: bool = true;
 }
 
 const BANNED_FIELD_NAMES: &[&str] = &["from", "new"];
@@ -228,6 +248,7 @@ impl SalsaStruct {
                 let value_field_name = ef.name();
                 let value_field_ty = ef.ty();
                 let value_field_backdate = ef.is_backdate_field();
+                let value_field_eq = ef.eq.as_ref();
                 let config_name = syn::Ident::new(
                     &format!(
                         "__{}",
@@ -250,7 +271,7 @@ impl SalsaStruct {
                     ef.name(),
                 ));
 
-                let should_backdate_value_fn = configuration::should_backdate_value_fn(value_field_backdate);
+                let should_backdate_value_fn = configuration::should_backdate_value_fn(value_field_backdate, value_field_eq);
                 let item_impl: syn::ItemImpl = parse_quote! {
                     impl salsa::function::Configuration for #config_name {
                         type Jar = #jar_ty;
@@ -293,6 +314,122 @@ impl SalsaStruct {
         }
     }
 
+    /// Generate `impl salsa::DebugWithDb<DynDb> for Foo`, which shows the id
+    /// along with the value of each field (itself shown with the database, if possible).
+    ///
+    /// Returns `None` unless the user gave the `debug` option.
+    pub(crate) fn as_debug_with_db_impl(&self) -> Option<syn::ItemImpl> {
+        self.args.debug.as_ref()?;
+
+        let ident = self.id_ident();
+        let db_dyn_ty = self.db_dyn_ty();
+        let ident_string = ident.to_string();
+
+        let fields = self.all_fields().map(|field| -> proc_macro2::TokenStream {
+            let field_name_string = field.name().to_string();
+            let field_getter = field.get_name();
+            let field_ty = field.ty();
+            let (field_ty, field_value): (syn::Type, syn::Expr) = match field.return_mode() {
+                ReturnMode::Clone => (field_ty.clone(), parse_quote!(&self.#field_getter(_db))),
+                ReturnMode::Ref => (field_ty.clone(), parse_quote!(self.#field_getter(_db))),
+                mode @ (ReturnMode::Deref | ReturnMode::AsRef) => (
+                    mode.return_ty(&parse_quote!('_), field_ty),
+                    parse_quote!(&self.#field_getter(_db)),
+                ),
+            };
+            quote! {
+                .field(
+                    #field_name_string,
+                    &salsa::debug::helper::SalsaDebug::<#field_ty, #db_dyn_ty>::salsa_debug(#field_value, _db),
+                )
+            }
+        });
+
+        Some(parse_quote! {
+            impl salsa::DebugWithDb<#db_dyn_ty> for #ident {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>, _db: &#db_dyn_ty) -> std::fmt::Result {
+                    #[allow(unused_imports)]
+                    use salsa::debug::helper::Fallback;
+                    f.debug_struct(#ident_string)
+                        .field("[salsa id]", &self.0.as_u32())
+                        #(#fields)*
+                        .finish()
+                }
+            }
+        })
+    }
+
+    /// Generates a function that formats the data of this struct for `salsa::debug::dump`,
+    /// showing each of `fields` (read from the data, of type `data_ty`, with the
+    /// corresponding member of `members`) as `..` if it does not implement `Debug`.
+    pub(crate) fn data_dump_fmt_fn<'a>(
+        &self,
+        data_ty: &syn::Type,
+        fields: impl Iterator<Item = &'a SalsaField>,
+        members: Vec<syn::Member>,
+    ) -> syn::Expr {
+        let ident_string = self.id_ident().to_string();
+        let (field_names, field_tys): (Vec<_>, Vec<_>) = fields
+            .map(|field| (field.name().to_string(), field.ty()))
+            .unzip();
+        parse_quote! {
+            |__data: &#data_ty, __fmt: &mut std::fmt::Formatter<'_>| {
+                #[allow(unused_imports)]
+                use salsa::debug::helper::DumpFallback;
+                __fmt.debug_struct(#ident_string)
+                    #(
+                        .field(
+                            #field_names,
+                            salsa::debug::helper::DumpDebug::<#field_tys>::dump_debug(&__data.#members),
+                        )
+                    )*
+                    .finish()
+            }
+        }
+    }
+
+    /// Generate `impl salsa::serde::Serialize for Foo` and `impl salsa::serde::Deserialize for Foo`,
+    /// which write the field values and re-create the struct with its constructor.
+    ///
+    /// `field_values` are expressions giving a reference to the value of each field,
+    /// given the database `__db` as well as `__jar` and `__runtime`; `constructor_db`
+    /// is the database passed to the constructor, given the `&mut` database `__db`.
+    ///
+    /// Returns `None` unless the user gave the `serde` option.
+    pub(crate) fn serde_impls(
+        &self,
+        field_values: Vec<syn::Expr>,
+        constructor_db: syn::Expr,
+    ) -> Option<TokenStream> {
+        self.args.serde.as_ref()?;
+
+        let ident = self.id_ident();
+        let jar_ty = self.jar_ty();
+        let db_dyn_ty = self.db_dyn_ty();
+        let field_names = self.all_field_names();
+        let field_tys = self.all_field_tys();
+        let constructor_name = self.constructor_name();
+
+        Some(quote! {
+            impl salsa::serde::Serialize for #ident {
+                fn serialize<__S: salsa::serde::Serializer>(&self, __serializer: __S) -> Result<__S::Ok, __S::Error> {
+                    salsa::serde::serialize_struct::<#db_dyn_ty, Self, __S>(*self, __serializer, |__db, __fields| {
+                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+                        __fields.fields((#(#field_values,)*))
+                    })
+                }
+            }
+
+            impl<'de> salsa::serde::Deserialize<'de> for #ident {
+                fn deserialize<__D: salsa::serde::Deserializer<'de>>(__deserializer: __D) -> Result<Self, __D::Error> {
+                    salsa::serde::deserialize_struct::<#db_dyn_ty, Self, (#(#field_tys,)*), __D>(__deserializer, |__db, (#(#field_names,)*)| {
+                        Self::#constructor_name(#constructor_db, #(#field_names,)*)
+                    })
+                }
+            }
+        })
+    }
+
     /// Disallow `#[id]` attributes on the fields of this struct.
     ///
     /// If an `#[id]` field is found, return an error.
@@ -312,6 +449,164 @@ impl SalsaStruct {
 
         Ok(())
     }
+
+    /// Disallow `#[eq]` attributes on the fields of this struct,
+    /// whose values are never backdated.
+    ///
+    /// If an `#[eq]` field is found, return an error.
+    ///
+    /// # Parameters
+    ///
+    /// * `kind`, the attribute name (e.g., `input` or `interned`)
+    pub(crate) fn disallow_eq_fields(&self, kind: &str) -> syn::Result<()> {
+        for ef in self.all_fields() {
+            if ef.eq.is_some() {
+                return Err(syn::Error::new(
+                    ef.name().span(),
+                    format!("`#[eq]` cannot be used with `#[salsa::{kind}]`"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that `#[eq]` is only used on value fields that are not also `#[no_eq]`.
+    pub(crate) fn validate_eq_fields(&self) -> syn::Result<()> {
+        for ef in self.all_fields() {
+            if ef.eq.is_none() {
+                continue;
+            }
+            if ef.has_id_attr {
+                return Err(syn::Error::new(
+                    ef.name().span(),
+                    "`#[eq]` cannot be used with `#[id]`",
+                ));
+            }
+            if ef.has_no_eq_attr {
+                return Err(syn::Error::new(
+                    ef.name().span(),
+                    "`#[eq]` cannot be used with `#[no_eq]`",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The number of prior values to keep for each field, if the `history` option was given.
+    pub(crate) fn history_capacity(&self) -> Option<usize> {
+        self.args.history
+    }
+
+    /// True if the `shared` option was given.
+    pub(crate) fn is_shared(&self) -> bool {
+        self.args.shared.is_some()
+    }
+
+    /// The builder call that enables per-entry dependencies on the ingredient
+    /// storing the struct's data, if the `per_entry_deps` option was given.
+    pub(crate) fn per_entry_deps_call(&self) -> Option<TokenStream> {
+        self.args
+            .per_entry_deps
+            .as_ref()
+            .map(|_| quote! { .with_per_entry_deps() })
+    }
+
+    /// The durability of each field: given by its `#[durability]` attribute,
+    /// or else by the `durability` option, or else `LOW`.
+    /// See [`durability_tokens`] for the accepted forms.
+    pub(crate) fn all_field_durabilities(&self) -> Vec<TokenStream> {
+        self.all_fields()
+            .map(
+                |ef| match ef.durability.as_ref().or(self.args.durability.as_ref()) {
+                    Some(durability) => durability_tokens(durability),
+                    None => quote! { salsa::Durability::LOW },
+                },
+            )
+            .collect()
+    }
+
+    /// Disallow the `serde` option, which only makes sense for inputs and interned structs.
+    ///
+    /// # Parameters
+    ///
+    /// * `kind`, the attribute name (e.g., `tracked`)
+    pub(crate) fn disallow_serde(&self, kind: &str) -> syn::Result<()> {
+        if let Some(serde) = &self.args.serde {
+            return Err(syn::Error::new(
+                serde.span(),
+                format!("`serde` cannot be used with `#[salsa::{kind}]`"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Disallow the `shared` option, which only makes sense for interned structs.
+    ///
+    /// # Parameters
+    ///
+    /// * `kind`, the attribute name (e.g., `tracked` or `input`)
+    pub(crate) fn disallow_shared(&self, kind: &str) -> syn::Result<()> {
+        if let Some(shared) = &self.args.shared {
+            return Err(syn::Error::new(
+                shared.span(),
+                format!("`shared` cannot be used with `#[salsa::{kind}]`"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Disallow the `per_entry_deps` option, which only makes sense for interned and tracked structs.
+    ///
+    /// # Parameters
+    ///
+    /// * `kind`, the attribute name (e.g., `input`)
+    pub(crate) fn disallow_per_entry_deps(&self, kind: &str) -> syn::Result<()> {
+        if let Some(per_entry_deps) = &self.args.per_entry_deps {
+            return Err(syn::Error::new(
+                per_entry_deps.span(),
+                format!("`per_entry_deps` cannot be used with `#[salsa::{kind}]`"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Disallow the options that only make sense for inputs
+    /// (`history`, `durability`, and `#[durability]` on fields).
+    ///
+    /// # Parameters
+    ///
+    /// * `kind`, the attribute name (e.g., `tracked` or `interned`)
+    pub(crate) fn disallow_input_options(&self, kind: &str) -> syn::Result<()> {
+        if self.args.history.is_some() {
+            return Err(syn::Error::new(
+                self.id_ident().span(),
+                format!("`history` cannot be used with `#[salsa::{kind}]`"),
+            ));
+        }
+
+        if let Some(durability) = &self.args.durability {
+            return Err(syn::Error::new(
+                durability.span(),
+                format!("`durability` cannot be used with `#[salsa::{kind}]`"),
+            ));
+        }
+
+        for ef in self.all_fields() {
+            if ef.durability.is_some() {
+                return Err(syn::Error::new(
+                    ef.name().span(),
+                    format!("`#[durability]` cannot be used with `#[salsa::{kind}]`"),
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 pub(crate) const FIELD_OPTION_ATTRIBUTES: &[(&str, fn(&syn::Attribute, &mut SalsaField))] = &[
@@ -324,14 +619,43 @@ pub(crate) const FIELD_OPTION_ATTRIBUTES: &[(&str, fn(&syn::Attribute, &mut Sals
     ("set", |attr, ef| {
         ef.set_name = attr.parse_args().unwrap();
     }),
+    ("durability", |attr, ef| {
+        ef.durability = Some(attr.parse_args().unwrap());
+    }),
+    ("eq", |attr, ef| {
+        ef.eq = Some(attr.parse_args().unwrap());
+    }),
+    ("returns", |attr, ef| {
+        ef.returns = Some(attr.parse_args().unwrap());
+    }),
 ];
 
+/// The `salsa::Durability` given by a `durability` option or `#[durability]` attribute:
+/// `LOW`, `MEDIUM` or `HIGH`, an integer level, or any other expression of type `salsa::Durability`.
+fn durability_tokens(durability: &syn::Expr) -> TokenStream {
+    match durability {
+        syn::Expr::Path(syn::ExprPath {
+            qself: None, path, ..
+        }) if path.is_ident("LOW") || path.is_ident("MEDIUM") || path.is_ident("HIGH") => {
+            quote! { salsa::Durability::#path }
+        }
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(level),
+            ..
+        }) => quote! { salsa::Durability::new(#level) },
+        _ => quote! { #durability },
+    }
+}
+
 pub(crate) struct SalsaField {
     field: syn::Field,
 
     pub(crate) has_id_attr: bool,
     pub(crate) has_ref_attr: bool,
     pub(crate) has_no_eq_attr: bool,
+    pub(crate) durability: Option<syn::Expr>,
+    pub(crate) eq: Option<syn::Path>,
+    pub(crate) returns: Option<ReturnMode>,
     get_name: syn::Ident,
     set_name: syn::Ident,
 }
@@ -357,6 +681,9 @@ impl SalsaField {
             has_id_attr: false,
             has_ref_attr: false,
             has_no_eq_attr: false,
+            durability: None,
+            eq: None,
+            returns: None,
             get_name,
             set_name,
         };
@@ -370,6 +697,13 @@ impl SalsaField {
             }
         }
 
+        if result.has_ref_attr && result.returns.is_some() {
+            return Err(syn::Error::new(
+                field_name.span(),
+                "`#[return_ref]` and `#[returns]` cannot be used together",
+            ));
+        }
+
         Ok(result)
     }
 
@@ -393,9 +727,18 @@ impl SalsaField {
         &self.set_name
     }
 
-    /// Do you clone the value of this field? (True if it is not a ref field)
-    pub(crate) fn is_clone_field(&self) -> bool {
-        !self.has_ref_attr
+    /// How the getter returns the value of this field: given by its `#[returns]`
+    /// attribute, or by `#[return_ref]`, or else by clone.
+    pub(crate) fn return_mode(&self) -> ReturnMode {
+        if let Some(mode) = self.returns {
+            return mode;
+        }
+
+        if self.has_ref_attr {
+            return ReturnMode::Ref;
+        }
+
+        ReturnMode::Clone
     }
 
     /// Do you potentially backdate the value of this field? (True if it is not a no-eq field)
@@ -3,7 +3,7 @@ use syn::spanned::Spanned;
 use syn::{ReturnType, Token};
 
 use crate::configuration::{self, Configuration, CycleRecoveryStrategy};
-use crate::options::Options;
+use crate::options::{Options, ReturnMode};
 
 pub(crate) fn tracked(
     args: proc_macro::TokenStream,
@@ -40,6 +40,20 @@ fn tracked_fn(args: Args, item_fn: syn::ItemFn) -> syn::Result<TokenStream> {
         }
     }
 
+    if let (Some(no_eq), Some(_)) = (&args.no_eq, &args.eq) {
+        return Err(syn::Error::new(
+            no_eq.span(),
+            "`no_eq` and `eq` cannot be used together",
+        ));
+    }
+
+    if let (Some(return_ref), Some(_)) = (&args.return_ref, &args.returns) {
+        return Err(syn::Error::new(
+            return_ref.span(),
+            "`return_ref` and `returns` cannot be used together",
+        ));
+    }
+
     let struct_item = configuration_struct(&item_fn);
     let configuration = fn_configuration(&args, &item_fn);
     let struct_item_ident = &struct_item.ident;
@@ -82,7 +96,23 @@ impl crate::options::AllowedOptions for TrackedFn {
 
     const LRU: bool = true;
 
+    const HISTORY: bool = false;
+
+    const DURABILITY: bool = false;
+
     const CONSTRUCTOR_NAME: bool = false;
+
+    const DEBUG: bool = false;
+
+    const EQ: bool = true;
+
+    const RETURNS: bool = true;
+
+    const SERDE: bool = false;
+
+    const SHARED: bool = false;
+
+    const PER_ENTRY_DEPS: bool = false;
 }
 
 /// Returns the key type for this tracked function.
@@ -112,6 +142,7 @@ fn configuration_struct(item_fn: &syn::ItemFn) -> syn::ItemStruct {
 
     parse_quote! {
         #[allow(non_camel_case_types)]
+        #[derive(Clone)]
         #visibility struct #fn_name {
             intern_map: #intern_map,
             function: salsa::function::FunctionIngredient<Self>,
@@ -168,7 +199,8 @@ fn fn_configuration(args: &Args, item_fn: &syn::ItemFn) -> Configuration {
         (cycle_strategy, cycle_panic)
     };
 
-    let backdate_fn = configuration::should_backdate_value_fn(args.should_backdate());
+    let backdate_fn =
+        configuration::should_backdate_value_fn(args.should_backdate(), args.eq.as_ref());
 
     // The type of the configuration struct; this has the same name as the fn itself.
 
@@ -194,6 +226,36 @@ fn fn_configuration(args: &Args, item_fn: &syn::ItemFn) -> Configuration {
         }
     };
 
+    // Create the `fmt_key` function, which (a) maps from the interned id to the actual
+    // keys and then (b) formats each of them, with the database if possible.
+    let indices = (0..item_fn.sig.inputs.len() - 1).map(Literal::usize_unsuffixed);
+    let arg_tys = item_fn.sig.inputs.iter().skip(1).map(|arg| match arg {
+        syn::FnArg::Receiver(_) => unreachable!(),
+        syn::FnArg::Typed(pat_ty) => pat_ty.ty.clone(),
+    });
+    let separators = (0..item_fn.sig.inputs.len() - 1).map(|i| if i == 0 { "" } else { ", " });
+    let fmt_key_fn = parse_quote! {
+        fn fmt_key(__db: &salsa::function::DynDb<Self>, __id: Self::Key, __fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            #[allow(unused_imports)]
+            use salsa::debug::helper::Fallback;
+
+            let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+            let __ingredients =
+                <_ as salsa::storage::HasIngredientsFor<#fn_ty>>::ingredient(__jar);
+            let __key = __ingredients.intern_map.data(__runtime, __id);
+            __fmt.write_str("(")?;
+            #(
+                write!(
+                    __fmt,
+                    "{}{:?}",
+                    #separators,
+                    salsa::debug::helper::SalsaDebug::<#arg_tys, salsa::function::DynDb<Self>>::salsa_debug(&__key.#indices, __db),
+                )?;
+            )*
+            __fmt.write_str(")")
+        }
+    };
+
     Configuration {
         jar_ty,
         salsa_struct_ty,
@@ -203,6 +265,7 @@ fn fn_configuration(args: &Args, item_fn: &syn::ItemFn) -> Configuration {
         backdate_fn,
         execute_fn,
         recover_fn,
+        fmt_key_fn,
     }
 }
 
@@ -292,8 +355,10 @@ fn wrapper_fns(
 
     let ref_getter_fn = ref_getter_fn(args, item_fn, config_ty)?;
     let accumulated_fn = accumulated_fn(args, item_fn, config_ty)?;
+    let accumulated_diff_fn = accumulated_diff_fn(args, item_fn, config_ty)?;
     let setter_fn = setter_fn(args, item_fn, config_ty)?;
-    let specify_fn = specify_fn(args, item_fn, config_ty)?.map(|f| quote! { #f });
+    let specify_fns = specify_fns(args, item_fn, config_ty)?;
+    let subscription_fns = subscription_fns(args, item_fn, config_ty)?;
     let set_lru_fn = set_lru_capacity_fn(args, config_ty)?.map(|f| quote! { #f });
 
     let setter_impl: syn::ItemImpl = parse_quote! {
@@ -307,9 +372,17 @@ fn wrapper_fns(
             #[allow(dead_code, clippy::needless_lifetimes)]
             #accumulated_fn
 
+            #[allow(dead_code, clippy::needless_lifetimes)]
+            #accumulated_diff_fn
+
             #set_lru_fn
 
-            #specify_fn
+            #(#specify_fns)*
+
+            #(
+                #[allow(dead_code, clippy::needless_lifetimes)]
+                #subscription_fns
+            )*
         }
     };
 
@@ -323,6 +396,8 @@ fn getter_fn(
     config_ty: &syn::Type,
 ) -> syn::Result<syn::ItemFn> {
     let mut getter_fn = item_fn.clone();
+    // Report cycles closed by this call at the caller's location.
+    getter_fn.attrs.push(syn::parse_quote!(#[track_caller]));
     let arg_idents: Vec<_> = item_fn
         .sig
         .inputs
@@ -337,20 +412,16 @@ fn getter_fn(
             }
         })
         .collect::<Result<_, _>>()?;
-    if args.return_ref.is_some() {
-        getter_fn = make_fn_return_ref(getter_fn)?;
-        getter_fn.block = Box::new(parse_quote_spanned! {
-            item_fn.block.span() => {
-                #config_ty::get(#(#arg_idents,)*)
-            }
-        });
-    } else {
-        getter_fn.block = Box::new(parse_quote_spanned! {
-            item_fn.block.span() => {
-                Clone::clone(#config_ty::get(#(#arg_idents,)*))
-            }
-        });
+    let return_mode = args.return_mode();
+    if return_mode != ReturnMode::Clone {
+        getter_fn = make_fn_return_mode(getter_fn, return_mode)?;
     }
+    let value = return_mode.return_value(parse_quote!(#config_ty::get(#(#arg_idents,)*)));
+    getter_fn.block = Box::new(parse_quote_spanned! {
+        item_fn.block.span() => {
+            #value
+        }
+    });
     Ok(getter_fn)
 }
 
@@ -366,7 +437,8 @@ fn ref_getter_fn(
     let jar_ty = args.jar_ty();
     let mut ref_getter_fn = item_fn.clone();
     ref_getter_fn.sig.ident = syn::Ident::new("get", item_fn.sig.ident.span());
-    ref_getter_fn = make_fn_return_ref(ref_getter_fn)?;
+    ref_getter_fn.attrs.push(syn::parse_quote!(#[track_caller]));
+    ref_getter_fn = make_fn_return_mode(ref_getter_fn, ReturnMode::Ref)?;
 
     let (db_var, arg_names) = fn_args(item_fn)?;
     ref_getter_fn.block = parse_quote! {
@@ -457,17 +529,18 @@ fn set_lru_capacity_fn(
     Ok(Some(lru_fn))
 }
 
-fn specify_fn(
+fn specify_fns(
     args: &Args,
     item_fn: &syn::ItemFn,
     config_ty: &syn::Type,
-) -> syn::Result<Option<syn::ImplItemMethod>> {
+) -> syn::Result<Vec<syn::ImplItemMethod>> {
     if args.specify.is_none() {
-        return Ok(None);
+        return Ok(vec![]);
     }
 
     // `specify` has the same signature as the original,
     // but it takes a value arg and has no return type.
+    // `try_specify` is the same, but returns a `Result`.
     let jar_ty = args.jar_ty();
     let (db_var, arg_names) = fn_args(item_fn)?;
     let mut setter_sig = item_fn.sig.clone();
@@ -476,47 +549,66 @@ fn specify_fn(
     let value_arg = syn::Ident::new("__value", item_fn.sig.output.span());
     setter_sig.inputs.push(parse_quote!(#value_arg: #value_ty));
     setter_sig.output = ReturnType::Default;
-    Ok(Some(syn::ImplItemMethod {
-        attrs: vec![],
-        vis: item_fn.vis.clone(),
-        defaultness: None,
-        sig: setter_sig,
-        block: parse_quote! {
-            {
 
-                let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(#db_var);
-                let __ingredients = <_ as salsa::storage::HasIngredientsFor<#config_ty>>::ingredient(__jar);
-                __ingredients.function.specify_and_record(#db_var, #(#arg_names,)* #value_arg)
-            }
+    let mut try_setter_sig = setter_sig.clone();
+    try_setter_sig.ident = syn::Ident::new("try_specify", item_fn.sig.ident.span());
+    try_setter_sig.output = parse_quote!(-> Result<(), salsa::SpecifyError>);
+
+    Ok(vec![
+        syn::ImplItemMethod {
+            attrs: vec![],
+            vis: item_fn.vis.clone(),
+            defaultness: None,
+            sig: setter_sig,
+            block: parse_quote! {
+                {
+
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(#db_var);
+                    let __ingredients = <_ as salsa::storage::HasIngredientsFor<#config_ty>>::ingredient(__jar);
+                    __ingredients.function.specify_and_record(#db_var, #(#arg_names,)* #value_arg)
+                }
+            },
         },
-    }))
+        syn::ImplItemMethod {
+            attrs: vec![],
+            vis: item_fn.vis.clone(),
+            defaultness: None,
+            sig: try_setter_sig,
+            block: parse_quote! {
+                {
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(#db_var);
+                    let __ingredients = <_ as salsa::storage::HasIngredientsFor<#config_ty>>::ingredient(__jar);
+                    __ingredients.function.try_specify_and_record(#db_var, #(#arg_names,)* #value_arg)
+                }
+            },
+        },
+    ])
 }
-/// Given a function def tagged with `#[return_ref]`, modifies `ref_getter_fn`
-/// so that it returns an `&Value` instead of `Value`. May introduce a name for the
-/// database lifetime if required.
-fn make_fn_return_ref(mut ref_getter_fn: syn::ItemFn) -> syn::Result<syn::ItemFn> {
+
+/// Given a function def tagged with `return_ref` or `returns(<mode>)`, modifies
+/// `getter_fn` so that it returns the value as given by `return_mode` (e.g.,
+/// `&Value` instead of `Value`). May introduce a name for the database lifetime
+/// if required.
+fn make_fn_return_mode(
+    mut getter_fn: syn::ItemFn,
+    return_mode: ReturnMode,
+) -> syn::Result<syn::ItemFn> {
     // The 0th input should be a `&dyn Foo`. We need to ensure
     // it has a named lifetime parameter.
-    let (db_lifetime, _) = db_lifetime_and_ty(&mut ref_getter_fn)?;
+    let (db_lifetime, _) = db_lifetime_and_ty(&mut getter_fn)?;
 
-    let (right_arrow, elem) = match ref_getter_fn.sig.output {
+    let (right_arrow, elem) = match getter_fn.sig.output {
         ReturnType::Default => (
-            syn::Token![->](ref_getter_fn.sig.paren_token.span),
+            syn::Token![->](getter_fn.sig.paren_token.span),
             parse_quote!(()),
         ),
         ReturnType::Type(rarrow, ty) => (rarrow, ty),
     };
 
-    let ref_output = syn::TypeReference {
-        and_token: syn::Token![&](right_arrow.span()),
-        lifetime: Some(db_lifetime),
-        mutability: None,
-        elem,
-    };
-
-    ref_getter_fn.sig.output = syn::ReturnType::Type(right_arrow, Box::new(ref_output.into()));
+    let output = return_mode.return_ty(&db_lifetime, &elem);
+    getter_fn.sig.output = syn::ReturnType::Type(right_arrow, Box::new(output));
 
-    Ok(ref_getter_fn)
+    Ok(getter_fn)
 }
 
 /// Given an item function, identifies the name given to the `&dyn Db` reference and returns it,
@@ -601,6 +693,107 @@ fn accumulated_fn(
     Ok(accumulated_fn)
 }
 
+/// Generates the `accumulated_diff` function, which takes an extra
+/// `&mut AccumulatedTracker` argument and invokes `accumulated_diff`
+/// on the function ingredient to find the values added to and removed from
+/// an accumulator since the tracker was last used.
+fn accumulated_diff_fn(
+    args: &Args,
+    item_fn: &syn::ItemFn,
+    config_ty: &syn::Type,
+) -> syn::Result<syn::ItemFn> {
+    let jar_ty = args.jar_ty();
+
+    let mut accumulated_diff_fn = accumulated_fn(args, item_fn, config_ty)?;
+    accumulated_diff_fn.sig.ident = syn::Ident::new("accumulated_diff", item_fn.sig.ident.span());
+    accumulated_diff_fn.sig.inputs.push(parse_quote! {
+        __tracker: &mut salsa::accumulator::AccumulatedTracker<<__A as salsa::accumulator::Accumulator>::Data>
+    });
+    accumulated_diff_fn.sig.output = parse_quote! {
+        -> salsa::accumulator::AccumulatedDiff<<__A as salsa::accumulator::Accumulator>::Data>
+    };
+    // `accumulated_fn` always generates a where clause.
+    if let Some(where_clause) = &mut accumulated_diff_fn.sig.generics.where_clause {
+        where_clause
+            .predicates
+            .push(parse_quote!(<__A as salsa::accumulator::Accumulator>::Data: PartialEq));
+    }
+
+    let (db_var, arg_names) = fn_args(item_fn)?;
+    accumulated_diff_fn.block = parse_quote! {
+        {
+            let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(#db_var);
+            let __ingredients = <_ as salsa::storage::HasIngredientsFor<#config_ty>>::ingredient(__jar);
+            let __key = __ingredients.intern_map.intern(__runtime, (#(#arg_names),*));
+            __ingredients.function.accumulated_diff::<__A>(#db_var, __key, __tracker)
+        }
+    };
+
+    Ok(accumulated_diff_fn)
+}
+
+/// Generates the `subscribe` and `unsubscribe` functions, which take the same arguments
+/// as the tracked function, and `changed_subscriptions`, which returns the arguments
+/// and values of the subscribed calls whose value changed.
+fn subscription_fns(
+    args: &Args,
+    item_fn: &syn::ItemFn,
+    config_ty: &syn::Type,
+) -> syn::Result<Vec<syn::ImplItemMethod>> {
+    let jar_ty = args.jar_ty();
+    let (db_var, arg_names) = fn_args(item_fn)?;
+
+    let mut subscribe_fns = vec![];
+    for name in ["subscribe", "unsubscribe"] {
+        let method = syn::Ident::new(name, item_fn.sig.ident.span());
+        let mut sig = item_fn.sig.clone();
+        sig.ident = method.clone();
+        sig.output = ReturnType::Default;
+        subscribe_fns.push(syn::ImplItemMethod {
+            attrs: vec![],
+            vis: item_fn.vis.clone(),
+            defaultness: None,
+            sig,
+            block: parse_quote! {
+                {
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(#db_var);
+                    let __ingredients = <_ as salsa::storage::HasIngredientsFor<#config_ty>>::ingredient(__jar);
+                    let __key = __ingredients.intern_map.intern(__runtime, (#(#arg_names),*));
+                    __ingredients.function.#method(__key)
+                }
+            },
+        });
+    }
+
+    // Functions with a single salsa struct argument are keyed by it directly;
+    // the others are keyed by the interned tuple of their arguments.
+    let (args_ty, args_expr): (syn::Type, syn::Expr) = if requires_interning(item_fn) {
+        (
+            key_tuple_ty(item_fn),
+            parse_quote!(__ingredients.intern_map.data(__runtime, __key).clone()),
+        )
+    } else {
+        (salsa_struct_ty(item_fn).clone(), parse_quote!(__key))
+    };
+    let vis = &item_fn.vis;
+    subscribe_fns.push(parse_quote! {
+        #vis fn changed_subscriptions<'__db>(
+            __db: &'__db salsa::function::DynDb<'__db, Self>,
+        ) -> Vec<(#args_ty, &'__db <Self as salsa::function::Configuration>::Value)> {
+            let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+            let __ingredients = <_ as salsa::storage::HasIngredientsFor<#config_ty>>::ingredient(__jar);
+            __ingredients
+                .function
+                .changed_subscriptions(__db)
+                .into_iter()
+                .map(|(__key, __value)| (#args_expr, __value))
+                .collect()
+        }
+    });
+
+    Ok(subscribe_fns)
+}
+
 /// Examines the function arguments and returns a tuple of:
 ///
 /// * the name of the database argument
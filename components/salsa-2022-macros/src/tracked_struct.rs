@@ -41,7 +41,9 @@ impl TrackedStruct {
         let ingredients_for_impl = self.tracked_struct_ingredients(&config_structs);
         let salsa_struct_in_db_impl = self.salsa_struct_in_db_impl();
         let tracked_struct_in_db_impl = self.tracked_struct_in_db_impl();
+        let specifiable_in_db_impl = self.specifiable_in_db_impl();
         let as_id_impl = self.as_id_impl();
+        let as_debug_with_db_impl = self.as_debug_with_db_impl();
         Ok(quote! {
             #(#config_structs)*
             #id_struct
@@ -49,12 +51,18 @@ impl TrackedStruct {
             #ingredients_for_impl
             #salsa_struct_in_db_impl
             #tracked_struct_in_db_impl
+            #specifiable_in_db_impl
             #as_id_impl
+            #as_debug_with_db_impl
             #(#config_impls)*
         })
     }
 
     fn validate_tracked(&self) -> syn::Result<()> {
+        self.disallow_input_options("tracked")?;
+        self.disallow_serde("tracked")?;
+        self.disallow_shared("tracked")?;
+        self.validate_eq_fields()?;
         Ok(())
     }
 
@@ -69,59 +77,39 @@ impl TrackedStruct {
         let id_field_names: Vec<_> = self.id_fields().map(SalsaField::name).collect();
         let id_field_get_names: Vec<_> = self.id_fields().map(SalsaField::get_name).collect();
         let id_field_tys: Vec<_> = self.id_fields().map(SalsaField::ty).collect();
-        let id_field_clones: Vec<_> = self.id_fields().map(SalsaField::is_clone_field).collect();
-        let id_field_getters: Vec<syn::ImplItemMethod> = id_field_indices.iter().zip(&id_field_get_names).zip(&id_field_tys).zip(&id_field_clones).map(|(((field_index, field_get_name), field_ty), is_clone_field)|
-            if !*is_clone_field {
-                parse_quote! {
-                    pub fn #field_get_name<'db>(self, __db: &'db #db_dyn_ty) -> &'db #field_ty
-                    {
-                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
-                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                        &__ingredients.#struct_index.tracked_struct_data(__runtime, self).#field_index
-                    }
-                }
-            } else {
-                parse_quote! {
-                    pub fn #field_get_name<'db>(self, __db: &'db #db_dyn_ty) -> #field_ty
-                    {
-                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
-                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                        __ingredients.#struct_index.tracked_struct_data(__runtime, self).#field_index.clone()
-                    }
+        let id_field_return_modes: Vec<_> = self.id_fields().map(SalsaField::return_mode).collect();
+        let id_field_getters: Vec<syn::ImplItemMethod> = id_field_indices.iter().zip(&id_field_get_names).zip(&id_field_tys).zip(&id_field_return_modes).map(|(((field_index, field_get_name), field_ty), return_mode)| {
+            let return_ty = return_mode.return_ty(&parse_quote!('db), field_ty);
+            let value = return_mode.return_value(parse_quote!(&__ingredients.#struct_index.tracked_struct_data(__runtime, self).#field_index));
+            parse_quote! {
+                pub fn #field_get_name<'db>(self, __db: &'db #db_dyn_ty) -> #return_ty
+                {
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+                    let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
+                    #value
                 }
             }
-        )
+        })
         .collect();
 
         let value_field_indices = self.value_field_indices();
         let value_field_names: Vec<_> = self.value_fields().map(SalsaField::name).collect();
         let value_field_tys: Vec<_> = self.value_fields().map(SalsaField::ty).collect();
         let value_field_get_names: Vec<_> = self.value_fields().map(SalsaField::get_name).collect();
-        let value_field_clones: Vec<_> = self
-            .value_fields()
-            .map(SalsaField::is_clone_field)
-            .collect();
-        let value_field_getters: Vec<syn::ImplItemMethod> = value_field_indices.iter().zip(&value_field_get_names).zip(&value_field_tys).zip(&value_field_clones).map(|(((field_index, field_get_name), field_ty), is_clone_field)|
-            if !*is_clone_field {
-                parse_quote! {
-                    pub fn #field_get_name<'db>(self, __db: &'db #db_dyn_ty) -> &'db #field_ty
-                    {
-                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
-                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                        __ingredients.#field_index.fetch(__db, self)
-                    }
-                }
-            } else {
-                parse_quote! {
-                    pub fn #field_get_name<'db>(self, __db: &'db #db_dyn_ty) -> #field_ty
-                    {
-                        let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
-                        let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                        __ingredients.#field_index.fetch(__db, self).clone()
-                    }
+        let value_field_return_modes: Vec<_> =
+            self.value_fields().map(SalsaField::return_mode).collect();
+        let value_field_getters: Vec<syn::ImplItemMethod> = value_field_indices.iter().zip(&value_field_get_names).zip(&value_field_tys).zip(&value_field_return_modes).map(|(((field_index, field_get_name), field_ty), return_mode)| {
+            let return_ty = return_mode.return_ty(&parse_quote!('db), field_ty);
+            let value = return_mode.return_value(parse_quote!(__ingredients.#field_index.fetch(__db, self)));
+            parse_quote! {
+                pub fn #field_get_name<'db>(self, __db: &'db #db_dyn_ty) -> #return_ty
+                {
+                    let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
+                    let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
+                    #value
                 }
             }
-        )
+        })
         .collect();
 
         let all_field_names = self.all_field_names();
@@ -134,7 +122,7 @@ impl TrackedStruct {
                 {
                     let (__jar, __runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(__db);
                     let __ingredients = <#jar_ty as salsa::storage::HasIngredientsFor< #ident >>::ingredient(__jar);
-                    let __id = __ingredients.#struct_index.new_struct(__runtime, (#(#id_field_names,)*));
+                    let __id = __ingredients.#struct_index.new_struct(__db, (#(#id_field_names,)*));
                     #(
                         __ingredients.#value_field_indices.specify_field(__db, __id, #value_field_names);
                     )*
@@ -162,6 +150,15 @@ impl TrackedStruct {
         let config_struct_names = config_structs.iter().map(|s| &s.ident);
         let debug_name_struct = literal(self.id_ident());
         let debug_name_fields: Vec<_> = self.all_field_names().into_iter().map(literal).collect();
+        let id_field_members = (0..id_field_tys.len())
+            .map(|i| syn::Member::Unnamed(syn::Index::from(i)))
+            .collect();
+        let per_entry_deps_call = self.per_entry_deps_call();
+        let dump_fmt_fn = self.data_dump_fmt_fn(
+            &parse_quote!((#(#id_field_tys,)*)),
+            self.id_fields(),
+            id_field_members,
+        );
 
         parse_quote! {
             impl salsa::storage::IngredientsFor for #ident {
@@ -211,6 +208,8 @@ impl TrackedStruct {
                                 },
                             );
                             salsa::tracked_struct::TrackedStructIngredient::new(index, #debug_name_struct)
+                                .with_dump_fmt(#dump_fmt_fn)
+                                #per_entry_deps_call
                         },
                     )
                 }
@@ -256,6 +255,25 @@ impl TrackedStruct {
         }
     }
 
+    /// Implementation of `SpecifiableInDb`.
+    fn specifiable_in_db_impl(&self) -> syn::ItemImpl {
+        let ident = self.id_ident();
+        let jar_ty = self.jar_ty();
+        let tracked_struct_index = self.tracked_struct_index();
+        parse_quote! {
+            impl<DB> salsa::salsa_struct::SpecifiableInDb<DB> for #ident
+            where
+                DB: ?Sized + salsa::DbWithJar<#jar_ty>,
+            {
+                fn owned_by_active_query(self, db: &DB) -> bool {
+                    let (jar, runtime) = <_ as salsa::storage::HasJar<#jar_ty>>::jar(db);
+                    let ingredients = <#jar_ty as salsa::storage::HasIngredientsFor<#ident>>::ingredient(jar);
+                    ingredients.#tracked_struct_index.is_output_of_active_query(runtime, self)
+                }
+            }
+        }
+    }
+
     /// List of id fields (fields that are part of the tracked struct's identity across revisions).
     ///
     /// If this is an enum, empty iterator.
@@ -1,15 +1,16 @@
 //! Basic test of accumulator functionality.
 
-use std::fmt;
+use std::{fmt, io};
 
 use crate::{
     cycle::CycleRecoveryStrategy,
-    hash::FxDashMap,
+    debug::{dump_opaque, Dump, DumpFmt, DumpValue},
+    hash::{FxDashMap, FxIndexMap},
     ingredient::{fmt_index, Ingredient, IngredientRequiresReset},
     key::DependencyIndex,
     runtime::local_state::QueryOrigin,
     storage::HasJar,
-    DatabaseKeyIndex, Event, EventKind, IngredientIndex, Revision, Runtime,
+    DatabaseKeyIndex, EventKind, EventKinds, IngredientIndex, Revision, Runtime,
 };
 
 pub trait Accumulator {
@@ -20,26 +21,110 @@ pub trait Accumulator {
     where
         Db: ?Sized + HasJar<Self::Jar>;
 }
+#[derive(Clone)]
 pub struct AccumulatorIngredient<Data: Clone> {
     index: IngredientIndex,
     map: FxDashMap<DatabaseKeyIndex, AccumulatedValues<Data>>,
+
+    /// Formats the accumulated values for [`crate::debug::dump`].
+    fmt_data: DumpFmt<Data>,
+
     debug_name: &'static str,
 }
 
+#[derive(Clone)]
 struct AccumulatedValues<Data> {
+    /// The last revision in which the executor pushed `values` or was validated.
     produced_at: Revision,
+
+    /// The revision in which the executor executed and pushed `values`.
+    executed_at: Revision,
+
     values: Vec<Data>,
 }
 
+/// The values added to and removed from an accumulator since the previous call to
+/// [`FunctionIngredient::accumulated_diff`](`crate::function::FunctionIngredient::accumulated_diff`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccumulatedDiff<Data> {
+    pub added: Vec<Data>,
+    pub removed: Vec<Data>,
+}
+
+impl<Data> AccumulatedDiff<Data> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// Drops the values that were both removed and added (e.g. because a query executed
+    /// again and accumulated the same values), so that only the difference remains.
+    pub(crate) fn cancel_out(&mut self)
+    where
+        Data: PartialEq,
+    {
+        self.removed.retain(
+            |removed| match self.added.iter().position(|added| added == removed) {
+                Some(i) => {
+                    self.added.remove(i);
+                    false
+                }
+                None => true,
+            },
+        );
+    }
+}
+
+/// Remembers the queries a root query transitively depended on, and the values each
+/// accumulated, as of the last call to
+/// [`FunctionIngredient::accumulated_diff`](`crate::function::FunctionIngredient::accumulated_diff`).
+/// A tracker must always be used with the same root query.
+pub struct AccumulatedTracker<Data> {
+    pub(crate) root: Option<DatabaseKeyIndex>,
+    pub(crate) synced_at: Option<Revision>,
+    pub(crate) queries: FxIndexMap<DatabaseKeyIndex, TrackedQuery<Data>>,
+}
+
+pub(crate) struct TrackedQuery<Data> {
+    /// The queries this query depended on.
+    pub(crate) inputs: Vec<DatabaseKeyIndex>,
+
+    /// The revision in which this query executed and accumulated `values`, if it accumulated any.
+    pub(crate) executed_at: Option<Revision>,
+
+    pub(crate) values: Vec<Data>,
+}
+
+impl<Data> AccumulatedTracker<Data> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            synced_at: None,
+            queries: FxIndexMap::default(),
+        }
+    }
+}
+
+impl<Data> Default for AccumulatedTracker<Data> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<Data: Clone> AccumulatorIngredient<Data> {
     pub fn new(index: IngredientIndex, debug_name: &'static str) -> Self {
         Self {
             map: FxDashMap::default(),
             index,
+            fmt_data: dump_opaque,
             debug_name,
         }
     }
 
+    /// Sets the function used to format the accumulated values for [`crate::debug::dump`].
+    pub fn with_dump_fmt(self, fmt_data: DumpFmt<Data>) -> Self {
+        Self { fmt_data, ..self }
+    }
+
     fn dependency_index(&self) -> DependencyIndex {
         DependencyIndex {
             ingredient_index: self.index,
@@ -56,15 +141,22 @@ impl<Data: Clone> AccumulatorIngredient<Data> {
             }
         };
 
+        // Values accumulated while executing from scratch would duplicate the memoized ones.
+        if runtime.is_isolated() {
+            return;
+        }
+
         let mut accumulated_values = self.map.entry(active_query).or_insert(AccumulatedValues {
             values: vec![],
             produced_at: current_revision,
+            executed_at: current_revision,
         });
 
         // This is the first push in a new revision. Reset.
         if accumulated_values.produced_at != current_revision {
             accumulated_values.values.truncate(0);
             accumulated_values.produced_at = current_revision;
+            accumulated_values.executed_at = current_revision;
         }
 
         runtime.add_output(self.dependency_index());
@@ -87,6 +179,7 @@ impl<Data: Clone> AccumulatorIngredient<Data> {
             let AccumulatedValues {
                 values,
                 produced_at,
+                executed_at: _,
             } = v.value();
 
             if *produced_at == current_revision {
@@ -94,6 +187,20 @@ impl<Data: Clone> AccumulatorIngredient<Data> {
             }
         }
     }
+
+    /// Returns the revision in which `query` executed and pushed the values it
+    /// accumulated in the current revision, if it accumulated any.
+    pub(crate) fn executed_at(
+        &self,
+        runtime: &Runtime,
+        query: DatabaseKeyIndex,
+    ) -> Option<Revision> {
+        let current_revision = runtime.current_revision();
+        let v = self.map.get(&query)?;
+        // See `produced_by`.
+        runtime.report_untracked_read();
+        (v.produced_at == current_revision).then_some(v.executed_at)
+    }
 }
 
 impl<DB: ?Sized, Data> Ingredient<DB> for AccumulatorIngredient<Data>
@@ -135,13 +242,13 @@ where
     ) {
         assert!(stale_output_key.is_none());
         if self.map.remove(&executor).is_some() {
-            db.salsa_event(Event {
-                runtime_id: db.salsa_runtime().id(),
-                kind: EventKind::DidDiscardAccumulated {
-                    executor_key: executor,
-                    accumulator: self.dependency_index(),
-                },
-            })
+            db.salsa_runtime()
+                .report_event(db, EventKinds::DID_DISCARD_ACCUMULATED, || {
+                    EventKind::DidDiscardAccumulated {
+                        executor_key: executor,
+                        accumulator: self.dependency_index(),
+                    }
+                })
         }
     }
 
@@ -153,9 +260,36 @@ where
         panic!("unexpected call: accumulator is not registered as a dependent fn");
     }
 
+    fn debug_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_index(self.debug_name, index, fmt)
     }
+
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("accumulator", self.debug_name)?;
+        let mut executors: Vec<DatabaseKeyIndex> = self.map.iter().map(|e| *e.key()).collect();
+        executors.sort();
+        for executor in executors {
+            if let Some(accumulated) = self.map.get(&executor) {
+                let values: Vec<_> = accumulated
+                    .values
+                    .iter()
+                    .map(|value| DumpValue(value, self.fmt_data))
+                    .collect();
+                dump.entry(
+                    executor,
+                    format_args!(
+                        "produced_at={:?}, values={:?}",
+                        accumulated.produced_at, values
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<Data> IngredientRequiresReset for AccumulatorIngredient<Data>
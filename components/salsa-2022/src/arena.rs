@@ -0,0 +1,164 @@
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// The number of slots in the first chunk is `1 << FIRST_CHUNK_BITS`;
+/// each following chunk is twice as large as the one before.
+const FIRST_CHUNK_BITS: u32 = 5;
+
+/// Enough chunks to hold every `u32` index.
+const CHUNKS: usize = (u32::BITS + 1 - FIRST_CHUNK_BITS) as usize;
+
+/// An append-only arena of boxed values indexed by dense `u32` indices (the value of an id),
+/// whose lookups take no locks: a lookup is a bounds check and two pointer loads.
+///
+/// Slots are stored in chunks of increasing size that are allocated on first use
+/// and never moved, so references into the arena remain valid while values are added.
+/// Values can only be freed through `&mut self`, except via the unsafe [`Self::remove`].
+pub(crate) struct Arena<T> {
+    /// Chunk `c` holds the slots for indices `32 * (2^c - 1)..32 * (2^(c + 1) - 1)`;
+    /// a null pointer means that the chunk was not allocated yet.
+    chunks: [AtomicPtr<AtomicPtr<T>>; CHUNKS],
+
+    phantom: PhantomData<Box<T>>,
+}
+
+// The arena owns the values it stores and hands out shared references to them.
+unsafe impl<T: Send> Send for Arena<T> {}
+unsafe impl<T: Send + Sync> Sync for Arena<T> {}
+
+/// Returns the chunk containing `index` and the offset of `index` within it.
+fn locate(index: u32) -> (usize, usize) {
+    let n = u64::from(index) + (1 << FIRST_CHUNK_BITS);
+    let bit = u64::BITS - 1 - n.leading_zeros();
+    ((bit - FIRST_CHUNK_BITS) as usize, (n - (1 << bit)) as usize)
+}
+
+fn chunk_len(chunk: usize) -> usize {
+    1 << (chunk + FIRST_CHUNK_BITS as usize)
+}
+
+impl<T> Arena<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            chunks: std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            phantom: PhantomData,
+        }
+    }
+
+    fn slot(&self, index: u32) -> Option<&AtomicPtr<T>> {
+        let (chunk, offset) = locate(index);
+        let slots = self.chunks[chunk].load(Ordering::Acquire);
+        if slots.is_null() {
+            return None;
+        }
+        // Safety: an allocated chunk has `chunk_len(chunk)` slots and lives as long as `self`.
+        Some(unsafe { &*slots.add(offset) })
+    }
+
+    fn slot_or_allocate(&self, index: u32) -> &AtomicPtr<T> {
+        let (chunk, offset) = locate(index);
+        let mut slots = self.chunks[chunk].load(Ordering::Acquire);
+        if slots.is_null() {
+            let new_slots: Box<[AtomicPtr<T>]> = (0..chunk_len(chunk))
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect();
+            let new_slots = Box::into_raw(new_slots) as *mut AtomicPtr<T>;
+            slots = match self.chunks[chunk].compare_exchange(
+                ptr::null_mut(),
+                new_slots,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new_slots,
+                Err(existing) => {
+                    // Another thread allocated the chunk first.
+                    drop(unsafe { free_chunk(new_slots, chunk) });
+                    existing
+                }
+            };
+        }
+        // Safety: as in `slot`.
+        unsafe { &*slots.add(offset) }
+    }
+
+    /// Stores `value` at `index`, or gives it back if `index` is already occupied.
+    pub(crate) fn insert(&self, index: u32, value: Box<T>) -> Result<(), Box<T>> {
+        let value = Box::into_raw(value);
+        match self.slot_or_allocate(index).compare_exchange(
+            ptr::null_mut(),
+            value,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(unsafe { Box::from_raw(value) }),
+        }
+    }
+
+    pub(crate) fn get(&self, index: u32) -> Option<&T> {
+        let value = self.slot(index)?.load(Ordering::Acquire);
+        // Safety: values are only freed through `&mut self`, or by callers of `remove`,
+        // who must keep them alive for as long as `self` is shared.
+        unsafe { value.as_ref() }
+    }
+
+    /// Removes the value at `index`, if any.
+    ///
+    /// # Safety
+    ///
+    /// References to the value returned by [`Self::get`] may still be in use:
+    /// the caller must not drop the returned value until it has `&mut` access to the arena.
+    pub(crate) unsafe fn remove(&self, index: u32) -> Option<Box<T>> {
+        let value = self.slot(index)?.swap(ptr::null_mut(), Ordering::AcqRel);
+        (!value.is_null()).then(|| Box::from_raw(value))
+    }
+
+    /// Iterates over the stored values, in order of index.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        (0..CHUNKS).flat_map(move |chunk| {
+            let first_index = chunk_len(chunk) - chunk_len(0);
+            let slots = self.chunks[chunk].load(Ordering::Acquire);
+            let len = if slots.is_null() { 0 } else { chunk_len(chunk) };
+            (0..len).filter_map(move |offset| {
+                // Safety: as in `slot` and `get`.
+                let value = unsafe { (*slots.add(offset)).load(Ordering::Acquire).as_ref()? };
+                Some(((first_index + offset) as u32, value))
+            })
+        })
+    }
+
+    /// Frees all the values.
+    pub(crate) fn clear(&mut self) {
+        for (chunk, slots) in self.chunks.iter_mut().enumerate() {
+            let slots = std::mem::replace(slots.get_mut(), ptr::null_mut());
+            if !slots.is_null() {
+                // Safety: we have `&mut self`, so there are no references into the chunk.
+                let mut slots = unsafe { free_chunk(slots, chunk) };
+                for slot in slots.iter_mut() {
+                    let value = *slot.get_mut();
+                    if !value.is_null() {
+                        drop(unsafe { Box::from_raw(value) });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Takes back ownership of the slots of `chunk`, allocated by [`Arena::slot_or_allocate`].
+unsafe fn free_chunk<T>(slots: *mut AtomicPtr<T>, chunk: usize) -> Box<[AtomicPtr<T>]> {
+    Box::from_raw(ptr::slice_from_raw_parts_mut(slots, chunk_len(chunk)))
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Arena<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
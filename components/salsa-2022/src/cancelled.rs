@@ -1,14 +1,17 @@
 use std::{
     fmt,
     panic::{self, UnwindSafe},
+    sync::Arc,
 };
 
+use crossbeam::atomic::AtomicCell;
+
 /// A panic payload indicating that execution of a salsa query was cancelled.
 ///
 /// This can occur for a few reasons:
-/// *
-/// *
-/// *
+/// * a write to an input is pending and this runtime has read values that it may affect;
+/// * the runtime was blocked on another thread, and that thread panicked;
+/// * somebody explicitly cancelled this runtime through its [`CancellationToken`].
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Cancelled {
@@ -19,6 +22,10 @@ pub enum Cancelled {
     /// The query was blocked on another thread, and that thread panicked.
     #[non_exhaustive]
     PropagatedPanic,
+
+    /// The runtime was cancelled through its [`CancellationToken`].
+    #[non_exhaustive]
+    Requested,
 }
 
 impl Cancelled {
@@ -48,6 +55,7 @@ impl std::fmt::Display for Cancelled {
         let why = match self {
             Cancelled::PendingWrite => "pending write",
             Cancelled::PropagatedPanic => "propagated panic",
+            Cancelled::Requested => "explicit request",
         };
         f.write_str("cancelled because of ")?;
         f.write_str(why)
@@ -55,3 +63,30 @@ impl std::fmt::Display for Cancelled {
 }
 
 impl std::error::Error for Cancelled {}
+
+/// A handle that can be used to cancel one particular runtime (typically a snapshot)
+/// from any thread, without writing to the database.
+///
+/// Obtain it from [`Runtime::cancellation_token`](`crate::Runtime::cancellation_token`)
+/// before handing the snapshot to another thread. Once cancelled, the next query
+/// invoked on that runtime unwinds with [`Cancelled::Requested`].
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicCell<bool>>,
+}
+
+impl CancellationToken {
+    /// Requests cancellation of the runtime this token belongs to.
+    pub fn cancel(&self) {
+        self.cancelled.store(true);
+    }
+
+    /// True if [`Self::cancel`] has been invoked.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load()
+    }
+
+    pub(crate) fn reset(&self) {
+        self.cancelled.store(false);
+    }
+}
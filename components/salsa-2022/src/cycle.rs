@@ -1,6 +1,13 @@
 use crate::debug::DebugWithDb;
 use crate::{key::DatabaseKeyIndex, Database};
-use std::{panic::AssertUnwindSafe, sync::Arc};
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    panic::AssertUnwindSafe,
+    panic::Location,
+    sync::Arc,
+};
 
 /// Captures the participants of a cycle that occurred when executing a query.
 ///
@@ -17,16 +24,39 @@ use std::{panic::AssertUnwindSafe, sync::Arc};
 ///
 /// You can read more about cycle handling in
 /// the [salsa book](https://https://salsa-rs.github.io/salsa/cycles.html).
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///
+/// Two cycles compare equal if they have the same participants,
+/// wherever they were detected.
+#[derive(Debug, Clone)]
 pub struct Cycle {
     participants: CycleParticipants,
+
+    /// The [`CycleParticipant::key_debug`] text of each participant, captured when
+    /// the cycle was created so that the cycle can be displayed without a database.
+    participant_names: Arc<Vec<String>>,
+
+    /// Location of the call that closed the cycle.
+    location: &'static Location<'static>,
 }
 
 pub(crate) type CycleParticipants = Arc<Vec<DatabaseKeyIndex>>;
 
 impl Cycle {
-    pub(crate) fn new(participants: CycleParticipants) -> Self {
-        Self { participants }
+    /// Creates a cycle, rendering the key data of each participant (which reads from `db`).
+    pub(crate) fn new(
+        db: &dyn Database,
+        participants: CycleParticipants,
+        location: &'static Location<'static>,
+    ) -> Self {
+        let participant_names = participants
+            .iter()
+            .map(|&key| format!("{:?}", KeyWithData { key, db }))
+            .collect();
+        Self {
+            participants,
+            participant_names: Arc::new(participant_names),
+            location,
+        }
     }
 
     /// True if two `Cycle` values represent the same cycle.
@@ -57,6 +87,29 @@ impl Cycle {
         self.participants.iter().copied()
     }
 
+    /// Returns the location of the call that closed the cycle (i.e., the call to a tracked
+    /// function that was already executing). Typically this is a call in user code,
+    /// but it can point into salsa when the cycle was detected while validating
+    /// the dependencies of a memoized value.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns structured information about each participant in the cycle,
+    /// in the same order as [`Self::participant_keys`].
+    pub fn participants<DB: ?Sized + Database>(&self, db: &DB) -> Vec<CycleParticipant> {
+        let db = db.as_salsa_database();
+        self.participant_keys()
+            .zip(self.participant_names.iter())
+            .map(|(key, key_debug)| CycleParticipant {
+                key,
+                function_name: db.debug_name(key.ingredient_index),
+                key_debug: key_debug.clone(),
+                recovery_strategy: db.cycle_recovery_strategy(key.ingredient_index),
+            })
+            .collect()
+    }
+
     /// Returns a vector with the debug information for
     /// all the participants in the cycle.
     pub fn all_participants<DB: ?Sized + Database>(&self, db: &DB) -> Vec<String> {
@@ -103,6 +156,81 @@ impl Cycle {
     }
 }
 
+impl PartialEq for Cycle {
+    fn eq(&self, other: &Self) -> bool {
+        self.participants == other.participants
+    }
+}
+
+impl Eq for Cycle {}
+
+impl PartialOrd for Cycle {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cycle {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.participants.cmp(&other.participants)
+    }
+}
+
+impl Hash for Cycle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.participants.hash(state)
+    }
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cycle detected at {}: ", self.location)?;
+        for name in self.participant_names.iter() {
+            write!(f, "{} -> ", name)?;
+        }
+        match self.participant_names.first() {
+            Some(first) => f.write_str(first),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+/// Describes one participant in a [`Cycle`]; see [`Cycle::participants`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CycleParticipant {
+    /// The key of the query that participated in the cycle.
+    pub key: DatabaseKeyIndex,
+
+    /// Name of the tracked function that was executing.
+    pub function_name: &'static str,
+
+    /// The key rendered together with its data, e.g. `my_fn(MyInput { [salsa id]: 0, field: 22 })`.
+    /// Salsa structs are shown with their fields, and functions with multiple arguments
+    /// show each of their arguments.
+    pub key_debug: String,
+
+    /// How the function recovers from cycles.
+    pub recovery_strategy: CycleRecoveryStrategy,
+}
+
+impl fmt::Display for CycleParticipant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key_debug)
+    }
+}
+
+struct KeyWithData<'me> {
+    key: DatabaseKeyIndex,
+    db: &'me dyn Database,
+}
+
+impl fmt::Debug for KeyWithData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.db.fmt_key(self.key.into(), f)
+    }
+}
+
 /// Cycle recovery strategy: Is this query capable of recovering from
 /// a cycle that results from executing the function? If so, how?
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -114,7 +242,7 @@ pub enum CycleRecoveryStrategy {
     /// strategies.
     ///
     /// In the case of a failure due to a cycle, the panic
-    /// value will be XXX (FIXME).
+    /// value will be the [`Cycle`].
     Panic,
 
     /// Recovers from cycles by storing a sentinel value.
@@ -1,4 +1,4 @@
-use crate::{storage::HasJarsDyn, DebugWithDb, Event, Runtime};
+use crate::{storage::HasJarsDyn, DebugWithDb, Event, EventKinds, Runtime};
 
 pub trait Database: HasJarsDyn + AsSalsaDatabase {
     /// This function is invoked at key points in the salsa
@@ -11,7 +11,46 @@ pub trait Database: HasJarsDyn + AsSalsaDatabase {
         log::debug!("salsa_event: {:?}", event.debug(self));
     }
 
+    /// The kinds of events passed to [`Self::salsa_event`]: events of other kinds
+    /// are skipped without being constructed. This is called once, when the storage
+    /// of the database is created.
+    ///
+    /// By default, the [default kinds](`EventKinds::DEFAULT`) are reported.
+    fn salsa_event_kinds() -> EventKinds
+    where
+        Self: Sized,
+    {
+        EventKinds::DEFAULT
+    }
+
     fn salsa_runtime(&self) -> &Runtime;
+
+    /// Creates a handle to this database that is used to verify the dependencies
+    /// of memoized values on another thread. Returns `None` by default, in which
+    /// case dependencies are always verified sequentially.
+    ///
+    /// To enable parallel deep verification, implement this method much like
+    /// [`ParallelDatabase::snapshot`], but using [`crate::Storage::verification_snapshot`],
+    /// and set the number of worker threads with [`crate::Storage::set_verification_threads`].
+    /// Each worker verifies some of the inputs of a memo while the current thread
+    /// verifies them in order as usual; the workers only mark memoized values as
+    /// verified and never execute queries. Queries are claimed as usual, so each
+    /// one is verified only once.
+    fn snapshot_for_verification(&self) -> Option<Box<dyn Database + Send>> {
+        None
+    }
+
+    /// Creates a fork of this database in which a tracked function is executed from scratch
+    /// to check one of its memoized values, for differential verification
+    /// (see [`crate::Storage::set_differential_verification`]). The fork is dropped
+    /// once the value is checked, so the check does not change this database.
+    ///
+    /// Returns `None` by default, in which case enabling differential verification
+    /// panics on the first check. Implement it like a fork of the database,
+    /// but using [`crate::Storage::differential_fork`].
+    fn fork_for_differential_verification(&self) -> Option<Box<dyn Database>> {
+        None
+    }
 }
 
 /// Indicates a database that also supports parallel query
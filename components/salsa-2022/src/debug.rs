@@ -1,9 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
+    fmt, io,
     rc::Rc,
     sync::Arc,
 };
 
+use crate::{key::DependencyIndex, Database};
+
 pub trait DebugWithDb<Db: ?Sized> {
     fn debug<'me, 'db>(&'me self, db: &'me Db) -> DebugWith<'me, Db>
     where
@@ -159,3 +162,150 @@ where
         f.debug_list().entries(elements).finish()
     }
 }
+
+/// Writes a text dump of everything stored in `db` to `writer`: for each ingredient
+/// (in the order of their [`IngredientIndex`](`crate::IngredientIndex`)), its entries,
+/// such as the fields of each input with their durability and the revision in which
+/// they changed, or the memoized values of each tracked function with their dependencies.
+///
+/// Entries are sorted by id, so that dumps taken in two runs can be compared with `diff`
+/// to find where an incremental computation went wrong
+/// (provided that the runs create their inputs in the same order).
+/// Values are shown with their [`Debug`](`std::fmt::Debug`) impl, or as `..` if they have none.
+pub fn dump<Db>(db: &Db, mut writer: impl io::Write) -> io::Result<()>
+where
+    Db: ?Sized + Database,
+{
+    db.dump(&mut writer)
+}
+
+/// Where ingredients write their entries for [`dump`];
+/// see [`Ingredient::dump`](`crate::ingredient::Ingredient::dump`).
+pub struct Dump<'a> {
+    db: &'a dyn Database,
+    out: &'a mut dyn io::Write,
+
+    /// Index of the ingredient being dumped.
+    pub(crate) ingredient_index: usize,
+}
+
+impl<'a> Dump<'a> {
+    pub(crate) fn new(db: &'a dyn Database, out: &'a mut dyn io::Write) -> Self {
+        Dump {
+            db,
+            out,
+            ingredient_index: 0,
+        }
+    }
+
+    /// The database being dumped.
+    pub fn db(&self) -> &'a dyn Database {
+        self.db
+    }
+
+    /// Writes the header of the ingredient, e.g. `[3] tracked fn parse`.
+    pub fn ingredient(&mut self, kind: &str, debug_name: &str) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "[{}] {} {}",
+            self.ingredient_index, kind, debug_name
+        )
+    }
+
+    /// Writes the entry for `key`, e.g. `parse(0): verified_at=R2, ...`.
+    pub fn entry(
+        &mut self,
+        key: impl Into<DependencyIndex>,
+        fields: fmt::Arguments<'_>,
+    ) -> io::Result<()> {
+        let key = key.into().into_debug(self.db);
+        if fields.as_str() == Some("") {
+            writeln!(self.out, "    {:?}", key)
+        } else {
+            writeln!(self.out, "    {:?}: {}", key, fields)
+        }
+    }
+
+    /// Formats a list of keys, e.g. `[text(0), parse(1)]`.
+    pub fn keys(&self, keys: impl IntoIterator<Item = DependencyIndex>) -> impl fmt::Debug + 'a {
+        let db = self.db;
+        keys.into_iter()
+            .map(|key| key.into_debug(db))
+            .collect::<Vec<_>>()
+    }
+}
+
+/// Formats a value for [`dump`]; ingredients that store values without a
+/// [`Debug`](`std::fmt::Debug`) bound are given one of these by the generated code.
+pub type DumpFmt<T> = fn(&T, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+/// The [`DumpFmt`] for values that cannot be shown.
+pub fn dump_opaque<T>(_value: &T, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt.write_str("..")
+}
+
+/// Shows a value with a [`DumpFmt`].
+pub(crate) struct DumpValue<'a, T>(pub(crate) &'a T, pub(crate) DumpFmt<T>);
+
+impl<T> fmt::Debug for DumpValue<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.1)(self.0, f)
+    }
+}
+
+/// Helpers used by the generated code to format a value with the database
+/// if it implements [`DebugWithDb`], falling back to [`std::fmt::Debug`] otherwise.
+///
+/// This relies on inherent associated functions taking precedence over trait ones:
+/// `SalsaDebug::<T, Db>::salsa_debug` resolves to the inherent function when
+/// `T: DebugWithDb<Db>` and to [`Fallback::salsa_debug`] otherwise.
+/// It therefore only works when `T` is a concrete type.
+#[doc(hidden)]
+pub mod helper {
+    use super::{DebugWith, DebugWithDb};
+    use std::{fmt, marker::PhantomData};
+
+    pub trait Fallback<T: fmt::Debug, Db: ?Sized> {
+        fn salsa_debug<'a>(a: &'a T, _db: &Db) -> &'a dyn fmt::Debug {
+            a
+        }
+    }
+
+    impl<Everything, Db: ?Sized, T: fmt::Debug> Fallback<T, Db> for Everything {}
+
+    pub struct SalsaDebug<T, Db: ?Sized>(PhantomData<T>, PhantomData<Db>);
+
+    impl<T: DebugWithDb<Db>, Db: ?Sized> SalsaDebug<T, Db> {
+        #[allow(dead_code)]
+        pub fn salsa_debug<'a>(a: &'a T, db: &'a Db) -> DebugWith<'a, Db> {
+            a.debug(db)
+        }
+    }
+
+    /// Like [`Fallback`], but used to format values for [`super::dump`]:
+    /// `DumpDebug::<T>::dump_debug` shows values that do not implement [`fmt::Debug`] as `..`.
+    pub trait DumpFallback<T> {
+        fn dump_debug(_a: &T) -> &dyn fmt::Debug {
+            &Opaque
+        }
+    }
+
+    impl<Everything, T> DumpFallback<T> for Everything {}
+
+    pub struct DumpDebug<T>(PhantomData<T>);
+
+    impl<T: fmt::Debug> DumpDebug<T> {
+        #[allow(dead_code)]
+        pub fn dump_debug(a: &T) -> &dyn fmt::Debug {
+            a
+        }
+    }
+
+    struct Opaque;
+
+    impl fmt::Debug for Opaque {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("..")
+        }
+    }
+}
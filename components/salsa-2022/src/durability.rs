@@ -15,6 +15,10 @@
 /// frequently editing. Medium or high durabilities are used for
 /// configuration, the source from library crates, or other things
 /// that are unlikely to be edited.
+///
+/// There are three levels of durability by default. A database can be
+/// configured with more levels (see [`Storage::with_durabilities`](`crate::Storage::with_durabilities`)),
+/// which are created with [`Durability::new`] and are more durable than `HIGH`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Durability(u8);
 
@@ -35,14 +39,24 @@ impl Durability {
     /// Example: the standard library or something from crates.io
     pub const HIGH: Durability = Durability(2);
 
-    /// The maximum possible durability; equivalent to HIGH but
-    /// "conceptually" distinct (i.e., if we add more durability
-    /// levels, this could change).
-    pub(crate) const MAX: Durability = Self::HIGH;
+    /// The maximum possible durability, greater than or equal to every level
+    /// the database was configured with. Equivalent to the highest of those levels.
+    pub(crate) const MAX: Durability = Durability(u8::MAX);
 
-    /// Number of durability levels.
+    /// Default number of durability levels.
     pub(crate) const LEN: usize = 3;
 
+    /// The durability with the given `level`, where `0` is `LOW`, `1` is `MEDIUM` and `2` is `HIGH`.
+    ///
+    /// Levels above `2` must be less than the number of durability levels that the database
+    /// was configured with. This is checked as soon as the durability is used with the database:
+    /// [`Setter::with_durability`](`crate::setter::Setter::with_durability`), creating an input
+    /// whose fields default to it, and [`Storage::synthetic_write`](`crate::Storage::synthetic_write`)
+    /// panic if it is out of range.
+    pub const fn new(level: u8) -> Durability {
+        Durability(level)
+    }
+
     pub(crate) fn index(self) -> usize {
         self.0 as usize
     }
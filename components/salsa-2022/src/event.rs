@@ -1,7 +1,8 @@
 use crate::{
     debug::DebugWithDb, key::DatabaseKeyIndex, key::DependencyIndex, runtime::RuntimeId, Database,
+    Revision,
 };
-use std::fmt;
+use std::{fmt, ops, time::Duration};
 
 /// The `Event` struct identifies various notable things that can
 /// occur during salsa execution. Instances of this struct are given
@@ -65,6 +66,26 @@ pub enum EventKind {
         database_key: DatabaseKeyIndex,
     },
 
+    /// Indicates that this runtime has been blocked on another thread (with id
+    /// `other_runtime_id`) processing the given query (`database_key`) for
+    /// longer than the timeout given to [`crate::Storage::set_stall_warning_timeout`].
+    /// Reported at most once per [`EventKind::WillBlockOn`].
+    ///
+    /// The runtime stops waiting while the event is reported, so its query
+    /// stack is available to the event handler, and it then blocks again.
+    /// Use [`crate::Runtime::stall_monitor`] to find out what the blocked
+    /// runtimes are waiting for, and what the running ones are executing.
+    StillBlockedOn {
+        /// The id of the runtime we are blocked on.
+        other_runtime_id: RuntimeId,
+
+        /// The database-key for the affected value. Implements `Debug`.
+        database_key: DatabaseKeyIndex,
+
+        /// How long we have been blocked.
+        elapsed: Duration,
+    },
+
     /// Indicates that the function for this query will be executed.
     /// This is either because it has never executed before or because
     /// its inputs may be out of date.
@@ -100,6 +121,166 @@ pub enum EventKind {
         /// Accumulator that was accumulated into
         accumulator: DependencyIndex,
     },
+
+    /// A new revision has started, because the database is about to be modified
+    /// (e.g., an input is about to be set).
+    DidStartRevision {
+        /// The new current revision.
+        revision: Revision,
+    },
+
+    /// The value of an input field is about to be set.
+    ///
+    /// Executes before the new revision is started.
+    WillSetInput {
+        /// The field being set (an input field ingredient and the id of the input).
+        input: DependencyIndex,
+    },
+
+    /// A query created a tracked struct (or re-created one from an earlier revision,
+    /// in which case the struct keeps its id).
+    DidCreateTrackedStruct {
+        /// The tracked struct.
+        key: DatabaseKeyIndex,
+
+        /// The query that created it.
+        created_by: DatabaseKeyIndex,
+    },
+
+    /// A value was interned for the first time, so a new id was allocated for it.
+    DidInternValue {
+        /// The interned struct.
+        key: DatabaseKeyIndex,
+    },
+}
+
+/// A set of [`EventKind`]s.
+///
+/// A database declares the kinds of events it wants to receive by overriding
+/// [`Database::salsa_event_kinds`]; events of other kinds are never constructed.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EventKinds(u32);
+
+impl EventKinds {
+    /// No events.
+    pub const NONE: EventKinds = EventKinds(0);
+
+    /// [`EventKind::DidValidateMemoizedValue`].
+    pub const DID_VALIDATE_MEMOIZED_VALUE: EventKinds = EventKinds(1 << 0);
+    /// [`EventKind::WillBlockOn`].
+    pub const WILL_BLOCK_ON: EventKinds = EventKinds(1 << 1);
+    /// [`EventKind::StillBlockedOn`].
+    pub const STILL_BLOCKED_ON: EventKinds = EventKinds(1 << 2);
+    /// [`EventKind::WillExecute`].
+    pub const WILL_EXECUTE: EventKinds = EventKinds(1 << 3);
+    /// [`EventKind::WillCheckCancellation`].
+    pub const WILL_CHECK_CANCELLATION: EventKinds = EventKinds(1 << 4);
+    /// [`EventKind::WillDiscardStaleOutput`].
+    pub const WILL_DISCARD_STALE_OUTPUT: EventKinds = EventKinds(1 << 5);
+    /// [`EventKind::DidDiscard`].
+    pub const DID_DISCARD: EventKinds = EventKinds(1 << 6);
+    /// [`EventKind::DidDiscardAccumulated`].
+    pub const DID_DISCARD_ACCUMULATED: EventKinds = EventKinds(1 << 7);
+    /// [`EventKind::DidStartRevision`].
+    pub const DID_START_REVISION: EventKinds = EventKinds(1 << 8);
+    /// [`EventKind::WillSetInput`].
+    pub const WILL_SET_INPUT: EventKinds = EventKinds(1 << 9);
+    /// [`EventKind::DidCreateTrackedStruct`].
+    pub const DID_CREATE_TRACKED_STRUCT: EventKinds = EventKinds(1 << 10);
+    /// [`EventKind::DidInternValue`].
+    pub const DID_INTERN_VALUE: EventKinds = EventKinds(1 << 11);
+
+    /// The events reported by default: all but [`EventKind::DidStartRevision`],
+    /// [`EventKind::WillSetInput`], [`EventKind::DidCreateTrackedStruct`],
+    /// and [`EventKind::DidInternValue`], which must be requested explicitly.
+    pub const DEFAULT: EventKinds = EventKinds((1 << 8) - 1);
+
+    /// All events.
+    pub const ALL: EventKinds = EventKinds((1 << 12) - 1);
+
+    const NAMES: [&'static str; 12] = [
+        "DidValidateMemoizedValue",
+        "WillBlockOn",
+        "StillBlockedOn",
+        "WillExecute",
+        "WillCheckCancellation",
+        "WillDiscardStaleOutput",
+        "DidDiscard",
+        "DidDiscardAccumulated",
+        "DidStartRevision",
+        "WillSetInput",
+        "DidCreateTrackedStruct",
+        "DidInternValue",
+    ];
+
+    /// The set containing just the kind of `kind`.
+    pub fn of(kind: &EventKind) -> EventKinds {
+        match kind {
+            EventKind::DidValidateMemoizedValue { .. } => Self::DID_VALIDATE_MEMOIZED_VALUE,
+            EventKind::WillBlockOn { .. } => Self::WILL_BLOCK_ON,
+            EventKind::StillBlockedOn { .. } => Self::STILL_BLOCKED_ON,
+            EventKind::WillExecute { .. } => Self::WILL_EXECUTE,
+            EventKind::WillCheckCancellation => Self::WILL_CHECK_CANCELLATION,
+            EventKind::WillDiscardStaleOutput { .. } => Self::WILL_DISCARD_STALE_OUTPUT,
+            EventKind::DidDiscard { .. } => Self::DID_DISCARD,
+            EventKind::DidDiscardAccumulated { .. } => Self::DID_DISCARD_ACCUMULATED,
+            EventKind::DidStartRevision { .. } => Self::DID_START_REVISION,
+            EventKind::WillSetInput { .. } => Self::WILL_SET_INPUT,
+            EventKind::DidCreateTrackedStruct { .. } => Self::DID_CREATE_TRACKED_STRUCT,
+            EventKind::DidInternValue { .. } => Self::DID_INTERN_VALUE,
+        }
+    }
+
+    /// True if every kind in `other` is also in `self`.
+    pub const fn contains(self, other: EventKinds) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// True if `self` and `other` have a kind in common.
+    pub const fn intersects(self, other: EventKinds) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// The kinds in either `self` or `other`.
+    pub const fn union(self, other: EventKinds) -> EventKinds {
+        EventKinds(self.0 | other.0)
+    }
+
+    /// The kinds in `self` but not in `other`.
+    pub const fn difference(self, other: EventKinds) -> EventKinds {
+        EventKinds(self.0 & !other.0)
+    }
+}
+
+impl Default for EventKinds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl ops::BitOr for EventKinds {
+    type Output = EventKinds;
+
+    fn bitor(self, other: EventKinds) -> EventKinds {
+        self.union(other)
+    }
+}
+
+impl ops::BitOrAssign for EventKinds {
+    fn bitor_assign(&mut self, other: EventKinds) {
+        *self = self.union(other);
+    }
+}
+
+impl fmt::Debug for EventKinds {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Self::NAMES
+            .iter()
+            .enumerate()
+            .filter(|&(bit, _)| self.0 & (1 << bit) != 0)
+            .map(|(_, name)| name);
+        fmt.debug_set().entries(names).finish()
+    }
 }
 
 impl fmt::Debug for EventKind {
@@ -117,6 +298,16 @@ impl fmt::Debug for EventKind {
                 .field("other_runtime_id", other_runtime_id)
                 .field("database_key", database_key)
                 .finish(),
+            EventKind::StillBlockedOn {
+                other_runtime_id,
+                database_key,
+                elapsed,
+            } => fmt
+                .debug_struct("StillBlockedOn")
+                .field("other_runtime_id", other_runtime_id)
+                .field("database_key", database_key)
+                .field("elapsed", elapsed)
+                .finish(),
             EventKind::WillExecute { database_key } => fmt
                 .debug_struct("WillExecute")
                 .field("database_key", database_key)
@@ -141,6 +332,23 @@ impl fmt::Debug for EventKind {
                 .field("executor_key", executor_key)
                 .field("accumulator", accumulator)
                 .finish(),
+            EventKind::DidStartRevision { revision } => fmt
+                .debug_struct("DidStartRevision")
+                .field("revision", revision)
+                .finish(),
+            EventKind::WillSetInput { input } => fmt
+                .debug_struct("WillSetInput")
+                .field("input", input)
+                .finish(),
+            EventKind::DidCreateTrackedStruct { key, created_by } => fmt
+                .debug_struct("DidCreateTrackedStruct")
+                .field("key", key)
+                .field("created_by", created_by)
+                .finish(),
+            EventKind::DidInternValue { key } => fmt
+                .debug_struct("DidInternValue")
+                .field("key", key)
+                .finish(),
         }
     }
 }
@@ -163,6 +371,16 @@ where
                 .field("other_runtime_id", other_runtime_id)
                 .field("database_key", &database_key.debug(db))
                 .finish(),
+            EventKind::StillBlockedOn {
+                other_runtime_id,
+                database_key,
+                elapsed,
+            } => fmt
+                .debug_struct("StillBlockedOn")
+                .field("other_runtime_id", other_runtime_id)
+                .field("database_key", &database_key.debug(db))
+                .field("elapsed", elapsed)
+                .finish(),
             EventKind::WillExecute { database_key } => fmt
                 .debug_struct("WillExecute")
                 .field("database_key", &database_key.debug(db))
@@ -188,6 +406,23 @@ where
                 .field("executor_key", &executor_key.debug(db))
                 .field("accumulator", &accumulator.debug(db))
                 .finish(),
+            EventKind::DidStartRevision { revision } => fmt
+                .debug_struct("DidStartRevision")
+                .field("revision", revision)
+                .finish(),
+            EventKind::WillSetInput { input } => fmt
+                .debug_struct("WillSetInput")
+                .field("input", &input.debug(db))
+                .finish(),
+            EventKind::DidCreateTrackedStruct { key, created_by } => fmt
+                .debug_struct("DidCreateTrackedStruct")
+                .field("key", &key.debug(db))
+                .field("created_by", &created_by.debug(db))
+                .finish(),
+            EventKind::DidInternValue { key } => fmt
+                .debug_struct("DidInternValue")
+                .field("key", &key.debug(db))
+                .finish(),
         }
     }
 }
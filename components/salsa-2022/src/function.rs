@@ -1,16 +1,20 @@
-use std::{fmt, sync::Arc};
+use std::{any::Any, fmt, io, sync::Arc};
 
 use arc_swap::ArcSwap;
 use crossbeam::{atomic::AtomicCell, queue::SegQueue};
+use parking_lot::Mutex;
 
 use crate::{
     cycle::CycleRecoveryStrategy,
+    debug::Dump,
+    hash::{FxDashMap, FxHashSet},
     ingredient::{fmt_index, IngredientRequiresReset},
     jar::Jar,
     key::{DatabaseKeyIndex, DependencyIndex},
     runtime::local_state::QueryOrigin,
     salsa_struct::SalsaStructInDb,
-    Cycle, DbWithJar, Event, EventKind, Id, Revision,
+    tombstones::Tombstones,
+    Cycle, DbWithJar, EventKind, EventKinds, Id, Revision,
 };
 
 use super::{ingredient::Ingredient, routes::IngredientIndex, AsId};
@@ -19,6 +23,8 @@ mod accumulated;
 mod backdate;
 mod delete;
 mod diff_outputs;
+mod differential;
+mod dump;
 mod execute;
 mod fetch;
 mod inputs;
@@ -27,7 +33,11 @@ mod maybe_changed_after;
 mod memo;
 mod specify;
 mod store;
+mod subscribe;
 mod sync;
+mod verify_inputs;
+
+pub use specify::SpecifyError;
 
 /// Function ingredients are the "workhorse" of salsa.
 /// They are used for tracked functions, for the "value" fields of tracked structs, and for the fields of input structs.
@@ -36,7 +46,7 @@ mod sync;
 ///
 /// * the `fetch` method, which is invoked when the function is called by the user's code;
 ///   it will return a memoized value if one exists, or execute the function otherwise.
-/// * the `specify` method, which can only be used when the key is an entity created by the active query.
+/// * the `specify` method, which can only be used when the key is an entity created (or owned, if interned) by the active query.
 ///   It sets the value of the function imperatively, so that when later fetches occur, they'll return this value.
 /// * the `store` method, which can only be invoked with an `&mut` reference, and is to set input fields.
 pub struct FunctionIngredient<C: Configuration> {
@@ -68,13 +78,42 @@ pub struct FunctionIngredient<C: Configuration> {
     /// everytime and so forth.
     deleted_entries: SegQueue<ArcSwap<memo::Memo<C::Value>>>,
 
+    /// The values computed from scratch in the current revision for differential verification
+    /// (see [`crate::Storage::set_differential_verification`]). Cleared when a new revision starts.
+    from_scratch: FxDashMap<C::Key, Arc<memo::Memo<C::Value>>>,
+
     /// Set to true once we invoke `register_dependent_fn` for `C::SalsaStruct`.
     /// Prevents us from registering more than once.
     registered: AtomicCell<bool>,
 
+    /// Records keys whose salsa struct was deleted (with the `stale-id-checks` feature).
+    tombstones: Tombstones<C::Key>,
+
+    /// The keys that have been [subscribed](`Self::subscribe`) to.
+    subscriptions: Mutex<subscribe::Subscriptions>,
+
     debug_name: &'static str,
 }
 
+/// Cloning a function ingredient is how it is [forked](`crate::storage::Storage::fork`):
+/// the clone shares the memoized values with the original, but verifies them independently.
+impl<C: Configuration> Clone for FunctionIngredient<C> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            memo_map: self.memo_map.fork(),
+            sync_map: Default::default(),
+            lru: self.lru.clone(),
+            deleted_entries: Default::default(),
+            from_scratch: Default::default(),
+            registered: AtomicCell::new(self.registered.load()),
+            tombstones: self.tombstones.clone(),
+            subscriptions: Mutex::new(self.subscriptions.lock().clone()),
+            debug_name: self.debug_name,
+        }
+    }
+}
+
 pub trait Configuration {
     type Jar: for<'db> Jar<'db>;
 
@@ -89,7 +128,7 @@ pub trait Configuration {
     type Key: AsId;
 
     /// The value computed by the function.
-    type Value: fmt::Debug;
+    type Value: fmt::Debug + 'static;
 
     /// Determines whether this function can recover from being a participant in a cycle
     /// (and, if so, how).
@@ -115,6 +154,14 @@ pub trait Configuration {
     /// This invokes the recovery function given by the user.
     fn recover_from_cycle(db: &DynDb<Self>, cycle: &Cycle, key: Self::Key) -> Self::Value;
 
+    /// Formats the arguments that `key` stands for, as a parenthesized list
+    /// (e.g., `(MyInput { [salsa id]: 0, field: 22 })`). Used for diagnostics.
+    ///
+    /// The default implementation just shows the id of the key.
+    fn fmt_key(_db: &DynDb<Self>, key: Self::Key, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "({})", u32::from(key.as_id()))
+    }
+
     /// Given a salsa Id, returns the key. Convenience function to avoid
     /// having to type `<C::Key as AsId>::from_id`.
     fn key_from_id(id: Id) -> Self::Key {
@@ -146,7 +193,10 @@ where
             lru: Default::default(),
             sync_map: Default::default(),
             deleted_entries: Default::default(),
+            from_scratch: Default::default(),
             registered: Default::default(),
+            tombstones: Default::default(),
+            subscriptions: Default::default(),
             debug_name,
         }
     }
@@ -228,6 +278,17 @@ where
         self.origin(key)
     }
 
+    fn subtree_executed_at(&self, key_index: Id) -> Option<Revision> {
+        let key = C::key_from_id(key_index);
+        self.subtree_executed_at(key)
+    }
+
+    fn verify_from_scratch(&self, db: &DB, key_index: Id, value: &dyn Any) {
+        let key = C::key_from_id(key_index);
+        let value = value.downcast_ref::<C::Value>().unwrap();
+        self.compare_with_execution_from_scratch(db.as_jar_db(), key, value);
+    }
+
     fn mark_validated_output(
         &self,
         db: &DB,
@@ -251,19 +312,35 @@ where
 
     fn reset_for_new_revision(&mut self) {
         std::mem::take(&mut self.deleted_entries);
+        self.memo_map.reset_for_new_revision();
+        self.from_scratch.clear();
+    }
+
+    fn rollback_memos(&mut self, revision: Revision, restored: &FxHashSet<DependencyIndex>) {
+        self.memo_map.retain(|memo, verified_at| {
+            verified_at <= revision
+                || !memo
+                    .revisions
+                    .origin
+                    .inputs()
+                    .any(|input| restored.contains(&input))
+        });
     }
 
     fn salsa_struct_deleted(&self, db: &DB, id: crate::Id) {
         // Remove any data keyed by `id`, since `id` no longer
         // exists in this revision.
 
+        self.subscriptions.lock().shift_remove(&id);
         let id: C::Key = C::key_from_id(id);
+        self.tombstones
+            .record(id, db.salsa_runtime().current_revision());
         if let Some(origin) = self.delete_memo(id) {
             let key = self.database_key_index(id);
-            db.salsa_event(Event {
-                runtime_id: db.salsa_runtime().id(),
-                kind: EventKind::DidDiscard { key },
-            });
+            db.salsa_runtime()
+                .report_event(db, EventKinds::DID_DISCARD, || EventKind::DidDiscard {
+                    key,
+                });
 
             // Anything that was output by this memoized execution
             // is now itself stale.
@@ -273,9 +350,32 @@ where
         }
     }
 
+    fn debug_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_index(self.debug_name, index, fmt)
     }
+
+    fn fmt_key(
+        &self,
+        db: &DB,
+        index: Option<crate::Id>,
+        fmt: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match index {
+            Some(id) => {
+                fmt.write_str(self.debug_name)?;
+                C::fmt_key(db.as_jar_db(), C::key_from_id(id), fmt)
+            }
+            None => fmt_index(self.debug_name, index, fmt),
+        }
+    }
+
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        self.dump(dump)
+    }
 }
 
 impl<C> IngredientRequiresReset for FunctionIngredient<C>
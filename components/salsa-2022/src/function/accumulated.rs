@@ -1,5 +1,5 @@
 use crate::{
-    hash::FxHashSet,
+    hash::{FxHashSet, FxIndexMap},
     key::DependencyIndex,
     runtime::local_state::QueryOrigin,
     storage::{HasJar, HasJarsDyn},
@@ -7,7 +7,7 @@ use crate::{
 };
 
 use super::{Configuration, DynDb, FunctionIngredient};
-use crate::accumulator::Accumulator;
+use crate::accumulator::{AccumulatedDiff, AccumulatedTracker, Accumulator, TrackedQuery};
 
 impl<C> FunctionIngredient<C>
 where
@@ -36,6 +36,134 @@ where
         }
         result
     }
+
+    /// Returns the values that were added to and removed from `accumulator` by this query
+    /// and its transitive inputs since the last call with the same `tracker`
+    /// (on the first call, all the accumulated values are added).
+    ///
+    /// Only the queries that executed again since then have their values compared, and
+    /// the values are compared as multisets, so re-accumulating the same values is no change.
+    /// Backdating does not hide a re-executed query: verifying a memo records the last
+    /// revision in which anything it transitively depends on executed. If that is before
+    /// the last call, the whole subtree is skipped.
+    pub fn accumulated_diff<'db, A>(
+        &self,
+        db: &DynDb<'db, C>,
+        key: C::Key,
+        tracker: &mut AccumulatedTracker<A::Data>,
+    ) -> AccumulatedDiff<A::Data>
+    where
+        DynDb<'db, C>: HasJar<A::Jar>,
+        A: Accumulator,
+        A::Data: PartialEq,
+    {
+        self.fetch(db, key);
+
+        let root = self.database_key_index(key);
+        if let Some(tracked_root) = tracker.root {
+            assert_eq!(
+                tracked_root, root,
+                "accumulated values of a different query are already tracked"
+            );
+        }
+
+        let runtime = db.salsa_runtime();
+        let accumulator_ingredient = A::accumulator_ingredient(db);
+        let mut old_queries = std::mem::take(&mut tracker.queries);
+        let mut queries = FxIndexMap::default();
+        let mut diff = AccumulatedDiff {
+            added: vec![],
+            removed: vec![],
+        };
+        let mut stack = vec![root];
+        while let Some(query) = stack.pop() {
+            if queries.contains_key(&query) {
+                continue;
+            }
+
+            if let Some(synced_at) = tracker.synced_at {
+                let unchanged = db
+                    .subtree_executed_at(query)
+                    .is_some_and(|executed_at| executed_at <= synced_at);
+                if unchanged && old_queries.contains_key(&query) {
+                    carry_over(query, &mut old_queries, &mut queries);
+                    continue;
+                }
+            }
+
+            let executed_at = accumulator_ingredient.executed_at(runtime, query);
+            let values = match old_queries.swap_remove(&query) {
+                Some(old) if old.executed_at == executed_at => old.values,
+                old => {
+                    if let Some(old) = old {
+                        diff.removed.extend(old.values);
+                    }
+                    let mut values = vec![];
+                    accumulator_ingredient.produced_by(runtime, query, &mut values);
+                    diff.added.extend(values.iter().cloned());
+                    values
+                }
+            };
+
+            let inputs = query_inputs(db.origin(query));
+            stack.extend(inputs.iter().copied());
+            queries.insert(
+                query,
+                TrackedQuery {
+                    inputs,
+                    executed_at,
+                    values,
+                },
+            );
+        }
+
+        // The queries that are no longer depended upon.
+        for (_, old) in old_queries {
+            diff.removed.extend(old.values);
+        }
+
+        tracker.root = Some(root);
+        tracker.synced_at = Some(runtime.current_revision());
+        tracker.queries = queries;
+        diff.cancel_out();
+        diff
+    }
+}
+
+/// Moves `query` and everything it transitively depended on, as of the last diff,
+/// from `old_queries` to `queries`.
+fn carry_over<Data>(
+    query: DatabaseKeyIndex,
+    old_queries: &mut FxIndexMap<DatabaseKeyIndex, TrackedQuery<Data>>,
+    queries: &mut FxIndexMap<DatabaseKeyIndex, TrackedQuery<Data>>,
+) {
+    let mut stack = vec![query];
+    while let Some(query) = stack.pop() {
+        if let Some(old) = old_queries.swap_remove(&query) {
+            stack.extend(old.inputs.iter().copied());
+            queries.insert(query, old);
+        }
+    }
+}
+
+/// The queries among the dependencies recorded in `origin`.
+fn query_inputs(origin: Option<QueryOrigin>) -> Vec<DatabaseKeyIndex> {
+    match origin {
+        Some(QueryOrigin::Derived(edges)) | Some(QueryOrigin::DerivedUntracked(edges)) => edges
+            .inputs()
+            .iter()
+            .filter_map(|input| {
+                Some(DatabaseKeyIndex {
+                    ingredient_index: input.ingredient_index,
+                    key_index: input.key_index?,
+                })
+            })
+            .collect(),
+        None
+        | Some(QueryOrigin::Assigned(_))
+        | Some(QueryOrigin::BaseInput)
+        | Some(QueryOrigin::Field) => vec![],
+    }
 }
 
 /// The stack is used to execute a DFS across all the queries
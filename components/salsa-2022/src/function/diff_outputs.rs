@@ -1,6 +1,6 @@
 use crate::{
     key::DependencyIndex, runtime::local_state::QueryRevisions, storage::HasJarsDyn, Database,
-    DatabaseKeyIndex, Event, EventKind,
+    DatabaseKeyIndex, EventKind, EventKinds,
 };
 
 use super::{memo::Memo, Configuration, DynDb, FunctionIngredient};
@@ -45,14 +45,13 @@ where
     }
 
     fn report_stale_output(db: &DynDb<'_, C>, key: DatabaseKeyIndex, output: DependencyIndex) {
-        let runtime_id = db.salsa_runtime().id();
-        db.salsa_event(Event {
-            runtime_id,
-            kind: EventKind::WillDiscardStaleOutput {
-                execute_key: key,
-                output_key: output,
-            },
-        });
+        db.salsa_runtime()
+            .report_event(db, EventKinds::WILL_DISCARD_STALE_OUTPUT, || {
+                EventKind::WillDiscardStaleOutput {
+                    execute_key: key,
+                    output_key: output,
+                }
+            });
 
         db.remove_stale_output(key, output);
     }
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::{
+    debug::DebugWithDb,
+    runtime::{local_state::QueryOrigin, StampedValue},
+    Database,
+};
+
+use super::{memo::Memo, Configuration, DynDb, FunctionIngredient};
+
+impl<C> FunctionIngredient<C>
+where
+    C: Configuration,
+{
+    /// Checks the memoized or backdated value `value` of `key` against the value
+    /// obtained by executing the function from scratch in a throwaway fork of the database,
+    /// panicking if they differ.
+    /// See [`crate::Storage::set_differential_verification`].
+    pub(super) fn verify_from_scratch(&self, db: &DynDb<C>, key: C::Key, value: &C::Value) {
+        // Values assigned by other queries cannot be recomputed,
+        // and values that are not equal to themselves (`no_eq`) cannot be compared.
+        let recomputable = self.memo_map.get(key).is_some_and(|memo| {
+            matches!(
+                memo.revisions.origin,
+                QueryOrigin::Derived(_) | QueryOrigin::DerivedUntracked(_)
+            )
+        });
+        if !recomputable || !C::should_backdate_value(value, value) {
+            return;
+        }
+
+        let Some(fork) = db.fork_for_differential_verification() else {
+            panic!(
+                "differential verification is enabled, \
+                 but the database does not implement `fork_for_differential_verification`"
+            );
+        };
+        fork.verify_from_scratch(self.database_key_index(key), value);
+    }
+
+    /// Invoked on the ingredient of a fork of the database (see [`Self::verify_from_scratch`]):
+    /// executes the function from scratch in the fork, panicking if the result differs from `value`.
+    pub(super) fn compare_with_execution_from_scratch(
+        &self,
+        db: &DynDb<C>,
+        key: C::Key,
+        value: &C::Value,
+    ) {
+        let runtime = db.salsa_runtime();
+        let Some(from_scratch) = runtime.run_isolated(|| self.fetch_from_scratch(db, key).value)
+        else {
+            return;
+        };
+        if !C::should_backdate_value(value, from_scratch) {
+            panic!(
+                "differential verification failed for `{:?}`: \
+                 the memoized value is `{:?}`, but executing from scratch yields `{:?}`",
+                self.database_key_index(key).debug(db),
+                value,
+                from_scratch,
+            );
+        }
+    }
+
+    /// Fetches the value of `key` while executing from scratch: values assigned while
+    /// executing from scratch take precedence, other values assigned by other queries
+    /// are used as they are, and all other values are computed from scratch
+    /// (once per fork) without using or updating the memoized values.
+    pub(super) fn fetch_from_scratch(&self, db: &DynDb<C>, key: C::Key) -> StampedValue<&C::Value> {
+        let runtime = db.salsa_runtime();
+        let revision_now = runtime.current_revision();
+
+        let cached = self.from_scratch.get(&key).map(|memo| memo.clone());
+        if let Some(memo) = cached {
+            return self.value_from_scratch(&memo);
+        }
+
+        if let Some(memo) = self.memo_map.get(key) {
+            if let QueryOrigin::Assigned(_) | QueryOrigin::Field | QueryOrigin::BaseInput =
+                memo.revisions.origin
+            {
+                if let Some(value) = unsafe {
+                    // Unsafety invariant: memo is present in memo_map
+                    self.extend_memo_lifetime(&memo)
+                } {
+                    return memo.revisions.stamped_value(value);
+                }
+            }
+        }
+
+        let database_key_index = self.database_key_index(key);
+        if runtime.is_query_on_stack(database_key_index) {
+            runtime.abandon_isolated_execution();
+        }
+        let active_query = runtime.push_query(database_key_index);
+        let value = C::execute(db, key);
+        let revisions = active_query.pop(runtime);
+        let memo = Arc::new(Memo::new(Some(value), revision_now, revisions));
+        // If another thread got there first, keep its value, which may already be in use.
+        let memo = self.from_scratch.entry(key).or_insert(memo).clone();
+        self.value_from_scratch(&memo)
+    }
+
+    /// Records a value specified while executing from scratch.
+    pub(super) fn insert_from_scratch(&self, key: C::Key, memo: Memo<C::Value>) {
+        if let Some(old_memo) = self.from_scratch.insert(key, Arc::new(memo)) {
+            // There may be references to the old value out there; see `insert_memo`.
+            self.deleted_entries.push(ArcSwap::new(old_memo));
+        }
+    }
+
+    fn value_from_scratch(&self, memo: &Memo<C::Value>) -> StampedValue<&C::Value> {
+        let value = unsafe {
+            // Unsafety invariant: memo is present in `from_scratch` or `deleted_entries`,
+            // which are only cleared when a new revision starts (with `&mut self`).
+            self.extend_memo_lifetime(memo).unwrap()
+        };
+        memo.revisions.stamped_value(value)
+    }
+}
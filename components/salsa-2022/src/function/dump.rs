@@ -0,0 +1,41 @@
+use std::io;
+
+use crate::{debug::Dump, runtime::local_state::QueryOrigin, DebugWithDb};
+
+use super::{Configuration, FunctionIngredient};
+
+impl<C> FunctionIngredient<C>
+where
+    C: Configuration,
+{
+    pub(super) fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("function", self.debug_name)?;
+        let db = dump.db();
+        for (key, memo, verified_at) in self.memo_map.sorted() {
+            let value = match &memo.value {
+                Some(value) => format!("value={:?}, ", value),
+                None => String::new(),
+            };
+            let origin = match &memo.revisions.origin {
+                QueryOrigin::Assigned(by) => format!("Assigned({:?})", by.debug(db)),
+                QueryOrigin::Field => "Field".to_string(),
+                QueryOrigin::BaseInput => "BaseInput".to_string(),
+                QueryOrigin::Derived(_) => "Derived".to_string(),
+                QueryOrigin::DerivedUntracked(_) => "DerivedUntracked".to_string(),
+            };
+            dump.entry(
+                self.database_key_index(key),
+                format_args!(
+                    "{}verified_at={:?}, changed_at={:?}, durability={:?}, origin={}, deps={:?}",
+                    value,
+                    verified_at,
+                    memo.revisions.changed_at,
+                    memo.revisions.durability,
+                    origin,
+                    dump.keys(memo.revisions.origin.inputs()),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use crate::{
     debug::DebugWithDb,
     runtime::{local_state::ActiveQueryGuard, StampedValue},
-    Cycle, Database, Event, EventKind,
+    Cycle, Database, EventKind, EventKinds,
 };
 
 use super::{memo::Memo, Configuration, DynDb, FunctionIngredient};
@@ -33,11 +33,16 @@ where
 
         log::info!("{:?}: executing query", database_key_index);
 
-        db.salsa_event(Event {
-            runtime_id: runtime.id(),
-            kind: EventKind::WillExecute {
-                database_key: database_key_index.into(),
-            },
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "execute",
+            ingredient = self.debug_name,
+            key = database_key_index.key_index.as_u32(),
+        )
+        .entered();
+
+        runtime.report_event(db, EventKinds::WILL_EXECUTE, || EventKind::WillExecute {
+            database_key: database_key_index,
         });
 
         // Query was not previously executed, or value is potentially
@@ -12,16 +12,36 @@ impl<C> FunctionIngredient<C>
 where
     C: Configuration,
 {
+    #[track_caller]
     pub fn fetch(&self, db: &DynDb<C>, key: C::Key) -> &C::Value {
+        self.fetch_stamped(db, key).value
+    }
+
+    /// Like [`Self::fetch`], but also returns the durability and `changed_at` revision of the value.
+    #[track_caller]
+    pub(super) fn fetch_stamped(&self, db: &DynDb<C>, key: C::Key) -> StampedValue<&C::Value> {
         let runtime = db.salsa_runtime();
 
         runtime.unwind_if_revision_cancelled(db);
 
-        let StampedValue {
-            value,
-            durability,
-            changed_at,
-        } = self.compute_value(db, key);
+        if runtime.is_isolated() {
+            let value = self.fetch_from_scratch(db, key);
+            runtime.report_tracked_read(
+                self.database_key_index(key).into(),
+                value.durability,
+                value.changed_at,
+            );
+            return value;
+        }
+
+        let value = self.compute_value(db, key);
+
+        // Values that changed in this revision were just computed from the current inputs.
+        if value.changed_at < runtime.current_revision()
+            && runtime.sample_differential_verification()
+        {
+            self.verify_from_scratch(db, key, value.value);
+        }
 
         if let Some(evicted) = self.lru.record_use(key.as_id()) {
             self.evict(AsId::from_id(evicted));
@@ -29,17 +49,22 @@ where
 
         db.salsa_runtime().report_tracked_read(
             self.database_key_index(key).into(),
-            durability,
-            changed_at,
+            value.durability,
+            value.changed_at,
         );
 
         value
     }
 
     #[inline]
+    #[track_caller]
     fn compute_value(&self, db: &DynDb<C>, key: C::Key) -> StampedValue<&C::Value> {
         loop {
-            if let Some(value) = self.fetch_hot(db, key).or_else(|| self.fetch_cold(db, key)) {
+            // NB: not written with `or_else`, as `#[track_caller]` does not propagate through closures.
+            if let Some(value) = self.fetch_hot(db, key) {
+                return value;
+            }
+            if let Some(value) = self.fetch_cold(db, key) {
                 return value;
             }
         }
@@ -63,9 +88,11 @@ where
         None
     }
 
+    #[track_caller]
     fn fetch_cold(&self, db: &DynDb<C>, key: C::Key) -> Option<StampedValue<&C::Value>> {
         let runtime = db.salsa_runtime();
         let database_key_index = self.database_key_index(key);
+        self.tombstones.check(key);
 
         // Try to claim this query: if someone else has claimed it already, go back and start again.
         let _claim_guard = self
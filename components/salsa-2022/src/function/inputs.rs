@@ -1,4 +1,4 @@
-use crate::runtime::local_state::QueryOrigin;
+use crate::{runtime::local_state::QueryOrigin, Revision};
 
 use super::{Configuration, FunctionIngredient};
 
@@ -9,4 +9,10 @@ where
     pub(super) fn origin(&self, key: C::Key) -> Option<QueryOrigin> {
         self.memo_map.get(key).map(|m| m.revisions.origin.clone())
     }
+
+    pub(super) fn subtree_executed_at(&self, key: C::Key) -> Option<Revision> {
+        self.memo_map
+            .get(key)
+            .map(|m| self.memo_map.subtree_executed_at(key, &m))
+    }
 }
@@ -9,6 +9,15 @@ pub(super) struct Lru {
     set: Mutex<FxLinkedHashSet<Id>>,
 }
 
+impl Clone for Lru {
+    fn clone(&self) -> Self {
+        Self {
+            capacity: AtomicCell::new(self.capacity.load()),
+            set: Mutex::new(self.set.lock().clone()),
+        }
+    }
+}
+
 impl Lru {
     pub(super) fn record_use(&self, index: Id) -> Option<Id> {
         let capacity = self.capacity.load();
@@ -8,7 +8,6 @@ use crate::{
         local_state::{ActiveQueryGuard, QueryOrigin},
         StampedValue,
     },
-    storage::HasJarsDyn,
     Database, Revision, Runtime,
 };
 
@@ -40,10 +39,19 @@ where
             let memo_guard = self.memo_map.get(key);
             if let Some(memo) = &memo_guard {
                 if self.shallow_verify_memo(db, runtime, database_key_index, memo) {
+                    runtime
+                        .report_subtree_executed_at(self.memo_map.subtree_executed_at(key, memo));
                     return memo.revisions.changed_at > revision;
                 }
                 drop(memo_guard); // release the arc-swap guard before cold path
                 if let Some(mcs) = self.maybe_changed_after_cold(db, key, revision) {
+                    // The memo was verified or replaced by a new one; either way, our caller
+                    // (once this query is off the stack) may want to know when it last executed.
+                    if let Some(memo) = self.memo_map.get(key) {
+                        runtime.report_subtree_executed_at(
+                            self.memo_map.subtree_executed_at(key, &memo),
+                        );
+                    }
                     return mcs;
                 } else {
                     // We failed to claim, have to retry.
@@ -88,6 +96,11 @@ where
             return Some(old_memo.revisions.changed_at > revision);
         }
 
+        // Verification snapshots never execute queries, so the value may have changed.
+        if runtime.is_verify_only() {
+            return Some(true);
+        }
+
         // If inputs have changed, but we have an old value, we can re-execute.
         // It is possible the result will be equal to the old value and hence
         // backdated. In that case, although we will have computed a new memo,
@@ -111,7 +124,8 @@ where
         database_key_index: DatabaseKeyIndex,
         memo: &Memo<C::Value>,
     ) -> bool {
-        let verified_at = memo.verified_at.load();
+        let key = C::key_from_id(database_key_index.key_index);
+        let verified_at = self.memo_map.verified_at(key, memo);
         let revision_now = runtime.current_revision();
 
         log::debug!(
@@ -125,9 +139,15 @@ where
             return true;
         }
 
-        if memo.check_durability(runtime) {
+        if memo.check_durability(runtime, verified_at) {
             // No input of the suitable durability has changed since last verified.
-            memo.mark_as_verified(db.as_salsa_database(), runtime, database_key_index);
+            self.memo_map.mark_as_verified(
+                db.as_salsa_database(),
+                runtime,
+                key,
+                memo,
+                database_key_index,
+            );
             return true;
         }
 
@@ -161,6 +181,14 @@ where
             return true;
         }
 
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "deep_verify",
+            ingredient = self.debug_name,
+            key = database_key_index.key_index.as_u32(),
+        )
+        .entered();
+
         match &old_memo.revisions.origin {
             QueryOrigin::Assigned(_) => {
                 // If the value was assigneed by another query,
@@ -181,22 +209,27 @@ where
                 return false;
             }
             QueryOrigin::Derived(edges) => {
-                // Fully tracked inputs? Iterate over the inputs and check them, one by one.
-                //
-                // NB: It's important here that we are iterating the inputs in the order that
-                // they executed. It's possible that if the value of some input I0 is no longer
-                // valid, then some later input I1 might never have executed at all, so verifying
-                // it is still up to date is meaningless.
-                let last_verified_at = old_memo.verified_at.load();
-                for &input in edges.inputs().iter() {
-                    if db.maybe_changed_after(input, last_verified_at) {
-                        return false;
-                    }
+                // Fully tracked inputs? Check them, one by one (and possibly in parallel).
+                let key = C::key_from_id(database_key_index.key_index);
+                let last_verified_at = self.memo_map.verified_at(key, old_memo);
+                if !self.inputs_unchanged(db, edges, last_verified_at) {
+                    return false;
                 }
+                self.memo_map.record_inputs_executed_at(
+                    key,
+                    old_memo,
+                    active_query.inputs_executed_at(),
+                );
             }
         }
 
-        old_memo.mark_as_verified(db.as_salsa_database(), runtime, database_key_index);
+        self.memo_map.mark_as_verified(
+            db.as_salsa_database(),
+            runtime,
+            C::key_from_id(database_key_index.key_index),
+            old_memo,
+            database_key_index,
+        );
         true
     }
 }
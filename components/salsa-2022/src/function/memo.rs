@@ -1,11 +1,15 @@
 use std::sync::Arc;
 
 use arc_swap::{ArcSwap, Guard};
+use crossbeam::queue::SegQueue;
 use crossbeam_utils::atomic::AtomicCell;
+use dashmap::mapref::one::Ref;
 
 use crate::{
-    hash::FxDashMap, key::DatabaseKeyIndex, runtime::local_state::QueryRevisions, AsId, Event,
-    EventKind, Revision, Runtime,
+    hash::{FxDashMap, FxHasher},
+    key::DatabaseKeyIndex,
+    runtime::local_state::QueryRevisions,
+    AsId, EventKind, EventKinds, Revision, Runtime,
 };
 
 /// The memo map maps from a key of type `K` to the memoized value for that `K`.
@@ -13,12 +17,31 @@ use crate::{
 /// dependency information.
 pub(super) struct MemoMap<K: AsId, V> {
     map: FxDashMap<K, ArcSwap<Memo<V>>>,
+
+    /// If this map was [forked](`Self::fork`) from another one, the memos it started out sharing
+    /// with that map, along with the revision in which they were last verified in *this* database.
+    /// The `verified_at` and `subtree_executed_at` fields of a shared memo belong to the database
+    /// that created it, since the revisions of a fork and of its parent are unrelated once either
+    /// starts a new one.
+    inherited: Option<FxDashMap<K, InheritedMemo<V>>>,
+
+    /// Keys whose inherited memo has been replaced or removed; their entries
+    /// in `inherited` are only dropped once a new revision starts.
+    replaced: SegQueue<K>,
+}
+
+struct InheritedMemo<V> {
+    memo: Arc<Memo<V>>,
+    verified_at: AtomicCell<Revision>,
+    subtree_executed_at: AtomicCell<Revision>,
 }
 
 impl<K: AsId, V> Default for MemoMap<K, V> {
     fn default() -> Self {
         Self {
             map: Default::default(),
+            inherited: None,
+            replaced: Default::default(),
         }
     }
 }
@@ -27,21 +50,176 @@ impl<K: AsId, V> MemoMap<K, V> {
     /// Inserts the memo for the given key; (atomically) overwrites any previously existing memo.-
     #[must_use]
     pub(super) fn insert(&self, key: K, memo: Arc<Memo<V>>) -> Option<ArcSwap<Memo<V>>> {
+        self.record_replaced(key);
         self.map.insert(key, ArcSwap::from(memo))
     }
 
     /// Removes any existing memo for the given key.
     #[must_use]
     pub(super) fn remove(&self, key: K) -> Option<ArcSwap<Memo<V>>> {
+        self.record_replaced(key);
         self.map.remove(&key).map(|o| o.1)
     }
 
+    /// Other threads may still be verifying the inherited memo for `key`,
+    /// so its entry in `inherited` is kept until the next revision.
+    fn record_replaced(&self, key: K) {
+        if let Some(inherited) = &self.inherited {
+            if inherited.contains_key(&key) {
+                self.replaced.push(key);
+            }
+        }
+    }
+
+    /// Drops the inherited entries of memos that are no longer in the map.
+    pub(super) fn reset_for_new_revision(&mut self) {
+        if let Some(inherited) = &self.inherited {
+            while let Some(key) = self.replaced.pop() {
+                inherited.remove_if(&key, |_, entry| match self.map.get(&key) {
+                    Some(memo) => !Arc::ptr_eq(&entry.memo, &memo.load()),
+                    None => true,
+                });
+            }
+        }
+    }
+
+    /// The revision in which `memo`, the memo for `key`, was last verified in this database.
+    pub(super) fn verified_at(&self, key: K, memo: &Memo<V>) -> Revision {
+        match self.inherited_entry(key, memo) {
+            Some(entry) => entry.verified_at.load(),
+            None => memo.verified_at.load(),
+        }
+    }
+
+    /// The last revision in which `memo`, the memo for `key`, or one of the memos
+    /// it transitively depends on was created in this database (see [`Memo::subtree_executed_at`]).
+    pub(super) fn subtree_executed_at(&self, key: K, memo: &Memo<V>) -> Revision {
+        match self.inherited_entry(key, memo) {
+            Some(entry) => entry.subtree_executed_at.load(),
+            None => memo.subtree_executed_at.load(),
+        }
+    }
+
+    fn inherited_entry(
+        &self,
+        key: K,
+        memo: &Memo<V>,
+    ) -> Option<Ref<'_, K, InheritedMemo<V>, FxHasher>> {
+        self.inherited
+            .as_ref()?
+            .get(&key)
+            .filter(|entry| std::ptr::eq(Arc::as_ptr(&entry.memo), memo))
+    }
+
+    /// Records that one of the memos that `memo`, the memo for `key`, depends on
+    /// was created in `revision`; called when `memo` is verified by checking its inputs.
+    pub(super) fn record_inputs_executed_at(&self, key: K, memo: &Memo<V>, revision: Revision) {
+        let raise = |cell: &AtomicCell<Revision>| {
+            if cell.load() < revision {
+                cell.store(revision);
+            }
+        };
+        match self.inherited_entry(key, memo) {
+            Some(entry) => raise(&entry.subtree_executed_at),
+            None => raise(&memo.subtree_executed_at),
+        }
+    }
+
+    /// Mark `memo`, the memo for `key`, as having been verified in the `revision_now`,
+    /// which should be the current revision.
+    pub(super) fn mark_as_verified(
+        &self,
+        db: &dyn crate::Database,
+        runtime: &crate::Runtime,
+        key: K,
+        memo: &Memo<V>,
+        database_key_index: DatabaseKeyIndex,
+    ) {
+        runtime.report_event(db, EventKinds::DID_VALIDATE_MEMOIZED_VALUE, || {
+            EventKind::DidValidateMemoizedValue {
+                database_key: database_key_index,
+            }
+        });
+
+        match self.inherited_entry(key, memo) {
+            Some(entry) => entry.verified_at.store(runtime.current_revision()),
+            None => memo.verified_at.store(runtime.current_revision()),
+        }
+
+        // Also mark the outputs as verified
+        for output in memo.revisions.origin.outputs() {
+            db.mark_validated_output(database_key_index, output);
+        }
+    }
+
     /// Loads the current memo for `key_index`. This does not hold any sort of
     /// lock on the `memo_map` once it returns, so this memo could immediately
     /// become outdated if other threads store into the `memo_map`.
     pub(super) fn get(&self, key: K) -> Option<Guard<Arc<Memo<V>>>> {
         self.map.get(&key).map(|v| v.load())
     }
+
+    /// Removes the memos for which `f` returns false.
+    /// Requires `&mut self`, so there cannot be any references to the removed values.
+    /// `f` is given each memo along with the revision in which it was last verified.
+    pub(super) fn retain(&mut self, mut f: impl FnMut(&Memo<V>, Revision) -> bool) {
+        let inherited = &self.inherited;
+        self.map.retain(|key, memo| {
+            let memo = memo.load();
+            let verified_at = inherited
+                .as_ref()
+                .and_then(|inherited| inherited.get(key))
+                .filter(|entry| Arc::ptr_eq(&entry.memo, &memo))
+                .map_or_else(|| memo.verified_at.load(), |entry| entry.verified_at.load());
+            f(&memo, verified_at)
+        });
+    }
+
+    /// Returns the memos with the revision in which they were last verified,
+    /// sorted by key; used by [`crate::debug::dump`].
+    pub(super) fn sorted(&self) -> Vec<(K, Arc<Memo<V>>, Revision)> {
+        let mut memos: Vec<_> = self
+            .map
+            .iter()
+            .map(|entry| {
+                let memo = entry.value().load_full();
+                let verified_at = self.verified_at(*entry.key(), &memo);
+                (*entry.key(), memo, verified_at)
+            })
+            .collect();
+        memos.sort_by_key(|(key, ..)| key.as_id());
+        memos
+    }
+
+    /// Creates a copy of this map for a fork of the database. The copy shares the memos
+    /// themselves, but keeps track of when they were verified separately (see `inherited`).
+    pub(super) fn fork(&self) -> Self {
+        let inherited = FxDashMap::default();
+        let map = self
+            .map
+            .iter()
+            .map(|entry| {
+                let key = *entry.key();
+                let memo = entry.value().load_full();
+                let verified_at = AtomicCell::new(self.verified_at(key, &memo));
+                let subtree_executed_at = AtomicCell::new(self.subtree_executed_at(key, &memo));
+                inherited.insert(
+                    key,
+                    InheritedMemo {
+                        memo: memo.clone(),
+                        verified_at,
+                        subtree_executed_at,
+                    },
+                );
+                (key, ArcSwap::new(memo))
+            })
+            .collect();
+        Self {
+            map,
+            inherited: Some(inherited),
+            replaced: Default::default(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -50,9 +228,16 @@ pub(super) struct Memo<V> {
     pub(super) value: Option<V>,
 
     /// Last revision when this memo was verified; this begins
-    /// as the current revision.
+    /// as the current revision. Use [`MemoMap::verified_at`] to read it,
+    /// as forks of the database track this separately for the memos they share.
     pub(super) verified_at: AtomicCell<Revision>,
 
+    /// Last revision in which this memo, or one of the memos it transitively depends on,
+    /// was created (typically by executing the query). Begins as the current revision, and
+    /// is raised when verifying the memo finds that one of its inputs executed again.
+    /// Use [`MemoMap::subtree_executed_at`] to read it.
+    pub(super) subtree_executed_at: AtomicCell<Revision>,
+
     /// Revision information
     pub(super) revisions: QueryRevisions,
 }
@@ -62,42 +247,19 @@ impl<V> Memo<V> {
         Memo {
             value,
             verified_at: AtomicCell::new(revision_now),
+            subtree_executed_at: AtomicCell::new(revision_now),
             revisions,
         }
     }
-    /// True if this memo is known not to have changed based on its durability.
-    pub(super) fn check_durability(&self, runtime: &Runtime) -> bool {
+    /// True if this memo, last verified in `verified_at`, is known not to have changed based on its durability.
+    pub(super) fn check_durability(&self, runtime: &Runtime, verified_at: Revision) -> bool {
         let last_changed = runtime.last_changed_revision(self.revisions.durability);
-        let verified_at = self.verified_at.load();
         log::debug!(
             "check_durability(last_changed={:?} <= verified_at={:?}) = {:?}",
             last_changed,
-            self.verified_at,
+            verified_at,
             last_changed <= verified_at,
         );
         last_changed <= verified_at
     }
-
-    /// Mark memo as having been verified in the `revision_now`, which should
-    /// be the current revision.
-    pub(super) fn mark_as_verified(
-        &self,
-        db: &dyn crate::Database,
-        runtime: &crate::Runtime,
-        database_key_index: DatabaseKeyIndex,
-    ) {
-        db.salsa_event(Event {
-            runtime_id: runtime.id(),
-            kind: EventKind::DidValidateMemoizedValue {
-                database_key: database_key_index.into(),
-            },
-        });
-
-        self.verified_at.store(runtime.current_revision());
-
-        // Also mark the outputs as verified
-        for output in self.revisions.origin.outputs() {
-            db.mark_validated_output(database_key_index, output);
-        }
-    }
 }
@@ -1,40 +1,66 @@
-use crossbeam::atomic::AtomicCell;
+use std::fmt;
 
 use crate::{
     database::AsSalsaDatabase,
     runtime::local_state::{QueryOrigin, QueryRevisions},
-    tracked_struct::TrackedStructInDb,
+    salsa_struct::SpecifiableInDb,
     Database, DatabaseKeyIndex, DebugWithDb,
 };
 
 use super::{memo::Memo, Configuration, DynDb, FunctionIngredient};
 
+/// The reason a value could not be specified, returned by the generated `try_specify` functions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SpecifyError {
+    /// Values can only be specified from within a tracked function.
+    NoActiveQuery,
+
+    /// The key was not created (for tracked structs) or is not owned
+    /// (for interned structs) by the active query.
+    NotOwnedByActiveQuery,
+}
+
+impl fmt::Display for SpecifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecifyError::NoActiveQuery => f.write_str("can only use `set` with an active query"),
+            SpecifyError::NotOwnedByActiveQuery => {
+                f.write_str("can only use `set` on entities created during current query")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpecifyError {}
+
 impl<C> FunctionIngredient<C>
 where
     C: Configuration,
 {
     /// Specifies the value of the function for the given key.
     /// This is a way to imperatively set the value of a function.
-    /// It only works if the key is a tracked struct created in the current query.
+    /// It only works if the key is a tracked struct created in the current query
+    /// or an interned struct owned by the current query.
     pub(crate) fn specify<'db>(
         &self,
         db: &'db DynDb<'db, C>,
         key: C::Key,
         value: C::Value,
         origin: impl Fn(DatabaseKeyIndex) -> QueryOrigin,
-    ) where
-        C::Key: TrackedStructInDb<DynDb<'db, C>>,
+    ) -> Result<(), SpecifyError>
+    where
+        C::Key: SpecifiableInDb<DynDb<'db, C>>,
     {
         let runtime = db.salsa_runtime();
 
         let (active_query_key, current_deps) = match runtime.active_query() {
             Some(v) => v,
-            None => panic!("can only use `set` with an active query"),
+            None => return Err(SpecifyError::NoActiveQuery),
         };
 
-        let database_key_index = key.database_key_index(db);
-        if !runtime.is_output_of_active_query(database_key_index) {
-            panic!("can only use `set` on entities created during current query");
+        if !key.owned_by_active_query(db) {
+            return Err(SpecifyError::NotOwnedByActiveQuery);
         }
 
         // Subtle: we treat the "input" to a set query as if it were
@@ -55,6 +81,10 @@ where
         //
         // - a result that is verified in the current revision, because it was set, which will use the set value
         // - a result that is NOT verified and has untracked inputs, which will re-execute (and likely panic)
+        //
+        // Interned keys owned by C are expected to be obtained from C in the same way.
+        // If R interns E itself, without executing C, the value assigned by C
+        // is not verified in the current revision, so F(E) is re-executed instead.
 
         let revision = runtime.current_revision();
         let mut revisions = QueryRevisions {
@@ -63,19 +93,24 @@ where
             origin: origin(active_query_key),
         };
 
+        // Executing from scratch leaves the memoized values alone:
+        // the value is kept on the side, where `fetch_from_scratch` finds it.
+        if runtime.is_isolated() {
+            let memo = Memo::new(Some(value), revision, revisions);
+            self.insert_from_scratch(key, memo);
+            return Ok(());
+        }
+
         if let Some(old_memo) = self.memo_map.get(key) {
             self.backdate_if_appropriate(&old_memo, &mut revisions, &value);
-            self.diff_outputs(db, database_key_index, &old_memo, &revisions);
+            self.diff_outputs(db, self.database_key_index(key), &old_memo, &revisions);
         }
 
-        let memo = Memo {
-            value: Some(value),
-            verified_at: AtomicCell::new(revision),
-            revisions,
-        };
+        let memo = Memo::new(Some(value), revision, revisions);
 
         log::debug!("specify: about to add memo {:#?} for key {:?}", memo, key);
         self.insert_memo(db, key, memo);
+        Ok(())
     }
 
     /// Specify the value for `key` but do not record it is an output.
@@ -84,24 +119,44 @@ where
     /// so recording them as an explicit output (and checking them for validity, etc) is pure overhead.
     pub fn specify_field<'db>(&self, db: &'db DynDb<'db, C>, key: C::Key, value: C::Value)
     where
-        C::Key: TrackedStructInDb<DynDb<'db, C>>,
+        C::Key: SpecifiableInDb<DynDb<'db, C>>,
     {
-        self.specify(db, key, value, |_| QueryOrigin::Field);
+        if let Err(error) = self.specify(db, key, value, |_| QueryOrigin::Field) {
+            panic!("{error}");
+        }
     }
 
     /// Specify the value for `key` *and* record that we did so.
     /// Used for explicit calls to `specify`, but not needed for pre-declared tracked struct fields.
+    /// Panics if the value cannot be specified; see [`Self::try_specify_and_record`].
     pub fn specify_and_record<'db>(&self, db: &'db DynDb<'db, C>, key: C::Key, value: C::Value)
     where
-        C::Key: TrackedStructInDb<DynDb<'db, C>>,
+        C::Key: SpecifiableInDb<DynDb<'db, C>>,
+    {
+        if let Err(error) = self.try_specify_and_record(db, key, value) {
+            panic!("{error}");
+        }
+    }
+
+    /// Like [`Self::specify_and_record`], but returns an error instead of panicking
+    /// if the value cannot be specified.
+    pub fn try_specify_and_record<'db>(
+        &self,
+        db: &'db DynDb<'db, C>,
+        key: C::Key,
+        value: C::Value,
+    ) -> Result<(), SpecifyError>
+    where
+        C::Key: SpecifiableInDb<DynDb<'db, C>>,
     {
         self.specify(db, key, value, |database_key_index| {
             QueryOrigin::Assigned(database_key_index)
-        });
+        })?;
 
         // Record that the current query *specified* a value for this cell.
         let database_key_index = self.database_key_index(key);
         db.salsa_runtime().add_output(database_key_index.into());
+        Ok(())
     }
 
     /// Invoked when the query `executor` has been validated as having green inputs
@@ -133,6 +188,12 @@ where
         }
 
         let database_key_index = self.database_key_index(key);
-        memo.mark_as_verified(db.as_salsa_database(), runtime, database_key_index);
+        self.memo_map.mark_as_verified(
+            db.as_salsa_database(),
+            runtime,
+            key,
+            &memo,
+            database_key_index,
+        );
     }
 }
@@ -1,7 +1,5 @@
 use std::sync::Arc;
 
-use crossbeam::atomic::AtomicCell;
-
 use crate::{
     durability::Durability,
     runtime::local_state::{QueryOrigin, QueryRevisions},
@@ -21,21 +19,22 @@ where
         value: C::Value,
         durability: Durability,
     ) {
+        runtime.assert_valid_durability(durability);
         let revision = runtime.current_revision();
-        let memo = Memo {
-            value: Some(value),
-            verified_at: AtomicCell::new(revision),
-            revisions: QueryRevisions {
+        let memo = Memo::new(
+            Some(value),
+            revision,
+            QueryRevisions {
                 changed_at: revision,
                 durability,
                 origin: QueryOrigin::BaseInput,
             },
-        };
+        );
 
         if let Some(old_value) = self.memo_map.insert(key, Arc::new(memo)) {
             // NB: we don't have to store `old_value` into `deleted_entries` because we have `&mut self`.
             let durability = old_value.load().revisions.durability;
-            runtime.report_tracked_write(durability);
+            runtime.report_tracked_write(self.database_key_index(key), durability);
         }
     }
 }
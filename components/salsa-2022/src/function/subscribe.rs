@@ -0,0 +1,69 @@
+use crate::{hash::FxIndexMap, AsId, Id, Revision};
+
+use super::{Configuration, DynDb, FunctionIngredient};
+
+/// The keys of a function that have been [subscribed](`FunctionIngredient::subscribe`) to,
+/// in the order they were subscribed, each with the `changed_at` revision of the value
+/// last returned for it by [`FunctionIngredient::changed_subscriptions`]
+/// (`None` if no value has been returned yet).
+pub(super) type Subscriptions = FxIndexMap<Id, Option<Revision>>;
+
+impl<C> FunctionIngredient<C>
+where
+    C: Configuration,
+{
+    /// Registers interest in the value for `key`, which will be returned by the
+    /// next call to [`Self::changed_subscriptions`] and then whenever it changes.
+    /// Subscribing to a key twice does nothing.
+    pub fn subscribe(&self, key: C::Key) {
+        self.subscriptions.lock().entry(key.as_id()).or_insert(None);
+    }
+
+    /// Removes the subscription for `key`, if any.
+    pub fn unsubscribe(&self, key: C::Key) {
+        self.subscriptions.lock().shift_remove(&key.as_id());
+    }
+
+    /// Returns the subscribed keys whose value has changed since it was last returned
+    /// (or that have not been returned yet), along with their current value.
+    ///
+    /// Only the subscribed keys whose memoized value may be out of date are re-executed;
+    /// a key that is re-executed but yields an equal value (and is therefore backdated)
+    /// is not returned. This is meant to be called after a new revision,
+    /// outside of any tracked function.
+    pub fn changed_subscriptions<'db>(
+        &'db self,
+        db: &'db DynDb<'db, C>,
+    ) -> Vec<(C::Key, &'db C::Value)> {
+        // Don't hold the lock while executing: the function may subscribe to more keys.
+        let subscriptions: Vec<(Id, Option<Revision>)> = self
+            .subscriptions
+            .lock()
+            .iter()
+            .map(|(&id, &reported_at)| (id, reported_at))
+            .collect();
+
+        let mut changed = vec![];
+        for (id, reported_at) in subscriptions {
+            let key = C::key_from_id(id);
+            if let Some(reported_at) = reported_at {
+                if !self.maybe_changed_after(db, key, reported_at) {
+                    continue;
+                }
+            }
+
+            let value = self.fetch_stamped(db, key);
+            if reported_at.is_some_and(|reported_at| value.changed_at <= reported_at) {
+                continue;
+            }
+
+            // The key may have been unsubscribed in the meantime.
+            if let Some(entry) = self.subscriptions.lock().get_mut(&id) {
+                *entry = Some(value.changed_at);
+            }
+            changed.push((key, value.value));
+        }
+
+        changed
+    }
+}
@@ -21,35 +21,62 @@ struct SyncState {
 }
 
 impl SyncMap {
+    #[track_caller]
     pub(super) fn claim<'me>(
         &'me self,
         db: &'me dyn Database,
         database_key_index: DatabaseKeyIndex,
     ) -> Option<ClaimGuard<'me>> {
         let runtime = db.salsa_runtime();
-        match self.sync_map.entry(database_key_index.key_index) {
-            dashmap::mapref::entry::Entry::Vacant(entry) => {
-                entry.insert(SyncState {
-                    id: runtime.id(),
-                    anyone_waiting: AtomicBool::new(false),
-                });
-                Some(ClaimGuard {
-                    database_key: database_key_index,
-                    runtime,
-                    sync_map: &self.sync_map,
-                })
+        if runtime.snapshots_disabled() {
+            // No other runtime can claim the query, so the map is not needed:
+            // if the query is already claimed, it is on our own stack, which is a cycle.
+            if runtime.is_query_on_stack(database_key_index) {
+                runtime.unwind_cycle(db, database_key_index);
             }
-            dashmap::mapref::entry::Entry::Occupied(entry) => {
-                // NB: `Ordering::Relaxed` is sufficient here,
-                // as there are no loads that are "gated" on this
-                // value. Everything that is written is also protected
-                // by a lock that must be acquired. The role of this
-                // boolean is to decide *whether* to acquire the lock,
-                // not to gate future atomic reads.
-                entry.get().anyone_waiting.store(true, Ordering::Relaxed);
-                let other_id = entry.get().id;
-                runtime.block_on_or_unwind(db, database_key_index, other_id, entry);
-                None
+            return Some(ClaimGuard {
+                database_key: database_key_index,
+                runtime,
+                sync_map: None,
+            });
+        }
+
+        let mut blocked_since = None;
+        loop {
+            match self.sync_map.entry(database_key_index.key_index) {
+                dashmap::mapref::entry::Entry::Vacant(entry) if blocked_since.is_none() => {
+                    entry.insert(SyncState {
+                        id: runtime.id(),
+                        anyone_waiting: AtomicBool::new(false),
+                    });
+                    return Some(ClaimGuard {
+                        database_key: database_key_index,
+                        runtime,
+                        sync_map: Some(&self.sync_map),
+                    });
+                }
+                // The query completed while we were reporting that we are still
+                // blocked on it; the caller will find the new memo.
+                dashmap::mapref::entry::Entry::Vacant(_) => return None,
+                dashmap::mapref::entry::Entry::Occupied(entry) => {
+                    // NB: `Ordering::Relaxed` is sufficient here,
+                    // as there are no loads that are "gated" on this
+                    // value. Everything that is written is also protected
+                    // by a lock that must be acquired. The role of this
+                    // boolean is to decide *whether* to acquire the lock,
+                    // not to gate future atomic reads.
+                    entry.get().anyone_waiting.store(true, Ordering::Relaxed);
+                    let other_id = entry.get().id;
+                    // Once the wait is over, the caller retries; if we only stopped
+                    // waiting to report a stall, we block again.
+                    blocked_since = Some(runtime.block_on_or_unwind(
+                        db,
+                        database_key_index,
+                        other_id,
+                        entry,
+                        blocked_since,
+                    )?);
+                }
             }
         }
     }
@@ -61,13 +88,18 @@ impl SyncMap {
 pub(super) struct ClaimGuard<'me> {
     database_key: DatabaseKeyIndex,
     runtime: &'me Runtime,
-    sync_map: &'me FxDashMap<Id, SyncState>,
+
+    /// `None` in `no_snapshots` databases, where claims are not recorded.
+    sync_map: Option<&'me FxDashMap<Id, SyncState>>,
 }
 
 impl<'me> ClaimGuard<'me> {
     fn remove_from_map_and_unblock_queries(&self, wait_result: WaitResult) {
+        let Some(sync_map) = self.sync_map else {
+            return;
+        };
         let (_, SyncState { anyone_waiting, .. }) =
-            self.sync_map.remove(&self.database_key.key_index).unwrap();
+            sync_map.remove(&self.database_key.key_index).unwrap();
 
         // NB: `Ordering::Relaxed` is sufficient here,
         // see `store` above for explanation.
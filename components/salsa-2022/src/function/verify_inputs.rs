@@ -0,0 +1,125 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crossbeam::sync::WaitGroup;
+
+use crate::{
+    database::AsSalsaDatabase,
+    key::DependencyIndex,
+    runtime::{local_state::QueryEdges, VerificationPool},
+    Database, Revision,
+};
+
+use super::{Configuration, DynDb, FunctionIngredient};
+
+/// Each verification worker is given at least this many inputs: for memos with fewer
+/// inputs, handing out the work costs more than it saves, so they are verified sequentially.
+const MIN_INPUTS_PER_WORKER: usize = 4;
+
+impl<C> FunctionIngredient<C>
+where
+    C: Configuration,
+{
+    /// True if none of the inputs in `edges` has changed since `last_verified_at`.
+    ///
+    /// The inputs are checked in order on the current thread. If parallel verification
+    /// is enabled (see [`Database::snapshot_for_verification`]) and there are enough inputs,
+    /// the later inputs are also verified concurrently by worker threads, so that by the
+    /// time we get to them they have (ideally) been verified already.
+    pub(super) fn inputs_unchanged(
+        &self,
+        db: &DynDb<C>,
+        edges: &QueryEdges,
+        last_verified_at: Revision,
+    ) -> bool {
+        let inputs = edges.inputs();
+        let wanted = inputs.len().saturating_sub(1) / MIN_INPUTS_PER_WORKER;
+        let pool = match db.salsa_runtime().verification_pool() {
+            Some(pool) if wanted > 0 => pool,
+            _ => {
+                return Self::inputs_unchanged_sequentially(
+                    db.as_salsa_database(),
+                    inputs,
+                    last_verified_at,
+                )
+            }
+        };
+
+        let workers = Workers::reserve(pool, wanted);
+        let snapshots: Vec<_> = (0..workers.reserved)
+            .map_while(|_| db.snapshot_for_verification())
+            .collect();
+
+        // Worker `i` verifies every `worker_count`-th input, starting from input `i + 1`.
+        // Verification snapshots never execute queries and so should not panic,
+        // but if they do (e.g. because they were cancelled), the sequential pass
+        // below will still get the right answer.
+        let worker_count = snapshots.len();
+        for (i, snapshot) in snapshots.into_iter().enumerate() {
+            let edges = edges.clone();
+            let done = workers.done.clone();
+            let running = workers.running.clone();
+            workers.pool.execute(move || {
+                let _running = running;
+                for &input in edges.inputs()[i + 1..].iter().step_by(worker_count) {
+                    if done.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    snapshot.maybe_changed_after(input, last_verified_at);
+                }
+            });
+        }
+
+        Self::inputs_unchanged_sequentially(db.as_salsa_database(), inputs, last_verified_at)
+    }
+
+    fn inputs_unchanged_sequentially(
+        db: &dyn Database,
+        inputs: &[DependencyIndex],
+        last_verified_at: Revision,
+    ) -> bool {
+        // NB: It's important here that we are iterating the inputs in the order that
+        // they executed. It's possible that if the value of some input I0 is no longer
+        // valid, then some later input I1 might never have executed at all, so verifying
+        // it is still up to date is meaningless.
+        for &input in inputs {
+            if db.maybe_changed_after(input, last_verified_at) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Workers reserved from a [`VerificationPool`] by [`FunctionIngredient::inputs_unchanged`].
+///
+/// When dropped (even if verification on the current thread panics, e.g. because it was
+/// cancelled), tells the workers to stop, waits for them so that their snapshots cannot
+/// block a later write, and returns them to the pool.
+struct Workers {
+    pool: Arc<VerificationPool>,
+    reserved: usize,
+    done: Arc<AtomicBool>,
+    running: WaitGroup,
+}
+
+impl Workers {
+    fn reserve(pool: Arc<VerificationPool>, wanted: usize) -> Self {
+        Self {
+            reserved: pool.reserve(wanted),
+            pool,
+            done: Default::default(),
+            running: WaitGroup::new(),
+        }
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        std::mem::take(&mut self.running).wait();
+        self.pool.release(self.reserved);
+    }
+}
@@ -1,8 +1,8 @@
-use std::fmt;
+use std::{any::Any, fmt, io};
 
 use crate::{
-    cycle::CycleRecoveryStrategy, key::DependencyIndex, runtime::local_state::QueryOrigin,
-    DatabaseKeyIndex, Id,
+    cycle::CycleRecoveryStrategy, debug::Dump, hash::FxHashSet, key::DependencyIndex,
+    runtime::local_state::QueryOrigin, DatabaseKeyIndex, Durability, Id,
 };
 
 use super::Revision;
@@ -28,6 +28,20 @@ pub trait Ingredient<DB: ?Sized> {
     /// What were the inputs (if any) that were used to create the value at `key_index`.
     fn origin(&self, key_index: Id) -> Option<QueryOrigin>;
 
+    /// The last revision in which the value at `key_index`, or one of the values it transitively
+    /// depends on, was computed by executing a query, if this ingredient memoizes one.
+    fn subtree_executed_at(&self, _key_index: Id) -> Option<Revision> {
+        None
+    }
+
+    /// Checks `value`, the value at `key_index` in the database that this one was forked from,
+    /// against the value obtained by executing the function from scratch in this database;
+    /// see [`crate::Database::fork_for_differential_verification`].
+    /// Only implemented by ingredients that memoize the values of functions.
+    fn verify_from_scratch(&self, _db: &DB, _key_index: Id, _value: &dyn Any) {
+        unreachable!("only the values of functions are verified from scratch")
+    }
+
     /// Invoked when the value `output_key` should be marked as valid in the current revision.
     /// This occurs because the value for `executor`, which generated it, was marked as valid
     /// in the current revision.
@@ -62,7 +76,49 @@ pub trait Ingredient<DB: ?Sized> {
     /// [`IngredientRequiresReset::RESET_ON_NEW_REVISION`] to true.
     fn reset_for_new_revision(&mut self);
 
+    /// True if this ingredient still has the information needed to restore the state it had
+    /// in `revision`; see [`Storage::rollback_to`](`crate::storage::Storage::rollback_to`).
+    fn can_rollback_to(&self, _revision: Revision) -> bool {
+        true
+    }
+
+    /// Restores the inputs stored in this ingredient to the values they had in `revision`,
+    /// pushing the index and durability of each input that changed onto `restored`.
+    /// Only invoked if [`Self::can_rollback_to`] returned true.
+    fn rollback_inputs(
+        &mut self,
+        _revision: Revision,
+        _restored: &mut Vec<(DependencyIndex, Durability)>,
+    ) {
+    }
+
+    /// Discards memoized values which were verified after `revision` and read one of the `restored` inputs,
+    /// as they reflect input values that no longer exist.
+    /// Invoked once [`Self::rollback_inputs`] has been invoked on every ingredient.
+    fn rollback_memos(&mut self, _revision: Revision, _restored: &FxHashSet<DependencyIndex>) {}
+
+    /// The name given to this ingredient by the macro (e.g., the name of the tracked function).
+    fn debug_name(&self) -> &'static str;
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Like [`Self::fmt_index`], but renders the key itself (e.g., the fields of the salsa struct
+    /// or the arguments of the tracked function) rather than just its id.
+    /// Used for diagnostics such as [`Cycle::participants`](`crate::Cycle::participants`).
+    fn fmt_key(
+        &self,
+        _db: &DB,
+        index: Option<crate::Id>,
+        fmt: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        self.fmt_index(index, fmt)
+    }
+
+    /// Writes a header for this ingredient followed by its entries, sorted by id;
+    /// see [`crate::debug::dump`].
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("ingredient", self.debug_name())
+    }
 }
 
 /// A helper function to show human readable fmt.
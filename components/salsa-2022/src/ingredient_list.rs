@@ -15,6 +15,14 @@ pub(crate) struct IngredientList {
     list: ArcSwapOption<Vec<IngredientIndex>>,
 }
 
+impl Clone for IngredientList {
+    fn clone(&self) -> Self {
+        Self {
+            list: ArcSwapOption::new(self.list.load_full()),
+        }
+    }
+}
+
 impl IngredientList {
     pub fn new() -> Self {
         Self {
@@ -1,7 +1,8 @@
-use std::fmt;
+use std::{fmt, io};
 
 use crate::{
     cycle::CycleRecoveryStrategy,
+    debug::Dump,
     ingredient::{fmt_index, Ingredient, IngredientRequiresReset},
     key::{DatabaseKeyIndex, DependencyIndex},
     runtime::{local_state::QueryOrigin, Runtime},
@@ -11,6 +12,7 @@ use crate::{
 pub trait InputId: AsId {}
 impl<T: AsId> InputId for T {}
 
+#[derive(Clone)]
 pub struct InputIngredient<Id>
 where
     Id: InputId,
@@ -100,9 +102,25 @@ where
         );
     }
 
+    fn debug_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_index(self.debug_name, index, fmt)
     }
+
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("input", self.debug_name)?;
+        for id in 0..self.counter {
+            let key = DatabaseKeyIndex {
+                ingredient_index: self.ingredient_index,
+                key_index: crate::Id::from_u32(id),
+            };
+            dump.entry(key, format_args!(""))?;
+        }
+        Ok(())
+    }
 }
 
 impl<Id> IngredientRequiresReset for InputIngredient<Id>
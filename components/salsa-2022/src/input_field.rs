@@ -1,12 +1,19 @@
 use crate::cycle::CycleRecoveryStrategy;
+use crate::debug::{dump_opaque, Dump, DumpFmt, DumpValue};
 use crate::ingredient::{fmt_index, Ingredient, IngredientRequiresReset};
 use crate::key::DependencyIndex;
 use crate::runtime::local_state::QueryOrigin;
 use crate::runtime::StampedValue;
-use crate::{AsId, DatabaseKeyIndex, Durability, Id, IngredientIndex, Revision, Runtime};
+use crate::{
+    AsId, Database, DatabaseKeyIndex, Durability, EventKind, EventKinds, Id, IngredientIndex,
+    Revision, Runtime,
+};
 use rustc_hash::FxHashMap;
+use std::collections::VecDeque;
 use std::fmt;
 use std::hash::Hash;
+use std::io;
+use std::sync::{Arc, OnceLock};
 
 /// Ingredient used to represent the fields of a `#[salsa::input]`.
 /// These fields can only be mutated by an explicit call to a setter
@@ -15,10 +22,101 @@ use std::hash::Hash;
 /// This makes the implementation considerably simpler.
 pub struct InputFieldIngredient<K, F> {
     index: IngredientIndex,
-    map: FxHashMap<K, StampedValue<F>>,
+
+    /// The values of the fields. Shared with [forks](`crate::Storage::fork`) of the database
+    /// until either side writes to them (see [`Self::map_mut`]).
+    map: Arc<FxHashMap<K, StampedValue<F>>>,
+
+    /// Prior values of the fields, if this ingredient was created [with history](`Self::with_history`).
+    /// Used to roll the database back to an earlier revision. Shared with forks like `map`.
+    history: Option<Arc<History<K, F>>>,
+
+    /// Copies a value. Set once the values are shared with a fork, which requires `F: Clone`,
+    /// so that whichever side writes to them first can copy them.
+    clone_value: Arc<OnceLock<CloneFn<F>>>,
+
+    /// Formats the values for [`crate::debug::dump`].
+    fmt_value: DumpFmt<F>,
+
     debug_name: &'static str,
 }
 
+type CloneFn<F> = fn(&F) -> F;
+
+struct History<K, F> {
+    /// Maximum number of prior values kept per field.
+    capacity: usize,
+
+    /// Copies a value into the history; the value itself is returned to the setter.
+    clone: CloneFn<F>,
+
+    fields: FxHashMap<K, FieldHistory<F>>,
+}
+
+struct FieldHistory<F> {
+    /// The prior values, oldest first, each paired with the revision in which it was overwritten.
+    values: VecDeque<(StampedValue<F>, Revision)>,
+
+    /// True if values were dropped from the front of `values` to stay within the capacity.
+    truncated: bool,
+}
+
+/// Cloning an input field ingredient is how it is [forked](`crate::storage::Storage::fork`):
+/// the clone shares the values with the original until either of them is written to.
+impl<K, F: Clone> Clone for InputFieldIngredient<K, F> {
+    fn clone(&self) -> Self {
+        let _ = self.clone_value.set(F::clone);
+        Self {
+            index: self.index,
+            map: self.map.clone(),
+            history: self.history.clone(),
+            clone_value: self.clone_value.clone(),
+            fmt_value: self.fmt_value,
+            debug_name: self.debug_name,
+        }
+    }
+}
+
+impl<K: AsId, F> History<K, F> {
+    fn copy(&self) -> Self {
+        let fields = self.fields.iter().map(|(key, field)| {
+            let values = field.values.iter().map(|(value, overwritten_at)| {
+                (copy_stamped_value(value, self.clone), *overwritten_at)
+            });
+            let field = FieldHistory {
+                values: values.collect(),
+                truncated: field.truncated,
+            };
+            (*key, field)
+        });
+        Self {
+            capacity: self.capacity,
+            clone: self.clone,
+            fields: fields.collect(),
+        }
+    }
+}
+
+fn copy_stamped_value<F>(value: &StampedValue<F>, clone: CloneFn<F>) -> StampedValue<F> {
+    StampedValue {
+        value: clone(&value.value),
+        durability: value.durability,
+        changed_at: value.changed_at,
+    }
+}
+
+/// Where to find the value that a field had in an earlier revision.
+enum PriorValue {
+    /// The current value was already set back then (or the field did not exist yet).
+    Current,
+
+    /// The value at the given position in the field history.
+    History(usize),
+
+    /// The value is no longer known.
+    Unavailable,
+}
+
 impl<K, F> InputFieldIngredient<K, F>
 where
     K: Eq + Hash + AsId,
@@ -27,10 +125,50 @@ where
         Self {
             index,
             map: Default::default(),
+            history: None,
+            clone_value: Default::default(),
+            fmt_value: dump_opaque,
             debug_name,
         }
     }
 
+    /// Sets the function used to format the values for [`crate::debug::dump`].
+    pub fn with_dump_fmt(self, fmt_value: DumpFmt<F>) -> Self {
+        Self { fmt_value, ..self }
+    }
+
+    /// Creates an ingredient that keeps up to `capacity` prior values for each field,
+    /// so that the database can be [rolled back](`crate::Storage::rollback_to`).
+    pub fn with_history(index: IngredientIndex, debug_name: &'static str, capacity: usize) -> Self
+    where
+        F: Clone,
+    {
+        Self {
+            history: Some(Arc::new(History {
+                capacity,
+                clone: F::clone,
+                fields: Default::default(),
+            })),
+            ..Self::new(index, debug_name)
+        }
+    }
+
+    /// Reports [`EventKind::WillSetInput`] for the field of `key` to `db`, and declares the
+    /// durability of the value about to be overwritten, so that the new revision only cancels
+    /// the snapshots that may have read it. Called by the setters before they start a new revision.
+    pub fn report_will_set<DB: ?Sized + Database>(&self, db: &DB, key: K) {
+        let runtime = db.salsa_runtime();
+        runtime.report_event(db, EventKinds::WILL_SET_INPUT, || EventKind::WillSetInput {
+            input: DependencyIndex {
+                ingredient_index: self.index,
+                key_index: Some(key.as_id()),
+            },
+        });
+        if let Some(old_value) = self.map.get(&key) {
+            runtime.declare_write_durability(old_value.durability);
+        }
+    }
+
     pub fn store(
         &mut self,
         runtime: &mut Runtime,
@@ -38,6 +176,7 @@ where
         value: F,
         durability: Durability,
     ) -> Option<F> {
+        runtime.assert_valid_durability(durability);
         let revision = runtime.current_revision();
         let stamped_value = StampedValue {
             value,
@@ -45,10 +184,70 @@ where
             changed_at: revision,
         };
 
-        if let Some(old_value) = self.map.insert(key, stamped_value) {
-            Some(old_value.value)
-        } else {
-            None
+        let old_value = self.map_mut().insert(key, stamped_value)?;
+        runtime.report_tracked_write(self.database_key_index(key), old_value.durability);
+        if let Some(history) = self.history_mut() {
+            let field = history.fields.entry(key).or_insert_with(|| FieldHistory {
+                values: VecDeque::new(),
+                truncated: false,
+            });
+            let copy = copy_stamped_value(&old_value, history.clone);
+            field.values.push_back((copy, revision));
+            if field.values.len() > history.capacity {
+                field.values.pop_front();
+                field.truncated = true;
+            }
+        }
+        Some(old_value.value)
+    }
+
+    /// Returns the values for writing, copying them first if they are shared with a fork.
+    fn map_mut(&mut self) -> &mut FxHashMap<K, StampedValue<F>> {
+        if Arc::get_mut(&mut self.map).is_none() {
+            let clone = *self.clone_value.get().unwrap();
+            let map = self
+                .map
+                .iter()
+                .map(|(key, value)| (*key, copy_stamped_value(value, clone)))
+                .collect();
+            self.map = Arc::new(map);
+        }
+        Arc::get_mut(&mut self.map).unwrap()
+    }
+
+    /// Like [`Self::map_mut`], for the history.
+    fn history_mut(&mut self) -> Option<&mut History<K, F>> {
+        let history = self.history.as_mut()?;
+        if Arc::get_mut(history).is_none() {
+            *history = Arc::new(history.copy());
+        }
+        Arc::get_mut(history)
+    }
+
+    /// Determines where to find the value that the field `key` had in `revision`.
+    fn prior_value(&self, key: &K, current: &StampedValue<F>, revision: Revision) -> PriorValue {
+        if current.changed_at <= revision {
+            return PriorValue::Current;
+        }
+
+        let field = match self.history.as_ref().and_then(|h| h.fields.get(key)) {
+            Some(field) => field,
+            // Without history, we cannot tell a field set after `revision`
+            // from one that was created after `revision`.
+            None if self.history.is_some() => return PriorValue::Current,
+            None => return PriorValue::Unavailable,
+        };
+
+        // Values are overwritten in order, so the value in `revision` is the first
+        // that was overwritten after it -- if it was already set in `revision`.
+        match field
+            .values
+            .iter()
+            .position(|(_, overwritten_at)| *overwritten_at > revision)
+        {
+            Some(i) if field.values[i].0.changed_at <= revision => PriorValue::History(i),
+            Some(0) | None if !field.truncated => PriorValue::Current,
+            _ => PriorValue::Unavailable,
         }
     }
 
@@ -68,6 +267,24 @@ where
         value
     }
 
+    /// Returns the durability with which the field was last set.
+    /// Like [`Self::fetch`], this records a read of the field.
+    pub fn fetch_durability(&self, runtime: &Runtime, key: K) -> Durability {
+        let StampedValue {
+            value: _,
+            durability,
+            changed_at,
+        } = self.map.get(&key).unwrap();
+
+        runtime.report_tracked_read(
+            self.database_key_index(key).into(),
+            *durability,
+            *changed_at,
+        );
+
+        *durability
+    }
+
     fn database_key_index(&self, key: K) -> DatabaseKeyIndex {
         DatabaseKeyIndex {
             ingredient_index: self.index,
@@ -117,9 +334,66 @@ where
         panic!("unexpected call: input fields don't register for resets");
     }
 
+    fn can_rollback_to(&self, revision: Revision) -> bool {
+        self.map.iter().all(|(key, current)| {
+            !matches!(
+                self.prior_value(key, current, revision),
+                PriorValue::Unavailable
+            )
+        })
+    }
+
+    fn rollback_inputs(
+        &mut self,
+        revision: Revision,
+        restored: &mut Vec<(DependencyIndex, Durability)>,
+    ) {
+        let prior_values: Vec<_> = self
+            .map
+            .iter()
+            .filter_map(
+                |(key, current)| match self.prior_value(key, current, revision) {
+                    PriorValue::Current => None,
+                    PriorValue::History(i) => Some((*key, i)),
+                    PriorValue::Unavailable => panic!("no value for {:?} in {:?}", key, revision),
+                },
+            )
+            .collect();
+
+        for (key, i) in prior_values {
+            let field = self.history_mut().unwrap().fields.get_mut(&key).unwrap();
+            let (value, _) = field.values.drain(i..).next().unwrap();
+            let old_value = self.map_mut().insert(key, value).unwrap();
+            let durability = old_value.durability.max(self.map[&key].durability);
+            restored.push((self.database_key_index(key).into(), durability));
+        }
+    }
+
+    fn debug_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_index(self.debug_name, index, fmt)
     }
+
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("input field", self.debug_name)?;
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by_key(|(key, _)| key.as_id());
+        for (key, value) in entries {
+            dump.entry(
+                self.database_key_index(*key),
+                format_args!(
+                    "value={:?}, durability={:?}, changed_at={:?}",
+                    DumpValue(&value.value, self.fmt_value),
+                    value.durability,
+                    value.changed_at
+                ),
+            )?;
+        }
+        Ok(())
+    }
 }
 
 impl<K, F> IngredientRequiresReset for InputFieldIngredient<K, F>
@@ -1,18 +1,24 @@
 use crossbeam::atomic::AtomicCell;
 use crossbeam::queue::SegQueue;
+use parking_lot::{Mutex, RwLock};
+use std::any::{Any, TypeId};
 use std::fmt;
 use std::hash::Hash;
+use std::io;
 use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock};
 
+use crate::arena::Arena;
+use crate::debug::{dump_opaque, Dump, DumpFmt, DumpValue};
 use crate::durability::Durability;
 use crate::id::AsId;
 use crate::ingredient::{fmt_index, IngredientRequiresReset};
 use crate::key::DependencyIndex;
 use crate::runtime::local_state::QueryOrigin;
 use crate::runtime::Runtime;
-use crate::DatabaseKeyIndex;
+use crate::{Database, DatabaseKeyIndex, DebugWithDb, EventKind, EventKinds};
 
-use super::hash::FxDashMap;
+use super::hash::{FxDashMap, FxHashMap};
 use super::ingredient::Ingredient;
 use super::routes::IngredientIndex;
 use super::Revision;
@@ -30,18 +36,26 @@ pub struct InternedIngredient<Id: InternedId, Data: InternedData> {
     /// Index of this ingredient in the database (used to construct database-ids, etc).
     ingredient_index: IngredientIndex,
 
-    /// Maps from data to the existing interned id for that data.
-    ///
-    /// Deadlock requirement: We access `key_map` while holding lock on `value_map`, but not vice versa.
-    key_map: FxDashMap<Data, Id>,
+    /// The interned values; shared with other databases if this ingredient was
+    /// created with [`Self::new_shared`]. Otherwise, if this ingredient was forked,
+    /// only holds the values interned since then.
+    table: Arc<InternedTable<Id, Data>>,
 
-    /// Maps from an interned id to its data.
-    ///
-    /// Deadlock requirement: We access `key_map` while holding lock on `value_map`, but not vice versa.
-    value_map: FxDashMap<Id, Box<Data>>,
+    /// The tables of the ingredients this one was [forked](`crate::Storage::fork`) from,
+    /// outermost first, as of the fork. Ids below the `start` of `table` are looked up in them.
+    layers: Vec<Layer<Id, Data>>,
 
-    /// counter for the next id.
-    counter: AtomicCell<u32>,
+    /// True if `table` is shared with other databases.
+    shared: bool,
+
+    /// True if reading a value adds a dependency on just that entry,
+    /// rather than on the whole table; see [`Self::with_per_entry_deps`].
+    per_entry_deps: bool,
+
+    /// Maps from an interned id to the query that interned it with [`Self::intern_owned`]
+    /// (its "owner"), which is permitted to specify the values of tracked functions for the id.
+    /// Only written by `intern_owned`, so plain interning does not pay for it.
+    owners: FxDashMap<Id, DatabaseKeyIndex>,
 
     /// Stores the revision when this interned ingredient was last cleared.
     /// You can clear an interned table at any point, deleting all its entries,
@@ -54,11 +68,225 @@ pub struct InternedIngredient<Id: InternedId, Data: InternedData> {
     /// references to that data floating about that are tied to the lifetime of some
     /// `&db` reference. This queue itself is not freed until we have an `&mut db` reference,
     /// guaranteeing that there are no more references to it.
-    deleted_entries: SegQueue<Box<Data>>,
+    deleted_entries: SegQueue<Box<InternedEntry<Data>>>,
+
+    /// Formats the interned values for [`crate::debug::dump`].
+    fmt_data: DumpFmt<Data>,
 
     debug_name: &'static str,
 }
 
+/// The values of an interned ingredient.
+///
+/// A fork of the database does not copy the table: the fork keeps reading the entries
+/// that existed when it was created (see [`Layer`]) and interns new values in a table of its own.
+/// Those entries are therefore *frozen*: deleting one only hides it from the database
+/// that deleted it (and from its later forks), so that earlier forks can still read it.
+/// Once every fork has been dropped, the table is unfrozen again and the hidden entries
+/// are freed, at the start of the next revision of the database that owns it.
+pub struct InternedTable<Id, Data> {
+    /// The first id of this table; smaller ids are stored in the tables of a forked ingredient's layers.
+    start: u32,
+
+    /// Maps from data to the existing interned id for that data.
+    key_map: FxDashMap<Data, Id>,
+
+    /// Maps from an interned id to its data. Ids are dense counters,
+    /// so this is an arena indexed by `id - start`, which can be read without taking locks.
+    values: Arena<InternedEntry<Data>>,
+
+    /// counter for the next id.
+    counter: AtomicCell<u32>,
+
+    /// Ids below this one may be read by a fork, so their entries are not removed
+    /// while any fork holds a [`Layer`] of this table.
+    frozen_below: AtomicCell<u32>,
+
+    /// Held for reading while deleting an entry and for writing while freezing the table,
+    /// so that a fork sees each deletion either entirely or not at all.
+    freeze_lock: RwLock<()>,
+
+    /// Maps frozen ids deleted by the owning database to the sequence number of the deletion.
+    hidden: FxDashMap<u32, u32>,
+
+    /// The sequence number of the next deletion of a frozen id.
+    deletions: AtomicCell<u32>,
+}
+
+/// The table of an ingredient that this one was forked from, as of the fork.
+struct Layer<Id, Data> {
+    table: Arc<InternedTable<Id, Data>>,
+
+    /// The ids allocated in `table` when the fork was created.
+    limit: u32,
+
+    /// The number of deletions recorded in `table` when the fork was created.
+    deletions: u32,
+}
+
+impl<Id, Data> Clone for Layer<Id, Data> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            limit: self.limit,
+            deletions: self.deletions,
+        }
+    }
+}
+
+struct InternedEntry<Data> {
+    data: Data,
+
+    /// The revision in which the entry was created
+    /// (always the first revision in a shared table, whose entries exist in every database).
+    created_at: Revision,
+}
+
+impl<Id: InternedId, Data: InternedData> Default for InternedTable<Id, Data> {
+    fn default() -> Self {
+        Self::starting_at(0)
+    }
+}
+
+impl<Id: InternedId, Data: InternedData> InternedTable<Id, Data> {
+    fn starting_at(start: u32) -> Self {
+        Self {
+            start,
+            key_map: Default::default(),
+            values: Default::default(),
+            counter: AtomicCell::new(start),
+            frozen_below: AtomicCell::new(start),
+            freeze_lock: Default::default(),
+            hidden: Default::default(),
+            deletions: Default::default(),
+        }
+    }
+
+    /// True if `id` was deleted by the owning database in one of its first `deletions` deletions.
+    fn is_hidden(&self, id: u32, deletions: u32) -> bool {
+        self.deletions.load() > 0 && self.hidden.get(&id).is_some_and(|seq| *seq < deletions)
+    }
+
+    /// Frees the hidden entries and unfreezes the table. Only invoked once no fork
+    /// holds a layer of the table (and hence with `&mut` access to it).
+    /// Ids below `start` belong to the tables of the forked ingredient's layers,
+    /// so they stay hidden.
+    fn unfreeze(&mut self) {
+        let start = self.start;
+        let mut freed = vec![];
+        self.hidden.retain(|&index, _| {
+            if index < start {
+                return true;
+            }
+            freed.push(index);
+            false
+        });
+        for index in freed {
+            let entry = unsafe {
+                // Unsafety invariant: with `&mut self`, there are no references to the entry.
+                self.values.remove(index - self.start)
+            };
+            if let Some(entry) = entry {
+                // The data may have been interned again since, with a new id.
+                self.key_map
+                    .remove_if(&entry.data, |_, id| id.as_id().as_u32() == index);
+            }
+        }
+        self.frozen_below.store(self.start);
+    }
+
+    /// Freezes the entries allocated so far and returns them as a layer for a fork.
+    fn freeze(self: &Arc<Self>) -> Layer<Id, Data> {
+        let _guard = self.freeze_lock.write();
+        let limit = self.counter.load();
+        self.frozen_below.fetch_max(limit);
+        Layer {
+            table: self.clone(),
+            limit,
+            deletions: self.deletions.load(),
+        }
+    }
+}
+
+/// The tables of the interned structs declared with `#[salsa::interned(shared)]`,
+/// one per struct, which are shared by the databases created with the same `SharedInterners`.
+/// Ids of such structs are therefore comparable across those databases, and each value is only stored once.
+///
+/// Databases use the [global](`Self::global`) tables unless they are created with
+/// [`Storage::with_shared_interners`](`crate::Storage::with_shared_interners`).
+/// Shared tables are never cleared: their values live as long as the `SharedInterners`.
+///
+/// The data of a shared interned struct must not contain ids of ingredients that are not
+/// shared (inputs, tracked structs, and other interned structs): each database numbers
+/// those separately, so the same id stands for different values in different databases,
+/// and sharing would conflate them.
+#[derive(Clone, Default)]
+pub struct SharedInterners {
+    tables: Arc<Mutex<FxHashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl SharedInterners {
+    /// Creates an empty set of tables, separate from the global one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The tables shared by all databases of the process.
+    pub fn global() -> &'static SharedInterners {
+        static GLOBAL: OnceLock<SharedInterners> = OnceLock::new();
+        GLOBAL.get_or_init(SharedInterners::new)
+    }
+
+    /// The table for the interned struct `Id`, created on first use.
+    fn table<Id, Data>(&self) -> Arc<InternedTable<Id, Data>>
+    where
+        Id: InternedId + Send + Sync + 'static,
+        Data: InternedData + Send + Sync + 'static,
+    {
+        let table = self
+            .tables
+            .lock()
+            .entry(TypeId::of::<InternedTable<Id, Data>>())
+            .or_insert_with(|| Arc::new(InternedTable::<Id, Data>::default()))
+            .clone();
+        table.downcast().unwrap()
+    }
+}
+
+/// Cloning an interned ingredient is how it is [forked](`crate::storage::Storage::fork`):
+/// the clone starts out with the same entries, but interns new values independently
+/// (unless the table is shared, in which case the fork keeps using it).
+/// The entries are not copied; see [`InternedTable`].
+impl<Id, Data> Clone for InternedIngredient<Id, Data>
+where
+    Id: InternedId,
+    Data: InternedData,
+{
+    fn clone(&self) -> Self {
+        let (table, layers) = if self.shared {
+            (self.table.clone(), vec![])
+        } else {
+            let layer = self.table.freeze();
+            let table = Arc::new(InternedTable::starting_at(layer.limit));
+            let mut layers = self.layers.clone();
+            layers.push(layer);
+            (table, layers)
+        };
+        Self {
+            ingredient_index: self.ingredient_index,
+            table,
+            layers,
+            shared: self.shared,
+            per_entry_deps: self.per_entry_deps,
+            owners: self.owners.clone(),
+            reset_at: self.reset_at,
+            deleted_entries: Default::default(),
+            fmt_data: self.fmt_data,
+            debug_name: self.debug_name,
+        }
+    }
+}
+
 impl<Id, Data> InternedIngredient<Id, Data>
 where
     Id: InternedId,
@@ -67,73 +295,256 @@ where
     pub fn new(ingredient_index: IngredientIndex, debug_name: &'static str) -> Self {
         Self {
             ingredient_index,
-            key_map: Default::default(),
-            value_map: Default::default(),
-            counter: AtomicCell::default(),
+            table: Default::default(),
+            layers: vec![],
+            shared: false,
+            per_entry_deps: false,
+            owners: Default::default(),
             reset_at: Revision::start(),
             deleted_entries: Default::default(),
+            fmt_data: dump_opaque,
             debug_name,
         }
     }
 
+    /// Creates an ingredient whose values are stored in the table for `Id` in `interners`,
+    /// shared with the other databases using it.
+    pub fn new_shared(
+        ingredient_index: IngredientIndex,
+        debug_name: &'static str,
+        interners: &SharedInterners,
+    ) -> Self
+    where
+        Id: Send + Sync + 'static,
+        Data: Send + Sync + 'static,
+    {
+        Self {
+            table: interners.table(),
+            shared: true,
+            ..Self::new(ingredient_index, debug_name)
+        }
+    }
+
+    /// Sets the function used to format the interned values for [`crate::debug::dump`].
+    pub fn with_dump_fmt(self, fmt_data: DumpFmt<Data>) -> Self {
+        Self { fmt_data, ..self }
+    }
+
+    /// Makes queries that read or intern a value depend on just the entry for that value,
+    /// so that they are only invalidated if that entry is deleted, rather than whenever
+    /// any entry is. Costs one dependency edge per entry read instead of one per table.
+    pub fn with_per_entry_deps(self) -> Self {
+        Self {
+            per_entry_deps: true,
+            ..self
+        }
+    }
+
     pub fn intern(&self, runtime: &Runtime, data: Data) -> Id {
-        runtime.report_tracked_read(
-            DependencyIndex::for_table(self.ingredient_index),
-            Durability::MAX,
-            self.reset_at,
+        self.intern_with(runtime, data, |_| ())
+    }
+
+    /// Like [`Self::intern`], but reports [`EventKind::DidInternValue`] to `db`
+    /// if a new id was allocated. Used by the constructors of interned structs.
+    pub fn intern_struct<DB: ?Sized + Database>(&self, db: &DB, data: Data) -> Id {
+        let runtime = db.salsa_runtime();
+        let mut is_new = false;
+        let id = self.intern_with(runtime, data, |_| is_new = true);
+        if is_new {
+            runtime.report_event(db, EventKinds::DID_INTERN_VALUE, || {
+                EventKind::DidInternValue {
+                    key: DatabaseKeyIndex {
+                        ingredient_index: self.ingredient_index,
+                        key_index: id.as_id(),
+                    },
+                }
+            });
+        }
+        id
+    }
+
+    /// Like [`Self::intern_struct`], but also makes the active query the owner of the id,
+    /// which permits it to specify the values of tracked functions for the id.
+    /// Used by the `_owned` constructors of interned structs.
+    ///
+    /// Ownership is granted explicitly, and only once, so that it does not depend on which
+    /// query happens to intern a value first: a value stays owned by the same query
+    /// until the table is reset.
+    ///
+    /// # Panics
+    ///
+    /// If no query is active, if the table is shared with other databases,
+    /// or if the value is already owned by another query.
+    pub fn intern_owned<DB: ?Sized + Database>(&self, db: &DB, data: Data) -> Id {
+        assert!(
+            !self.shared,
+            "cannot own the values of a shared interned table"
         );
+        let Some((query, _)) = db.salsa_runtime().active_query() else {
+            panic!("can only intern an owned value with an active query");
+        };
+        let id = self.intern_struct(db, data);
+        let owner = *self.owners.entry(id).or_insert(query);
+        if owner != query {
+            panic!(
+                "`{:?}` cannot own `{:?}`, which is already owned by `{:?}`",
+                query.debug(db),
+                DatabaseKeyIndex {
+                    ingredient_index: self.ingredient_index,
+                    key_index: id.as_id(),
+                }
+                .debug(db),
+                owner.debug(db),
+            );
+        }
+        id
+    }
 
-        if let Some(id) = self.key_map.get(&data) {
-            return *id;
+    fn intern_with(&self, runtime: &Runtime, data: Data, on_new_id: impl FnOnce(Id)) -> Id {
+        if let Some((id, created_at)) = self.lookup(runtime, &data) {
+            self.report_read(runtime, id, created_at);
+            return id;
         }
 
+        let table = &*self.table;
+
+        let created_at = if self.shared {
+            Revision::start()
+        } else {
+            runtime.current_revision()
+        };
+        let key = data.clone();
+        let mut value = Box::new(InternedEntry { data, created_at });
         loop {
-            let next_id = self.counter.fetch_add(1);
-            let next_id = Id::from_id(crate::id::Id::from_u32(next_id));
-            match self.value_map.entry(next_id) {
+            // Ids are never reused: the counter only grows, and `reset` starts the next table
+            // where this one stopped. Tombstones (see `crate::tombstones`) identify deleted
+            // tracked structs by their id alone, so the counter must not wrap around either.
+            let next_id = table
+                .counter
+                .fetch_update(|counter| counter.checked_add(1))
+                .expect("interned ids exhausted");
+            let next_id = crate::id::Id::from_u32(next_id);
+            match table.values.insert(next_id.as_u32() - table.start, value) {
                 // If we already have an entry with this id...
-                dashmap::mapref::entry::Entry::Occupied(_) => continue,
+                Err(returned) => value = returned,
 
                 // Otherwise...
-                dashmap::mapref::entry::Entry::Vacant(entry) => {
-                    self.key_map.insert(data.clone(), next_id);
-                    entry.insert(Box::new(data));
+                Ok(()) => {
+                    let next_id = Id::from_id(next_id);
+                    self.report_read(runtime, next_id, created_at);
+                    on_new_id(next_id);
+                    table.key_map.insert(key, next_id);
                     return next_id;
                 }
             }
         }
     }
 
+    /// Returns the id of `data`, if it was interned already, and the revision in which it was.
+    fn lookup(&self, runtime: &Runtime, data: &Data) -> Option<(Id, Revision)> {
+        let table = &*self.table;
+        if let Some(id) = table.key_map.get(data) {
+            let id = *id;
+            if !table.is_hidden(id.as_id().as_u32(), u32::MAX) {
+                let created_at = match table.values.get(id.as_id().as_u32() - table.start) {
+                    Some(entry) => entry.created_at,
+                    // The entry is being deleted.
+                    None => runtime.current_revision(),
+                };
+                return Some((id, created_at));
+            }
+        }
+
+        self.layers.iter().rev().find_map(|layer| {
+            let id = *layer.table.key_map.get(data)?;
+            if id.as_id().as_u32() >= layer.limit {
+                // Interned in the other database after the fork.
+                return None;
+            }
+            let entry = self.entry(id.as_id().as_u32())?;
+            Some((id, entry.created_at))
+        })
+    }
+
+    /// The entry for the id `index`, unless it does not exist (or no longer exists) in this database.
+    fn entry(&self, index: u32) -> Option<&InternedEntry<Data>> {
+        let table = &*self.table;
+        if table.is_hidden(index, u32::MAX) {
+            return None;
+        }
+        if index >= table.start {
+            return table.values.get(index - table.start);
+        }
+        for layer in self.layers.iter().rev() {
+            if layer.table.is_hidden(index, layer.deletions) {
+                return None;
+            }
+            if index >= layer.table.start {
+                if index >= layer.limit {
+                    return None;
+                }
+                return layer.table.values.get(index - layer.table.start);
+            }
+        }
+        None
+    }
+
+    /// Records that the active query read the entry for `id`, created in `created_at`.
+    fn report_read(&self, runtime: &Runtime, id: Id, created_at: Revision) {
+        if self.per_entry_deps {
+            runtime.report_tracked_read(
+                DependencyIndex {
+                    ingredient_index: self.ingredient_index,
+                    key_index: Some(id.as_id()),
+                },
+                Durability::MAX,
+                created_at,
+            );
+        } else {
+            runtime.report_tracked_read(
+                DependencyIndex::for_table(self.ingredient_index),
+                Durability::MAX,
+                self.reset_at,
+            );
+        }
+    }
+
     pub(crate) fn reset_at(&self) -> Revision {
         self.reset_at
     }
 
+    /// # Panics
+    ///
+    /// If the table is shared with other databases.
     pub fn reset(&mut self, revision: Revision) {
         assert!(revision > self.reset_at);
+        assert!(!self.shared, "cannot reset a shared interned table");
         self.reset_at = revision;
-        self.key_map.clear();
-        self.value_map.clear();
+        // Start a new table, rather than clearing this one, which forks may still read.
+        // Its ids start where the old ones ended, since ids are never reused.
+        self.table = Arc::new(InternedTable::starting_at(self.table.counter.load()));
+        self.layers.clear();
+        self.owners.clear();
+    }
+
+    /// True if `id` is owned by the currently active query (see [`Self::intern_owned`]).
+    pub fn is_owned_by_active_query(&self, runtime: &Runtime, id: Id) -> bool {
+        match (runtime.active_query(), self.owners.get(&id)) {
+            (Some((query, _)), Some(owner)) => *owner == query,
+            _ => false,
+        }
     }
 
     #[track_caller]
     pub fn data<'db>(&'db self, runtime: &'db Runtime, id: Id) -> &'db Data {
-        runtime.report_tracked_read(
-            DependencyIndex::for_table(self.ingredient_index),
-            Durability::MAX,
-            self.reset_at,
-        );
-
-        let data = match self.value_map.get(&id) {
-            Some(d) => d,
+        let entry = match self.entry(id.as_id().as_u32()) {
+            Some(entry) => entry,
             None => {
                 panic!("no data found for id `{:?}`", id)
             }
         };
-
-        // Unsafety clause:
-        //
-        // * Values are only removed or altered when we have `&mut self`
-        unsafe { transmute_lifetime(self, &**data) }
+        self.report_read(runtime, id, entry.created_at);
+        &entry.data
     }
 
     /// Get the ingredient index for this table.
@@ -153,38 +564,63 @@ where
     ///
     /// If you are wrong about this, it should not be unsafe, but unpredictable results may occur.
     pub(crate) fn delete_index(&self, id: Id) {
-        match self.value_map.entry(id) {
-            dashmap::mapref::entry::Entry::Vacant(_) => {
+        debug_assert!(!self.shared);
+        // Careful: even though `id` ought not to have been used in this revision,
+        // we don't know that for sure since users could have leaked things. If they did,
+        // they may have stray references into `data`. So push the box onto the
+        // "to be deleted" queue, which is only cleared when we have `&mut self`.
+        //
+        // To avoid this, we could include some kind of atomic counter in the `Box` that
+        // gets set whenever `data` executes, so we can track if the data was accessed since
+        // the last time an `&mut self` method was called. But that'd take extra storage
+        // and doesn't obviously seem worth it.
+        let index = id.as_id().as_u32();
+        let table = &*self.table;
+        let _guard = table.freeze_lock.read();
+        if index < table.frozen_below.load() {
+            // A fork may still read the entry, so only hide it from this database.
+            if self.entry(index).is_none() {
                 panic!("No entry for id `{:?}`", id);
             }
-            dashmap::mapref::entry::Entry::Occupied(entry) => {
-                self.key_map.remove(entry.get());
-
-                // Careful: even though `id` ought not to have been used in this revision,
-                // we don't know that for sure since users could have leaked things. If they did,
-                // they may have stray references into `data`. So push the box onto the
-                // "to be deleted" queue.
-                //
-                // To avoid this, we could include some kind of atomic counter in the `Box` that
-                // gets set whenever `data` executes, so we can track if the data was accessed since
-                // the last time an `&mut self` method was called. But that'd take extra storage
-                // and doesn't obviously seem worth it.
-                self.deleted_entries.push(entry.remove());
-            }
+            table.hidden.insert(index, table.deletions.fetch_add(1));
+            return;
         }
+        let entry = match unsafe { table.values.remove(index - table.start) } {
+            Some(entry) => entry,
+            None => panic!("No entry for id `{:?}`", id),
+        };
+        table.key_map.remove(&entry.data);
+        self.deleted_entries.push(entry);
     }
 
     pub(crate) fn clear_deleted_indices(&mut self) {
         std::mem::take(&mut self.deleted_entries);
+
+        // Once every fork has dropped its layer of the table, nothing can read the hidden entries.
+        if let Some(table) = Arc::get_mut(&mut self.table) {
+            table.unfreeze();
+        }
     }
-}
 
-// Returns `u` but with the lifetime of `t`.
-//
-// Safe if you know that data at `u` will remain shared
-// until the reference `t` expires.
-unsafe fn transmute_lifetime<'t, 'u, T, U>(_t: &'t T, u: &'u U) -> &'t U {
-    std::mem::transmute(u)
+    /// Invokes `op` on each interned value, in order of id.
+    pub(crate) fn for_each_sorted(
+        &self,
+        mut op: impl FnMut(Id, &Data) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let layers = self.layers.iter().map(|layer| (&*layer.table, layer.limit));
+        for (table, limit) in layers.chain([(&*self.table, u32::MAX)]) {
+            for (offset, _) in table.values.iter() {
+                let index = table.start + offset;
+                if index >= limit {
+                    break;
+                }
+                if let Some(entry) = self.entry(index) {
+                    op(Id::from_id(crate::id::Id::from_u32(index)), &entry.data)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<DB: ?Sized, Id, Data> Ingredient<DB> for InternedIngredient<Id, Data>
@@ -192,8 +628,18 @@ where
     Id: InternedId,
     Data: InternedData,
 {
-    fn maybe_changed_after(&self, _db: &DB, _input: DependencyIndex, revision: Revision) -> bool {
-        revision < self.reset_at
+    fn maybe_changed_after(&self, _db: &DB, input: DependencyIndex, revision: Revision) -> bool {
+        match input.key_index {
+            // A dependency on the whole table, which changes when the table is reset.
+            None => revision < self.reset_at,
+
+            // A dependency on one entry (see `with_per_entry_deps`). Ids are never reused,
+            // so if the entry is gone, it was deleted (or the table reset) after it was read.
+            Some(id) => match self.entry(id.as_u32()) {
+                Some(entry) => revision < entry.created_at,
+                None => true,
+            },
+        }
     }
 
     fn cycle_recovery_strategy(&self) -> crate::cycle::CycleRecoveryStrategy {
@@ -239,9 +685,24 @@ where
         panic!("unexpected call: interned ingredients do not register for salsa struct deletion events");
     }
 
+    fn debug_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_index(self.debug_name, index, fmt)
     }
+
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("interned", self.debug_name)?;
+        self.for_each_sorted(|id, data| {
+            let key = DatabaseKeyIndex {
+                ingredient_index: self.ingredient_index,
+                key_index: id.as_id(),
+            };
+            dump.entry(key, format_args!("{:?}", DumpValue(data, self.fmt_data)))
+        })
+    }
 }
 
 impl<Id, Data> IngredientRequiresReset for InternedIngredient<Id, Data>
@@ -252,13 +713,20 @@ where
     const RESET_ON_NEW_REVISION: bool = false;
 }
 
+#[derive(Clone)]
 pub struct IdentityInterner<Id: AsId> {
     data: PhantomData<Id>,
 }
 
+impl<Id: AsId> Default for IdentityInterner<Id> {
+    fn default() -> Self {
+        IdentityInterner { data: PhantomData }
+    }
+}
+
 impl<Id: AsId> IdentityInterner<Id> {
     pub fn new() -> Self {
-        IdentityInterner { data: PhantomData }
+        Self::default()
     }
 
     pub fn intern(&self, _runtime: &Runtime, id: Id) -> Id {
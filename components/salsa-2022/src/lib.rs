@@ -1,4 +1,5 @@
 pub mod accumulator;
+mod arena;
 pub mod cancelled;
 pub mod cycle;
 pub mod database;
@@ -16,17 +17,23 @@ pub mod interned;
 pub mod jar;
 pub mod key;
 pub mod plumbing;
+pub mod return_mode;
 pub mod revision;
 pub mod routes;
 pub mod runtime;
 pub mod salsa_struct;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod setter;
 pub mod storage;
+mod tombstones;
 #[doc(hidden)]
 pub mod tracked_struct;
 
+pub use self::cancelled::CancellationToken;
 pub use self::cancelled::Cancelled;
 pub use self::cycle::Cycle;
+pub use self::cycle::CycleParticipant;
 pub use self::database::Database;
 pub use self::database::ParallelDatabase;
 pub use self::database::Snapshot;
@@ -35,6 +42,8 @@ pub use self::debug::DebugWithDb;
 pub use self::durability::Durability;
 pub use self::event::Event;
 pub use self::event::EventKind;
+pub use self::event::EventKinds;
+pub use self::function::SpecifyError;
 pub use self::id::AsId;
 pub use self::id::Id;
 pub use self::key::DatabaseKeyIndex;
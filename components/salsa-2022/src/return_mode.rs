@@ -0,0 +1,36 @@
+//! Support for the `returns(as_ref)` option of tracked functions and
+//! salsa struct fields.
+
+/// Borrows the contents of a value, e.g. `Option<&T>` for an `Option<T>`.
+/// Used by the `returns(as_ref)` option, which returns `value.as_ref()`
+/// instead of a clone of the value or a reference to it.
+pub trait SalsaAsRef {
+    type AsRef<'a>
+    where
+        Self: 'a;
+
+    fn as_ref(&self) -> Self::AsRef<'_>;
+}
+
+impl<T> SalsaAsRef for Option<T> {
+    type AsRef<'a>
+        = Option<&'a T>
+    where
+        T: 'a;
+
+    fn as_ref(&self) -> Option<&T> {
+        self.as_ref()
+    }
+}
+
+impl<T, E> SalsaAsRef for Result<T, E> {
+    type AsRef<'a>
+        = Result<&'a T, &'a E>
+    where
+        T: 'a,
+        E: 'a;
+
+    fn as_ref(&self) -> Result<&T, &E> {
+        self.as_ref()
+    }
+}
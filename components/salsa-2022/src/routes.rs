@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 use crate::ingredient::IngredientRequiresReset;
+use crate::interned::SharedInterners;
 
 use super::{ingredient::Ingredient, storage::HasJars};
 
@@ -47,25 +50,47 @@ pub type DynMutRoute<DB: HasJars> =
 /// Each ingredient is registered with the database by invoking the [`Routes::push`] method.
 /// This method assigns it a unique [`IngredientIndex`] and stores some callbacks indicating
 /// how to find the ingredient later based only on the index.
+///
+/// Jars that are [registered](`crate::Storage::register_jar`) later on add their ingredients
+/// to a copy of the routes, which is why the callbacks are reference-counted.
 pub struct Routes<DB: HasJars> {
     /// Vector indexed by ingredient index. Yields the `DynRoute`,
     /// a function which can be applied to the `DB::Jars` to yield
     /// the `dyn Ingredient.
-    routes: Vec<(Box<DynRoute<DB>>, Box<DynMutRoute<DB>>)>,
+    routes: Vec<(Arc<DynRoute<DB>>, Arc<DynMutRoute<DB>>)>,
 
     /// Indices of routes which need a 'reset' call.
     needs_reset: Vec<IngredientIndex>,
+
+    /// Tables for the interned structs declared as `shared`.
+    shared_interners: SharedInterners,
+}
+
+impl<DB: HasJars> Clone for Routes<DB> {
+    fn clone(&self) -> Self {
+        Routes {
+            routes: self.routes.clone(),
+            needs_reset: self.needs_reset.clone(),
+            shared_interners: self.shared_interners.clone(),
+        }
+    }
 }
 
 impl<DB: HasJars> Routes<DB> {
     /// Construct an empty ingredients listing.
-    pub(super) fn new() -> Self {
+    pub(super) fn new(shared_interners: SharedInterners) -> Self {
         Routes {
             routes: vec![],
             needs_reset: vec![],
+            shared_interners,
         }
     }
 
+    /// The tables used by interned structs declared as `shared`.
+    pub fn shared_interners(&self) -> &SharedInterners {
+        &self.shared_interners
+    }
+
     /// Adds a new ingredient into the ingredients table, returning
     /// the `IngredientIndex` that can be used in a `DatabaseKeyIndex`.
     /// This index can then be used to fetch the "route" so that we can
@@ -89,8 +114,8 @@ impl<DB: HasJars> Routes<DB> {
     {
         let len = self.routes.len();
         self.routes.push((
-            Box::new(move |jars| route(jars)),
-            Box::new(move |jars| mut_route(jars)),
+            Arc::new(move |jars| route(jars)),
+            Arc::new(move |jars| mut_route(jars)),
         ));
         let index = IngredientIndex::from(len);
 
@@ -104,7 +129,7 @@ impl<DB: HasJars> Routes<DB> {
     /// Given an ingredient index, return the "route"
     /// (a function that, given a `&Jars`, returns the ingredient).
     pub fn route(&self, index: IngredientIndex) -> &dyn Fn(&DB::Jars) -> &dyn Ingredient<DB> {
-        &self.routes[index.as_usize()].0
+        &*self.routes[index.as_usize()].0
     }
 
     /// Given an ingredient index, return the "mut route"
@@ -113,7 +138,21 @@ impl<DB: HasJars> Routes<DB> {
         &self,
         index: IngredientIndex,
     ) -> &dyn Fn(&mut DB::Jars) -> &mut dyn Ingredient<DB> {
-        &self.routes[index.as_usize()].1
+        &*self.routes[index.as_usize()].1
+    }
+
+    /// Returns the routes for all ingredients.
+    pub fn all_routes(&self) -> impl Iterator<Item = &dyn Fn(&DB::Jars) -> &dyn Ingredient<DB>> + '_ {
+        self.routes.iter().map(|(route, _)| &**route as &dyn Fn(&DB::Jars) -> &dyn Ingredient<DB>)
+    }
+
+    /// Returns the mut routes for all ingredients.
+    pub fn all_mut_routes(
+        &self,
+    ) -> impl Iterator<Item = &dyn Fn(&mut DB::Jars) -> &mut dyn Ingredient<DB>> + '_ {
+        self.routes
+            .iter()
+            .map(|(_, mut_route)| &**mut_route as &dyn Fn(&mut DB::Jars) -> &mut dyn Ingredient<DB>)
     }
 
     /// Returns the mut routes for ingredients that need to be reset at the start of each revision.
@@ -1,25 +1,36 @@
+use crossbeam::atomic::AtomicCell;
 use std::{
-    panic::panic_any,
+    panic::{self, panic_any, AssertUnwindSafe, Location},
     sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
 };
 
 use crate::{
+    cancelled::CancellationToken,
     cycle::CycleRecoveryStrategy,
     debug::DebugWithDb,
     durability::Durability,
     key::{DatabaseKeyIndex, DependencyIndex},
     runtime::active_query::ActiveQuery,
-    Cancelled, Cycle, Database, Event, EventKind, Revision,
+    Cancelled, Cycle, Database, Event, EventKind, EventKinds, Revision,
 };
 
-use self::{dependency_graph::DependencyGraph, local_state::ActiveQueryGuard};
+use self::{
+    dependency_graph::DependencyGraph, local_state::ActiveQueryGuard, wait_graph::PublishedStack,
+};
 
 use super::{tracked_struct::Disambiguator, IngredientIndex};
 
 mod active_query;
+mod change_log;
 mod dependency_graph;
 pub mod local_state;
 mod shared_state;
+mod verification_pool;
+mod wait_graph;
+
+pub(crate) use self::verification_pool::VerificationPool;
+pub use self::wait_graph::{BlockedRuntime, RunningRuntime, StallMonitor, WaitGraph};
 
 pub struct Runtime {
     /// Our unique runtime id.
@@ -30,8 +41,29 @@ pub struct Runtime {
 
     /// Shared state that is accessible via all runtimes.
     shared_state: Arc<shared_state::SharedState>,
+
+    /// Token that can be used to cancel this runtime specifically.
+    cancellation_token: CancellationToken,
+
+    /// True for the runtimes of [verification snapshots](`Runtime::verification_snapshot`),
+    /// which only verify memoized values and never execute queries.
+    verify_only: bool,
+
+    /// The kinds of events the database wants (see [`Database::salsa_event_kinds`]).
+    event_kinds: EventKinds,
+
+    /// True if the database was declared `no_snapshots`: it cannot be snapshotted,
+    /// so queries are never claimed by another runtime (see [`Self::snapshots_disabled`]).
+    no_snapshots: bool,
+
+    /// The durability of the values about to be overwritten, declared by a setter
+    /// before it starts a new revision; see [`Self::declare_write_durability`].
+    next_write_durability: AtomicCell<Option<Durability>>,
 }
 
+/// Panic payload used to unwind out of [`Runtime::run_isolated`].
+struct AbandonedIsolatedExecution;
+
 #[derive(Clone, Debug)]
 pub(crate) enum WaitResult {
     Completed,
@@ -67,6 +99,11 @@ impl Default for Runtime {
             id: RuntimeId { counter: 0 },
             shared_state: Default::default(),
             local_state: Default::default(),
+            cancellation_token: Default::default(),
+            verify_only: false,
+            event_kinds: EventKinds::DEFAULT,
+            no_snapshots: false,
+            next_write_durability: Default::default(),
         }
     }
 }
@@ -85,7 +122,8 @@ impl Runtime {
         self.id
     }
 
-    pub(crate) fn current_revision(&self) -> Revision {
+    /// The revision the database is currently in; see [`crate::Storage::rollback_to`].
+    pub fn current_revision(&self) -> Revision {
         self.shared_state.revisions[0].load()
     }
 
@@ -100,6 +138,10 @@ impl Runtime {
     }
 
     pub fn snapshot(&self) -> Self {
+        if self.no_snapshots {
+            panic!("it is not legal to `snapshot` a database declared with `no_snapshots`");
+        }
+
         if self.local_state.query_in_progress() {
             panic!("it is not legal to `snapshot` during a query (see salsa-rs/salsa#80)");
         }
@@ -112,6 +154,132 @@ impl Runtime {
             id,
             shared_state: self.shared_state.clone(),
             local_state: Default::default(),
+            cancellation_token: Default::default(),
+            verify_only: false,
+            event_kinds: self.event_kinds,
+            no_snapshots: self.no_snapshots,
+            next_write_durability: Default::default(),
+        }
+    }
+
+    /// Creates a snapshot used to verify the dependencies of memoized values on
+    /// another thread (see [`crate::Database::snapshot_for_verification`]).
+    ///
+    /// Unlike [`Self::snapshot`], this may be called during a query. A query that
+    /// cannot be verified through the resulting runtime is reported as changed,
+    /// rather than executed, so no user code runs on verification threads.
+    pub fn verification_snapshot(&self) -> Self {
+        if self.no_snapshots {
+            panic!("it is not legal to `snapshot` a database declared with `no_snapshots`");
+        }
+
+        let id = RuntimeId {
+            counter: self.shared_state.next_id.fetch_add(1, Ordering::SeqCst),
+        };
+
+        Runtime {
+            id,
+            shared_state: self.shared_state.clone(),
+            local_state: Default::default(),
+            cancellation_token: Default::default(),
+            verify_only: true,
+            event_kinds: self.event_kinds,
+            no_snapshots: self.no_snapshots,
+            next_write_durability: Default::default(),
+        }
+    }
+
+    /// True if this is the runtime of a verification snapshot, which must not execute queries.
+    pub(crate) fn is_verify_only(&self) -> bool {
+        self.verify_only
+    }
+
+    /// See [`crate::Storage::set_verification_threads`].
+    pub(crate) fn set_verification_threads(&mut self, threads: usize) {
+        let pool = (threads > 0).then(|| Arc::new(VerificationPool::new(threads)));
+        *self.shared_state.verification_pool.lock() = pool;
+    }
+
+    /// The worker threads used to verify the dependencies of memoized values in parallel,
+    /// if parallel verification is enabled.
+    pub(crate) fn verification_pool(&self) -> Option<Arc<VerificationPool>> {
+        if self.no_snapshots {
+            return None;
+        }
+
+        self.shared_state.verification_pool.lock().clone()
+    }
+
+    /// Creates a runtime for a database with `durabilities` levels of durability.
+    pub(crate) fn new(durabilities: usize, event_kinds: EventKinds, no_snapshots: bool) -> Self {
+        Runtime {
+            shared_state: Arc::new(shared_state::SharedState::with_durabilities(durabilities)),
+            event_kinds,
+            no_snapshots,
+            next_write_durability: Default::default(),
+            ..Default::default()
+        }
+    }
+
+    /// True if the database was declared with `#[salsa::db(no_snapshots)]`.
+    ///
+    /// Such a database has a single runtime, so claiming a query for execution is uncontended:
+    /// the claim is not recorded in the query's synchronization map, and a query that is
+    /// already claimed is known to be on our own stack (a cycle). Such a database is also the only
+    /// kind whose values need not be `Send + Sync` (see [`crate::storage::AllowsSnapshots`]).
+    /// Its memo tables and the dependency graph used to report cycles
+    /// still use the same thread-safe data structures as any other database.
+    pub fn snapshots_disabled(&self) -> bool {
+        self.no_snapshots
+    }
+
+    /// True if the database wants events of (one of) the given kinds.
+    #[inline]
+    pub fn reports_events(&self, kinds: EventKinds) -> bool {
+        self.event_kinds.intersects(kinds)
+    }
+
+    /// Passes the event created by `event_kind` to [`Database::salsa_event`],
+    /// unless the database does not want events of kind `kinds`,
+    /// in which case the event is not created at all.
+    #[inline]
+    pub(crate) fn report_event<DB: ?Sized + Database>(
+        &self,
+        db: &DB,
+        kinds: EventKinds,
+        event_kind: impl FnOnce() -> EventKind,
+    ) {
+        if self.reports_events(kinds) && !self.local_state.is_isolated() {
+            db.salsa_event(Event {
+                runtime_id: self.id(),
+                kind: event_kind(),
+            });
+        }
+    }
+
+    /// Creates the runtime for a fork of the database (see [`crate::Storage::fork`]).
+    /// The fork starts out in the current revision, but has its own shared state,
+    /// so new revisions in the fork do not affect this runtime (and vice versa).
+    pub fn fork(&self) -> Self {
+        if self.local_state.query_in_progress() {
+            panic!("it is not legal to `fork` during a query");
+        }
+
+        self.differential_fork()
+    }
+
+    /// Like [`Self::fork`], but also legal while a query is executing
+    /// (see [`crate::Storage::differential_fork`]).
+    pub(crate) fn differential_fork(&self) -> Self {
+        Runtime {
+            id: RuntimeId { counter: 0 },
+            shared_state: Arc::new(self.shared_state.fork()),
+            local_state: Default::default(),
+            cancellation_token: Default::default(),
+            verify_only: false,
+            event_kinds: self.event_kinds,
+            no_snapshots: self.no_snapshots,
+            next_write_durability: Default::default(),
         }
     }
 
@@ -134,18 +302,138 @@ impl Runtime {
             .report_untracked_read(self.current_revision());
     }
 
-    /// Reports that an input with durability `durability` changed.
+    /// Reports that the input `key`, whose value had durability `durability`, changed.
     /// This will update the 'last changed at' values for every durability
     /// less than or equal to `durability` to the current revision.
-    pub(crate) fn report_tracked_write(&mut self, durability: Durability) {
+    /// The write is recorded in the [change log](`Self::change_log`) with the durability
+    /// of the overwritten value, not that of the new one.
+    pub(crate) fn report_tracked_write(&mut self, key: DatabaseKeyIndex, durability: Durability) {
+        self.record_write(Some(key), durability);
+    }
+
+    /// Acts as though some input of durability `durability` changed in the current revision;
+    /// see [`crate::Storage::synthetic_write`], which starts a new revision first.
+    pub(crate) fn synthetic_write(&mut self, durability: Durability) {
+        self.assert_valid_durability(durability);
+        self.record_write(None, durability);
+    }
+
+    fn record_write(&mut self, key: Option<DatabaseKeyIndex>, durability: Durability) {
         let new_revision = self.current_revision();
-        for rev in &self.shared_state.revisions[1..=durability.index()] {
+        for rev in &self.shared_state.revisions[1..=self.durability_index(durability)] {
             rev.store(new_revision);
         }
+        self.shared_state
+            .change_log
+            .lock()
+            .push(new_revision, key, durability);
+    }
+
+    /// Iterates over the most recent writes to inputs, oldest first:
+    /// the revision of each write, the key that was written (`None` for a [synthetic write](`crate::Storage::synthetic_write`)),
+    /// and the durability of the value that was overwritten.
+    ///
+    /// Only writes that overwrite a value are recorded, not the initial values of new inputs.
+    /// The log keeps a bounded number of writes (see [`crate::Storage::set_change_log_capacity`]),
+    /// dropping the oldest ones first.
+    pub fn change_log(
+        &self,
+    ) -> impl Iterator<Item = (Revision, Option<DatabaseKeyIndex>, Durability)> {
+        let entries: Vec<_> = self.shared_state.change_log.lock().entries().collect();
+        entries.into_iter()
+    }
+
+    /// Returns the writes made in `revision`, or `None` if some of them were already
+    /// dropped from the [change log](`Self::change_log`).
+    pub fn changes_in(
+        &self,
+        revision: Revision,
+    ) -> Option<Vec<(Option<DatabaseKeyIndex>, Durability)>> {
+        let change_log = self.shared_state.change_log.lock();
+        if !change_log.is_complete(revision) {
+            return None;
+        }
+        Some(
+            change_log
+                .entries()
+                .filter(|&(r, _, _)| r == revision)
+                .map(|(_, key, durability)| (key, durability))
+                .collect(),
+        )
+    }
+
+    /// See [`crate::Storage::set_change_log_capacity`].
+    pub(crate) fn set_change_log_capacity(&mut self, capacity: usize) {
+        self.shared_state.change_log.lock().set_capacity(capacity);
+    }
+
+    /// See [`crate::Storage::set_differential_verification`].
+    pub(crate) fn set_differential_verification(&mut self, one_in: usize) {
+        self.shared_state
+            .differential_verification
+            .store(one_in, Ordering::SeqCst);
+    }
+
+    /// Invoked when a fetch returns a memoized or backdated value;
+    /// true if the value should be checked against an execution from scratch.
+    pub(crate) fn sample_differential_verification(&self) -> bool {
+        let one_in = self
+            .shared_state
+            .differential_verification
+            .load(Ordering::Relaxed);
+        if one_in == 0 || self.verify_only || self.local_state.is_isolated() {
+            return false;
+        }
+        self.shared_state
+            .differential_fetches
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(one_in)
+    }
+
+    /// True while queries are executed from scratch for differential verification:
+    /// they neither use nor update the memoized values, and report no events.
+    pub(crate) fn is_isolated(&self) -> bool {
+        self.local_state.is_isolated()
+    }
+
+    /// Invokes `op` with queries executed from scratch (see [`Self::is_isolated`]).
+    /// Returns `None` if an execution ran into a cycle (see [`Self::abandon_isolated_execution`]).
+    /// Invoked on the runtime of a throwaway fork of the database
+    /// (see [`crate::Database::fork_for_differential_verification`]),
+    /// so that nothing `op` interns, creates or computes outlives the check.
+    pub(crate) fn run_isolated<T>(&self, op: impl FnOnce() -> T) -> Option<T> {
+        struct Reset<'me>(&'me local_state::LocalState, bool);
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.set_isolated(self.1);
+            }
+        }
+
+        let _reset = Reset(&self.local_state, self.local_state.set_isolated(true));
+        match panic::catch_unwind(AssertUnwindSafe(op)) {
+            Ok(value) => Some(value),
+            Err(payload) if payload.is::<AbandonedIsolatedExecution>() => None,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Invoked when a query executed from scratch depends on itself.
+    /// Executions from scratch do not attempt to recover from cycles:
+    /// the memoized value is simply not checked.
+    pub(crate) fn abandon_isolated_execution(&self) -> ! {
+        panic::resume_unwind(Box::new(AbandonedIsolatedExecution))
     }
 
     /// Adds `key` to the list of output created by the current query
     /// (if not already present).
+    /// Reports that an input of the active query was verified (or executed again)
+    /// and that it, or one of its own inputs, was last executed in `revision`.
+    /// Used to find the memos whose accumulated values may have changed
+    /// (see [`crate::function::FunctionIngredient::accumulated_diff`]).
+    pub(crate) fn report_subtree_executed_at(&self, revision: Revision) {
+        self.local_state.report_subtree_executed_at(revision)
+    }
+
     pub(crate) fn add_output(&self, key: DependencyIndex) {
         self.local_state.add_output(key);
     }
@@ -155,6 +443,11 @@ impl Runtime {
         self.local_state.is_output(entity)
     }
 
+    /// Check whether `database_key_index` is executing (or being verified) on this thread.
+    pub(crate) fn is_query_on_stack(&self, database_key_index: DatabaseKeyIndex) -> bool {
+        self.local_state.is_on_stack(database_key_index)
+    }
+
     /// Called when the active queries creates an index from the
     /// entity table with the index `entity_index`. Has the following effects:
     ///
@@ -185,7 +478,25 @@ impl Runtime {
     /// dependencies.
     #[inline]
     pub(crate) fn last_changed_revision(&self, d: Durability) -> Revision {
-        self.shared_state.revisions[d.index()].load()
+        self.shared_state.revisions[self.durability_index(d)].load()
+    }
+
+    /// The index of `d` in the `revisions` of the shared state.
+    /// [`Durability::MAX`] stands for the highest level.
+    #[inline]
+    fn durability_index(&self, d: Durability) -> usize {
+        d.index().min(self.shared_state.revisions.len() - 1)
+    }
+
+    /// Panics if `d` exceeds the number of durability levels this database was configured with.
+    #[track_caller]
+    pub(crate) fn assert_valid_durability(&self, d: Durability) {
+        assert!(
+            d.index() < self.shared_state.revisions.len(),
+            "{:?} is invalid: the database has {} durability levels",
+            d,
+            self.shared_state.revisions.len()
+        );
     }
 
     /// Starts unwinding the stack if the current revision is cancelled.
@@ -197,31 +508,73 @@ impl Runtime {
     /// Cancellation will automatically be triggered by salsa on any query
     /// invocation.
     ///
+    /// A pending write only cancels this runtime if it has read some value
+    /// whose durability is less than or equal to the durability of the write
+    /// (see [`Self::declare_write_durability`]).
+    /// Other runtimes are allowed to finish; the writer blocks until they do.
+    ///
     /// This method should not be overridden by `Database` implementors. A
     /// `salsa_event` is emitted when this method is called, so that should be
     /// used instead.
-    pub(crate) fn unwind_if_revision_cancelled<DB: ?Sized + Database>(&self, db: &DB) {
-        db.salsa_event(Event {
-            runtime_id: self.id(),
-            kind: EventKind::WillCheckCancellation,
+    pub fn unwind_if_revision_cancelled<DB: ?Sized + Database>(&self, db: &DB) {
+        self.report_event(db, EventKinds::WILL_CHECK_CANCELLATION, || {
+            EventKind::WillCheckCancellation
         });
-        if self.shared_state.revision_canceled.load() {
-            db.salsa_event(Event {
-                runtime_id: self.id(),
-                kind: EventKind::WillCheckCancellation,
-            });
-            self.unwind_cancelled();
+        if self.cancellation_token.is_cancelled() {
+            self.unwind_cancelled(Cancelled::Requested);
+        }
+        if let Some(write_durability) = self.shared_state.pending_write.load() {
+            if self.local_state.min_durability_read() <= write_durability {
+                self.unwind_cancelled(Cancelled::PendingWrite);
+            }
         }
     }
 
     #[cold]
-    pub(crate) fn unwind_cancelled(&self) {
+    pub(crate) fn unwind_cancelled(&self, reason: Cancelled) {
         self.report_untracked_read();
-        Cancelled::PendingWrite.throw();
+        reason.throw();
+    }
+
+    /// Signals that a write of durability `durability` is pending,
+    /// cancelling the runtimes that may have read values affected by it.
+    pub(crate) fn set_cancellation_flag(&self, durability: Durability) {
+        self.shared_state.pending_write.store(Some(durability));
+    }
+
+    /// Declares that the next revision is started to overwrite a value of durability `durability`,
+    /// so that it only cancels the runtimes that may have read such a value. The setters of inputs
+    /// declare the durability of the value they overwrite. Without a declaration (e.g., for
+    /// [`crate::Storage::jars_mut`]), the write is assumed to have the maximum durability.
+    pub(crate) fn declare_write_durability(&self, durability: Durability) {
+        let declared = self.next_write_durability.load();
+        self.next_write_durability.store(Some(
+            declared.map_or(durability, |declared| declared.max(durability)),
+        ));
+    }
+
+    /// Takes the durability declared with [`Self::declare_write_durability`] for the next write.
+    pub(crate) fn take_write_durability(&mut self) -> Durability {
+        self.next_write_durability.take().unwrap_or(Durability::MAX)
+    }
+
+    /// Returns a token that can be used (from any thread) to cancel this runtime,
+    /// without having to write to the database. This is typically used to cancel
+    /// one particular snapshot.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    /// Returns a handle that can be used (from any thread) to inspect which
+    /// runtimes of this database are blocked on one another, e.g. to diagnose
+    /// an apparent hang.
+    pub fn stall_monitor(&self) -> StallMonitor {
+        StallMonitor::new(&self.shared_state)
     }
 
-    pub(crate) fn set_cancellation_flag(&self) {
-        self.shared_state.revision_canceled.store(true);
+    /// See [`crate::Storage::set_stall_warning_timeout`].
+    pub(crate) fn set_stall_warning_timeout(&mut self, timeout: Option<Duration>) {
+        self.shared_state.stall_warning_timeout.store(timeout);
     }
 
     /// Increments the "current revision" counter and clears
@@ -232,12 +585,19 @@ impl Runtime {
         let r_old = self.current_revision();
         let r_new = r_old.next();
         self.shared_state.revisions[0].store(r_new);
-        self.shared_state.revision_canceled.store(false);
+        self.shared_state.pending_write.store(None);
+        self.cancellation_token.reset();
+        self.local_state.reset_durability_read();
         r_new
     }
 
     #[inline]
     pub(crate) fn push_query(&self, database_key_index: DatabaseKeyIndex) -> ActiveQueryGuard<'_> {
+        if self.shared_state.publish_stacks.load(Ordering::Relaxed) {
+            self.local_state.publish_query_stack(|query_stack| {
+                PublishedStack::new(self.id, &self.shared_state, query_stack)
+            });
+        }
         self.local_state.push_query(database_key_index)
     }
 
@@ -263,13 +623,23 @@ impl Runtime {
     ///
     /// * [`CycleRecoveryStrategy::Panic`]: panic with the [`Cycle`] as the value.
     /// * [`CycleRecoveryStrategy::Fallback`]: initiate unwinding with [`CycleParticipant::unwind`].
+    ///
+    /// # Stall warnings
+    ///
+    /// `blocked_since` is `None` the first time we block on `database_key`.
+    /// If we are still blocked once the [stall warning timeout](`crate::Storage::set_stall_warning_timeout`)
+    /// has elapsed, we stop waiting, report [`EventKind::StillBlockedOn`] with our query stack
+    /// restored, and return when we started to wait. The caller must then claim
+    /// `database_key` again, passing that back to wait for the rest of the time.
+    #[track_caller]
     pub(crate) fn block_on_or_unwind<QueryMutexGuard>(
         &self,
         db: &dyn Database,
         database_key: DatabaseKeyIndex,
         other_id: RuntimeId,
         query_mutex_guard: QueryMutexGuard,
-    ) {
+        blocked_since: Option<Instant>,
+    ) -> Option<Instant> {
         let mut dg = self.shared_state.dependency_graph.lock();
 
         if dg.depends_on(other_id, self.id()) {
@@ -280,16 +650,32 @@ impl Runtime {
             assert!(!dg.depends_on(other_id, self.id()));
         }
 
-        db.salsa_event(Event {
-            runtime_id: self.id(),
-            kind: EventKind::WillBlockOn {
-                other_runtime_id: other_id,
-                database_key: database_key.into(),
-            },
-        });
+        let start = blocked_since.unwrap_or_else(Instant::now);
+        let stall_deadline = match blocked_since {
+            Some(_) => None,
+            None => {
+                self.report_event(db, EventKinds::WILL_BLOCK_ON, || EventKind::WillBlockOn {
+                    other_runtime_id: other_id,
+                    database_key,
+                });
+                self.shared_state
+                    .stall_warning_timeout
+                    .load()
+                    .map(|timeout| start + timeout)
+            }
+        };
 
         let stack = self.local_state.take_query_stack();
 
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "block_on",
+            ingredient = db.debug_name(database_key.ingredient_index),
+            key = database_key.key_index.as_u32(),
+            other_runtime = ?other_id,
+        )
+        .entered();
+
         let (stack, result) = DependencyGraph::block_on(
             dg,
             self.id(),
@@ -297,22 +683,47 @@ impl Runtime {
             other_id,
             stack,
             query_mutex_guard,
+            stall_deadline,
         );
 
+        #[cfg(feature = "tracing")]
+        drop(span);
+
         self.local_state.restore_query_stack(stack);
 
         match result {
-            WaitResult::Completed => (),
+            Some(WaitResult::Completed) => None,
 
             // If the other thread panicked, then we consider this thread
             // cancelled. The assumption is that the panic will be detected
             // by the other thread and responded to appropriately.
-            WaitResult::Panicked => Cancelled::PropagatedPanic.throw(),
+            Some(WaitResult::Panicked) => Cancelled::PropagatedPanic.throw(),
 
-            WaitResult::Cycle(c) => c.throw(),
+            Some(WaitResult::Cycle(c)) => c.throw(),
+
+            None => {
+                self.report_event(db, EventKinds::STILL_BLOCKED_ON, || {
+                    EventKind::StillBlockedOn {
+                        other_runtime_id: other_id,
+                        database_key,
+                        elapsed: start.elapsed(),
+                    }
+                });
+                Some(start)
+            }
         }
     }
 
+    /// Handles a cycle detected in a `no_snapshots` database, where `database_key`
+    /// is already executing further up our own stack.
+    /// Throws the cycle if a participant has cycle recovery, and panics otherwise.
+    #[track_caller]
+    pub(crate) fn unwind_cycle(&self, db: &dyn Database, database_key: DatabaseKeyIndex) -> ! {
+        let mut dg = self.shared_state.dependency_graph.lock();
+        self.unblock_cycle_and_maybe_throw(db, &mut dg, database_key, self.id());
+        unreachable!("only other runtimes can recover from a cycle without unwinding")
+    }
+
     /// Handles a cycle in the dependency graph that was detected when the
     /// current thread tried to block on `database_key_index` which is being
     /// executed by `to_id`. If this function returns, then `to_id` no longer
@@ -321,6 +732,7 @@ impl Runtime {
     /// to be caught by some frame on our stack. This occurs either if there is
     /// a frame on our stack with cycle recovery (possibly the top one!) or if there
     /// is no cycle recovery at all.
+    #[track_caller]
     fn unblock_cycle_and_maybe_throw(
         &self,
         db: &dyn Database,
@@ -368,7 +780,11 @@ impl Runtime {
             // No need to store extra memory.
             v.shrink_to_fit();
 
-            Cycle::new(Arc::new(v))
+            // The query stack is taken while we hold the dependency graph, so format the
+            // participants with an empty one: the reads of their key data are not dependencies.
+            let location = Location::caller();
+            self.local_state
+                .with_empty_query_stack(|| Cycle::new(db, Arc::new(v), location))
         };
         log::debug!(
             "cycle {:?}, cycle_query {:#?}",
@@ -45,6 +45,11 @@ pub(super) struct ActiveQuery {
     /// We use a btree-set because we want to be able to
     /// extract the keys in sorted order.
     pub(super) outputs: BTreeSet<DependencyIndex>,
+
+    /// While verifying the memo for this query by checking its inputs, the last revision in
+    /// which one of them (or one of their own inputs, transitively) was executed; see
+    /// [`crate::Runtime::report_subtree_executed_at`].
+    pub(super) inputs_executed_at: Revision,
 }
 
 impl ActiveQuery {
@@ -58,6 +63,7 @@ impl ActiveQuery {
             cycle: None,
             disambiguator_map: Default::default(),
             outputs: Default::default(),
+            inputs_executed_at: Revision::start(),
         }
     }
 
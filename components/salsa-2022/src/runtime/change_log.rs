@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+
+use crate::{durability::Durability, key::DatabaseKeyIndex, revision::Revision};
+
+/// The number of writes kept by default; see [`crate::Storage::set_change_log_capacity`].
+pub(super) const DEFAULT_CAPACITY: usize = 1024;
+
+/// The most recent writes to inputs, oldest first.
+#[derive(Clone, Debug)]
+pub(super) struct ChangeLog {
+    /// Maximum number of entries; older entries are dropped to make room for new ones.
+    capacity: usize,
+
+    /// The revision in which each write happened, the key that was written
+    /// (`None` for a [synthetic write](`crate::Storage::synthetic_write`)),
+    /// and the durability of the overwritten value.
+    entries: VecDeque<(Revision, Option<DatabaseKeyIndex>, Durability)>,
+
+    /// The revision of the newest entry that was dropped, if any.
+    dropped_through: Option<Revision>,
+}
+
+impl Default for ChangeLog {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            entries: VecDeque::new(),
+            dropped_through: None,
+        }
+    }
+}
+
+impl ChangeLog {
+    pub(super) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    pub(super) fn push(
+        &mut self,
+        revision: Revision,
+        key: Option<DatabaseKeyIndex>,
+        durability: Durability,
+    ) {
+        self.entries.push_back((revision, key, durability));
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.entries.len() > self.capacity {
+            let (revision, _, _) = self.entries.pop_front().unwrap();
+            self.dropped_through = Some(revision);
+        }
+    }
+
+    pub(super) fn entries(
+        &self,
+    ) -> impl Iterator<Item = (Revision, Option<DatabaseKeyIndex>, Durability)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// True if none of the writes in `revision` were dropped.
+    pub(super) fn is_complete(&self, revision: Revision) -> bool {
+        self.dropped_through
+            .is_none_or(|dropped| dropped < revision)
+    }
+}
@@ -1,11 +1,15 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use crate::key::DatabaseKeyIndex;
 use parking_lot::{Condvar, MutexGuard};
 use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
 
-use super::{active_query::ActiveQuery, RuntimeId, WaitResult};
+use super::{
+    active_query::ActiveQuery,
+    wait_graph::{BlockedRuntime, PublishedStacks, RunningRuntime, WaitGraph},
+    RuntimeId, WaitResult,
+};
 
 type QueryStack = Vec<ActiveQuery>;
 
@@ -188,6 +192,10 @@ impl DependencyGraph {
     /// This ensures that computing `database_key` doesn't
     /// complete before `block_on` executes.
     ///
+    /// If we are still blocked at `stall_deadline`, we stop waiting so that
+    /// the stall can be reported: the edge is removed again and the wait
+    /// result is `None`.
+    ///
     /// Preconditions:
     /// * No path from `to_id` to `from_id`
     ///   (i.e., `me.depends_on(to_id, from_id)` is false)
@@ -199,7 +207,8 @@ impl DependencyGraph {
         to_id: RuntimeId,
         from_stack: QueryStack,
         query_mutex_guard: QueryMutexGuard,
-    ) -> (QueryStack, WaitResult) {
+        stall_deadline: Option<Instant>,
+    ) -> (QueryStack, Option<WaitResult>) {
         let condvar = me.add_edge(from_id, database_key, to_id, from_stack);
 
         // Release the mutex that prevents `database_key`
@@ -207,14 +216,54 @@ impl DependencyGraph {
         drop(query_mutex_guard);
 
         loop {
-            if let Some(stack_and_result) = me.wait_results.remove(&from_id) {
+            if let Some((stack, result)) = me.wait_results.remove(&from_id) {
                 debug_assert!(!me.edges.contains_key(&from_id));
-                return stack_and_result;
+                return (stack, Some(result));
+            }
+            match stall_deadline {
+                Some(deadline) => {
+                    if condvar.wait_until(&mut me, deadline).timed_out()
+                        && !me.wait_results.contains_key(&from_id)
+                    {
+                        return (me.remove_edge(from_id), None);
+                    }
+                }
+                None => condvar.wait(&mut me),
             }
-            condvar.wait(&mut me);
         }
     }
 
+    /// Returns a snapshot of the blocked runtimes and of the running runtimes
+    /// that have `published` their query stacks, each ordered by id.
+    pub(super) fn wait_graph(&self, published: &PublishedStacks) -> WaitGraph {
+        let mut blocked: Vec<BlockedRuntime> = self
+            .edges
+            .iter()
+            .map(|(&runtime_id, edge)| BlockedRuntime {
+                runtime_id,
+                query_stack: edge.stack.iter().map(|aq| aq.database_key_index).collect(),
+                blocked_on_key: edge.blocked_on_key,
+                blocked_on_runtime: edge.blocked_on_id,
+            })
+            .collect();
+        blocked.sort_by_key(|b| b.runtime_id);
+
+        let mut running: Vec<RunningRuntime> = published
+            .iter()
+            .filter(|(runtime_id, _)| !self.edges.contains_key(runtime_id))
+            .filter_map(|(&runtime_id, query_stack)| {
+                let query_stack = query_stack.lock().clone();
+                (!query_stack.is_empty()).then_some(RunningRuntime {
+                    runtime_id,
+                    query_stack,
+                })
+            })
+            .collect();
+        running.sort_by_key(|r| r.runtime_id);
+
+        WaitGraph { blocked, running }
+    }
+
     /// Helper for `block_on`: performs actual graph modification
     /// to add a dependency edge from `from_id` to `to_id`, which is
     /// computing `database_key`.
@@ -246,6 +295,19 @@ impl DependencyGraph {
         condvar
     }
 
+    /// Helper for `block_on`: removes the edge added by `add_edge`
+    /// when `from_id` stops waiting, returning its query stack.
+    fn remove_edge(&mut self, from_id: RuntimeId) -> QueryStack {
+        let edge = self.edges.remove(&from_id).expect("not blocked");
+        if let Some(dependents) = self.query_dependents.get_mut(&edge.blocked_on_key) {
+            dependents.retain(|r| *r != from_id);
+            if dependents.is_empty() {
+                self.query_dependents.remove(&edge.blocked_on_key);
+            }
+        }
+        edge.stack
+    }
+
     /// Invoked when runtime `to_id` completes executing
     /// `database_key`.
     pub(super) fn unblock_runtimes_blocked_on(
@@ -7,10 +7,11 @@ use crate::runtime::Revision;
 use crate::tracked_struct::Disambiguator;
 use crate::Cycle;
 use crate::Runtime;
-use std::cell::RefCell;
+use std::cell::{Cell, OnceCell, RefCell};
 use std::sync::Arc;
 
 use super::active_query::ActiveQuery;
+use super::wait_graph::PublishedStack;
 use super::StampedValue;
 
 /// State that is specific to a single execution thread.
@@ -28,6 +29,18 @@ pub(super) struct LocalState {
     /// Unwinding note: pushes onto this vector must be popped -- even
     /// during unwinding.
     query_stack: RefCell<Option<Vec<ActiveQuery>>>,
+
+    /// Minimum durability of all the values read by this runtime so far.
+    /// Used to decide whether a pending write must cancel this runtime.
+    min_durability_read: Cell<Durability>,
+
+    /// True while queries are executed from scratch for differential verification
+    /// (see [`crate::Storage::set_differential_verification`]).
+    isolated: Cell<bool>,
+
+    /// A copy of the query stack for [`crate::StallMonitor`],
+    /// kept once a stall monitor has been created.
+    published_stack: OnceCell<PublishedStack>,
 }
 
 /// Summarizes "all the inputs that a query used"
@@ -80,6 +93,16 @@ pub enum QueryOrigin {
 }
 
 impl QueryOrigin {
+    /// Indices for queries *read* by this query (or `&[]` if its value was not derived).
+    pub(crate) fn inputs(&self) -> impl Iterator<Item = DependencyIndex> + '_ {
+        let slice = match self {
+            QueryOrigin::Derived(edges) | QueryOrigin::DerivedUntracked(edges) => edges.inputs(),
+            QueryOrigin::Assigned(_) | QueryOrigin::BaseInput | QueryOrigin::Field => &[],
+        };
+
+        slice.iter().copied()
+    }
+
     /// Indices for queries *written* by this query (or `&[]` if its value was assigned).
     pub(crate) fn outputs(&self) -> impl Iterator<Item = DependencyIndex> + '_ {
         let slice = match self {
@@ -143,6 +166,9 @@ impl Default for LocalState {
     fn default() -> Self {
         LocalState {
             query_stack: RefCell::new(Some(Vec::new())),
+            min_durability_read: Cell::new(Durability::MAX),
+            isolated: Cell::new(false),
+            published_stack: OnceCell::new(),
         }
     }
 }
@@ -153,6 +179,9 @@ impl LocalState {
         let mut query_stack = self.query_stack.borrow_mut();
         let query_stack = query_stack.as_mut().expect("local stack taken");
         query_stack.push(ActiveQuery::new(database_key_index));
+        if let Some(published_stack) = self.published_stack.get() {
+            published_stack.push(database_key_index);
+        }
         ActiveQueryGuard {
             local_state: self,
             database_key_index,
@@ -160,6 +189,18 @@ impl LocalState {
         }
     }
 
+    /// Starts keeping a [`PublishedStack`] of the query stack, unless already done.
+    pub(super) fn publish_query_stack(
+        &self,
+        publish: impl FnOnce(Vec<DatabaseKeyIndex>) -> PublishedStack,
+    ) {
+        if self.published_stack.get().is_none() {
+            let query_stack = self
+                .with_query_stack(|stack| stack.iter().map(|aq| aq.database_key_index).collect());
+            let _ = self.published_stack.set(publish(query_stack));
+        }
+    }
+
     fn with_query_stack<R>(&self, c: impl FnOnce(&mut Vec<ActiveQuery>) -> R) -> R {
         c(self
             .query_stack
@@ -189,6 +230,23 @@ impl LocalState {
         })
     }
 
+    /// True if `database_key_index` is anywhere on the query stack.
+    pub(super) fn is_on_stack(&self, database_key_index: DatabaseKeyIndex) -> bool {
+        self.with_query_stack(|stack| {
+            stack
+                .iter()
+                .any(|query| query.database_key_index == database_key_index)
+        })
+    }
+
+    pub(super) fn report_subtree_executed_at(&self, revision: Revision) {
+        self.with_query_stack(|stack| {
+            if let Some(top_query) = stack.last_mut() {
+                top_query.inputs_executed_at = top_query.inputs_executed_at.max(revision);
+            }
+        })
+    }
+
     pub(super) fn add_output(&self, entity: DependencyIndex) {
         self.with_query_stack(|stack| {
             if let Some(top_query) = stack.last_mut() {
@@ -217,6 +275,7 @@ impl LocalState {
             "report_query_read_and_unwind_if_cycle_resulted(input={:?}, durability={:?}, changed_at={:?})",
             input, durability, changed_at
         );
+        self.note_durability_read(durability);
         self.with_query_stack(|stack| {
             if let Some(top_query) = stack.last_mut() {
                 top_query.add_read(input, durability, changed_at);
@@ -249,7 +308,32 @@ impl LocalState {
         })
     }
 
+    /// Minimum durability of the values this runtime has read so far
+    /// (or since the last call to [`Self::reset_durability_read`]).
+    pub(super) fn min_durability_read(&self) -> Durability {
+        self.min_durability_read.get()
+    }
+
+    pub(super) fn is_isolated(&self) -> bool {
+        self.isolated.get()
+    }
+
+    /// Sets whether queries are executed from scratch and returns the previous setting.
+    pub(super) fn set_isolated(&self, isolated: bool) -> bool {
+        self.isolated.replace(isolated)
+    }
+
+    pub(super) fn reset_durability_read(&self) {
+        self.min_durability_read.set(Durability::MAX);
+    }
+
+    fn note_durability_read(&self, durability: Durability) {
+        let min = self.min_durability_read.get().min(durability);
+        self.min_durability_read.set(min);
+    }
+
     pub(super) fn report_untracked_read(&self, current_revision: Revision) {
+        self.note_durability_read(Durability::LOW);
         self.with_query_stack(|stack| {
             if let Some(top_query) = stack.last_mut() {
                 top_query.add_untracked_read(current_revision);
@@ -260,6 +344,7 @@ impl LocalState {
     /// Update the top query on the stack to act as though it read a value
     /// of durability `durability` which changed in `revision`.
     pub(super) fn report_synthetic_read(&self, durability: Durability, revision: Revision) {
+        self.note_durability_read(durability);
         self.with_query_stack(|stack| {
             if let Some(top_query) = stack.last_mut() {
                 top_query.add_synthetic_read(durability, revision);
@@ -278,6 +363,15 @@ impl LocalState {
         self.query_stack.take().unwrap()
     }
 
+    /// Runs `op` with an empty query stack in place of the current one (which may
+    /// have been taken), so that anything `op` reads is not recorded.
+    pub(super) fn with_empty_query_stack<R>(&self, op: impl FnOnce() -> R) -> R {
+        let query_stack = self.query_stack.replace(Some(Vec::new()));
+        let result = op();
+        self.query_stack.replace(query_stack);
+        result
+    }
+
     /// Restores a query stack taken with [`Self::take_query_stack`] once
     /// the thread unblocks.
     pub(super) fn restore_query_stack(&self, stack: Vec<ActiveQuery>) {
@@ -310,6 +404,9 @@ pub(crate) struct ActiveQueryGuard<'me> {
 
 impl ActiveQueryGuard<'_> {
     fn pop_helper(&self) -> ActiveQuery {
+        if let Some(published_stack) = self.local_state.published_stack.get() {
+            published_stack.pop();
+        }
         self.local_state.with_query_stack(|stack| {
             // Sanity check: pushes and pops should be balanced.
             assert_eq!(stack.len(), self.push_len);
@@ -342,6 +439,15 @@ impl ActiveQueryGuard<'_> {
         popped_query.revisions(runtime)
     }
 
+    /// The last revision in which one of the inputs checked while verifying this query,
+    /// or one of their own inputs, was executed.
+    pub(crate) fn inputs_executed_at(&self) -> Revision {
+        self.local_state.with_query_stack(|stack| {
+            assert_eq!(stack.len(), self.push_len);
+            stack.last().unwrap().inputs_executed_at
+        })
+    }
+
     /// If the active query is registered as a cycle participant, remove and
     /// return that cycle.
     pub(crate) fn take_cycle(&self) -> Option<Cycle> {
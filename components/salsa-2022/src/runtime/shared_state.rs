@@ -1,11 +1,20 @@
-use std::sync::{atomic::AtomicUsize, Arc};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use crossbeam::atomic::AtomicCell;
 use parking_lot::Mutex;
 
 use crate::{durability::Durability, key::DependencyIndex, revision::AtomicRevision};
 
+use super::change_log::ChangeLog;
 use super::dependency_graph::DependencyGraph;
+use super::verification_pool::VerificationPool;
+use super::wait_graph::PublishedStacks;
 
 /// State that will be common to all threads (when we support multiple threads)
 #[derive(Debug)]
@@ -16,10 +25,12 @@ pub(super) struct SharedState {
     /// Vector we can clone
     pub(super) empty_dependencies: Arc<[DependencyIndex]>,
 
-    /// Set to true when the current revision has been canceled.
-    /// This is done when we an input is being changed. The flag
-    /// is set back to false once the input has been changed.
-    pub(super) revision_canceled: AtomicCell<bool>,
+    /// Set to `Some(d)` when the current revision has been canceled
+    /// because an input of durability `d` (or lower) is about to be changed.
+    /// Runtimes which have only read values of durability greater than `d`
+    /// are permitted to finish their work. The flag is set back to `None`
+    /// once the input has been changed.
+    pub(super) pending_write: AtomicCell<Option<Durability>>,
 
     /// Stores the "last change" revision for values of each duration.
     /// This vector is always of length at least 1 (for Durability 0)
@@ -35,6 +46,32 @@ pub(super) struct SharedState {
     /// The dependency graph tracks which runtimes are blocked on one
     /// another, waiting for queries to terminate.
     pub(super) dependency_graph: Mutex<DependencyGraph>,
+
+    /// The worker threads used for parallel deep verification
+    /// (see [`crate::Database::snapshot_for_verification`]), if enabled.
+    /// Forks share the pool of the database they were forked from.
+    pub(super) verification_pool: Mutex<Option<Arc<VerificationPool>>>,
+
+    /// How long a runtime may be blocked on another before it reports
+    /// [`crate::EventKind::StillBlockedOn`]. `None` disables the warning.
+    pub(super) stall_warning_timeout: AtomicCell<Option<Duration>>,
+
+    /// Set once a [`crate::StallMonitor`] has been created; runtimes then
+    /// publish their query stacks in `published_stacks`.
+    pub(super) publish_stacks: AtomicBool,
+
+    /// The query stacks of the runtimes, for [`crate::StallMonitor::wait_graph`].
+    pub(super) published_stacks: Mutex<PublishedStacks>,
+
+    /// The most recent writes to inputs (see [`crate::Runtime::change_log`]).
+    pub(super) change_log: Mutex<ChangeLog>,
+
+    /// One in this many fetches of memoized values is checked against an execution
+    /// from scratch (see [`crate::Storage::set_differential_verification`]). Zero disables it.
+    pub(super) differential_verification: AtomicUsize,
+
+    /// The number of fetches that could have been checked so far; used for sampling.
+    pub(super) differential_fetches: AtomicUsize,
 }
 
 impl Default for SharedState {
@@ -44,13 +81,46 @@ impl Default for SharedState {
 }
 
 impl SharedState {
-    fn with_durabilities(durabilities: usize) -> Self {
+    pub(super) fn with_durabilities(durabilities: usize) -> Self {
+        assert!(
+            (Durability::LEN..usize::from(u8::MAX)).contains(&durabilities),
+            "the number of durability levels must be at least {} and less than {}, not {}",
+            Durability::LEN,
+            u8::MAX,
+            durabilities
+        );
         SharedState {
             next_id: AtomicUsize::new(1),
             empty_dependencies: None.into_iter().collect(),
-            revision_canceled: Default::default(),
+            pending_write: Default::default(),
             revisions: (0..durabilities).map(|_| AtomicRevision::start()).collect(),
             dependency_graph: Default::default(),
+            verification_pool: Default::default(),
+            stall_warning_timeout: Default::default(),
+            publish_stacks: Default::default(),
+            published_stacks: Default::default(),
+            change_log: Default::default(),
+            differential_verification: Default::default(),
+            differential_fetches: Default::default(),
+        }
+    }
+
+    /// Creates the shared state for a fork: the revisions and the change log are copied, but
+    /// there are no other runtimes yet and so nothing is blocked or cancelled.
+    pub(super) fn fork(&self) -> Self {
+        let state = Self::with_durabilities(self.revisions.len());
+        for (forked, revision) in state.revisions.iter().zip(&self.revisions) {
+            forked.store(revision.load());
         }
+        *state.verification_pool.lock() = self.verification_pool.lock().clone();
+        state
+            .stall_warning_timeout
+            .store(self.stall_warning_timeout.load());
+        *state.change_log.lock() = self.change_log.lock().clone();
+        state.differential_verification.store(
+            self.differential_verification.load(Ordering::SeqCst),
+            Ordering::SeqCst,
+        );
+        state
     }
 }
@@ -0,0 +1,75 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crossbeam::channel::{self, Sender};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads used to verify the dependencies of memoized values in parallel
+/// (see [`crate::Storage::set_verification_threads`]).
+///
+/// The threads are started when the pool is created and then wait for jobs,
+/// so that fanning out a deep verification does not spawn any threads.
+/// They exit once the pool (shared by a database, its snapshots and its forks) is dropped.
+#[derive(Debug)]
+pub(crate) struct VerificationPool {
+    jobs: Sender<Job>,
+
+    /// The number of worker threads.
+    threads: usize,
+
+    /// The number of workers currently reserved; see [`Self::reserve`].
+    in_use: AtomicUsize,
+}
+
+impl VerificationPool {
+    pub(super) fn new(threads: usize) -> Self {
+        let (jobs, receiver) = channel::unbounded::<Job>();
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("salsa-verification-{i}"))
+                .spawn(move || {
+                    for job in receiver {
+                        // Jobs handle their own panics; this only keeps the thread alive.
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                })
+                .expect("failed to spawn a verification thread");
+        }
+        Self {
+            jobs,
+            threads,
+            in_use: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserves up to `wanted` workers, returning how many were reserved.
+    /// They must be returned with [`Self::release`].
+    ///
+    /// Each reserved worker runs at most one job at a time, so a job never waits
+    /// in the queue behind jobs that are waiting for it in turn.
+    pub(crate) fn reserve(&self, wanted: usize) -> usize {
+        let mut reserved = 0;
+        let _ = self
+            .in_use
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_use| {
+                reserved = wanted.min(self.threads.saturating_sub(in_use));
+                Some(in_use + reserved)
+            });
+        reserved
+    }
+
+    pub(crate) fn release(&self, workers: usize) {
+        self.in_use.fetch_sub(workers, Ordering::SeqCst);
+    }
+
+    /// Runs `job` on one of the worker threads, which must have been reserved.
+    pub(crate) fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        self.jobs
+            .send(Box::new(job))
+            .expect("verification threads exit only once the pool is dropped");
+    }
+}
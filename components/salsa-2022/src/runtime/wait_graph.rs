@@ -0,0 +1,197 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use crate::{debug::DebugWithDb, key::DatabaseKeyIndex, Database};
+
+use super::{shared_state::SharedState, RuntimeId};
+
+/// A handle that can be used (from any thread, e.g. a watchdog) to inspect
+/// which runtimes of a database are blocked on one another, and what the
+/// others are executing.
+/// Obtained from [`crate::Runtime::stall_monitor`].
+///
+/// Once a stall monitor has been created, every runtime of the database
+/// publishes its query stack, starting with the next query it executes.
+/// This takes an uncontended lock whenever a query is pushed or popped.
+#[derive(Clone, Debug)]
+pub struct StallMonitor {
+    shared_state: Arc<SharedState>,
+}
+
+impl StallMonitor {
+    pub(super) fn new(shared_state: &Arc<SharedState>) -> Self {
+        shared_state.publish_stacks.store(true, Ordering::Relaxed);
+        Self {
+            shared_state: shared_state.clone(),
+        }
+    }
+
+    /// Returns a snapshot of the runtimes that are currently blocked,
+    /// waiting for a query executing in another runtime,
+    /// and of the runtimes that are executing queries.
+    pub fn wait_graph(&self) -> WaitGraph {
+        let dependency_graph = self.shared_state.dependency_graph.lock();
+        let published_stacks = self.shared_state.published_stacks.lock();
+        dependency_graph.wait_graph(&published_stacks)
+    }
+}
+
+/// The query stacks published by running runtimes; see [`PublishedStack`].
+pub(super) type PublishedStacks = FxHashMap<RuntimeId, Arc<Mutex<Vec<DatabaseKeyIndex>>>>;
+
+/// A copy of the query stack of a runtime, kept in
+/// [`SharedState::published_stacks`] for [`StallMonitor::wait_graph`]
+/// until the runtime is dropped.
+#[derive(Debug)]
+pub(super) struct PublishedStack {
+    runtime_id: RuntimeId,
+    shared_state: Arc<SharedState>,
+    query_stack: Arc<Mutex<Vec<DatabaseKeyIndex>>>,
+}
+
+impl PublishedStack {
+    pub(super) fn new(
+        runtime_id: RuntimeId,
+        shared_state: &Arc<SharedState>,
+        query_stack: Vec<DatabaseKeyIndex>,
+    ) -> Self {
+        let query_stack = Arc::new(Mutex::new(query_stack));
+        shared_state
+            .published_stacks
+            .lock()
+            .insert(runtime_id, query_stack.clone());
+        Self {
+            runtime_id,
+            shared_state: shared_state.clone(),
+            query_stack,
+        }
+    }
+
+    pub(super) fn push(&self, database_key_index: DatabaseKeyIndex) {
+        self.query_stack.lock().push(database_key_index);
+    }
+
+    pub(super) fn pop(&self) {
+        self.query_stack.lock().pop();
+    }
+}
+
+impl Drop for PublishedStack {
+    fn drop(&mut self) {
+        self.shared_state
+            .published_stacks
+            .lock()
+            .remove(&self.runtime_id);
+    }
+}
+
+/// A snapshot of the runtimes that were blocked on one another, and of the
+/// runtimes executing queries, when [`StallMonitor::wait_graph`] was called.
+///
+/// Implements [`DebugWithDb`], which shows the query stacks with the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WaitGraph {
+    /// The blocked runtimes, ordered by id.
+    pub blocked: Vec<BlockedRuntime>,
+
+    /// The runtimes that are executing queries without being blocked, ordered by id.
+    pub running: Vec<RunningRuntime>,
+}
+
+/// A runtime that is blocked, along with the edge to the runtime it waits for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockedRuntime {
+    /// The blocked runtime.
+    pub runtime_id: RuntimeId,
+
+    /// The active queries of the blocked runtime, outermost first.
+    pub query_stack: Vec<DatabaseKeyIndex>,
+
+    /// The query the runtime is waiting for.
+    pub blocked_on_key: DatabaseKeyIndex,
+
+    /// The runtime executing `blocked_on_key`.
+    pub blocked_on_runtime: RuntimeId,
+}
+
+/// A runtime that is executing queries and is not blocked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunningRuntime {
+    /// The running runtime.
+    pub runtime_id: RuntimeId,
+
+    /// The active queries of the runtime, outermost first.
+    pub query_stack: Vec<DatabaseKeyIndex>,
+}
+
+impl WaitGraph {
+    /// True if no runtime is blocked or executing a query.
+    pub fn is_empty(&self) -> bool {
+        self.blocked.is_empty() && self.running.is_empty()
+    }
+
+    /// The runtime `runtime_id` waits for, if it is blocked.
+    pub fn blocked_on(&self, runtime_id: RuntimeId) -> Option<&BlockedRuntime> {
+        self.blocked.iter().find(|b| b.runtime_id == runtime_id)
+    }
+}
+
+impl<Db> DebugWithDb<Db> for WaitGraph
+where
+    Db: ?Sized + Database,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>, db: &Db) -> std::fmt::Result {
+        f.debug_struct("WaitGraph")
+            .field(
+                "blocked",
+                &self.blocked.iter().map(|b| b.debug(db)).collect::<Vec<_>>(),
+            )
+            .field(
+                "running",
+                &self.running.iter().map(|r| r.debug(db)).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<Db> DebugWithDb<Db> for BlockedRuntime
+where
+    Db: ?Sized + Database,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>, db: &Db) -> std::fmt::Result {
+        f.debug_struct("BlockedRuntime")
+            .field("runtime_id", &self.runtime_id)
+            .field(
+                "query_stack",
+                &self
+                    .query_stack
+                    .iter()
+                    .map(|k| k.debug(db))
+                    .collect::<Vec<_>>(),
+            )
+            .field("blocked_on_key", &self.blocked_on_key.debug(db))
+            .field("blocked_on_runtime", &self.blocked_on_runtime)
+            .finish()
+    }
+}
+
+impl<Db> DebugWithDb<Db> for RunningRuntime
+where
+    Db: ?Sized + Database,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>, db: &Db) -> std::fmt::Result {
+        f.debug_struct("RunningRuntime")
+            .field("runtime_id", &self.runtime_id)
+            .field(
+                "query_stack",
+                &self
+                    .query_stack
+                    .iter()
+                    .map(|k| k.debug(db))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
@@ -3,3 +3,11 @@ use crate::{Database, IngredientIndex};
 pub trait SalsaStructInDb<DB: ?Sized + Database> {
     fn register_dependent_fn(db: &DB, index: IngredientIndex);
 }
+
+/// Salsa structs that can be the key of a tracked function whose value is set with `specify`.
+pub trait SpecifiableInDb<DB: ?Sized + Database>: SalsaStructInDb<DB> {
+    /// True if the active query may specify values for this struct:
+    /// tracked structs must have been created by the active query
+    /// and interned structs must be owned by it (see `InternedIngredient::intern_owned`).
+    fn owned_by_active_query(self, db: &DB) -> bool;
+}
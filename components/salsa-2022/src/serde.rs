@@ -0,0 +1,238 @@
+//! Serialization of input and interned structs (with the `serde` feature).
+//!
+//! Salsa structs declared with the `serde` option (e.g., `#[salsa::input(serde)]`)
+//! implement [`Serialize`] and [`Deserialize`], but only within a call to
+//! [`serialize`] or [`deserialize`], which provide the database. The first
+//! time a struct is serialized, the values of its fields are written; later
+//! occurrences of the same struct only refer back to it. Deserializing then
+//! creates a new input (or re-interns the value) for each struct, and maps
+//! the references to the new ids, so references between structs are preserved
+//! even though the ids themselves differ.
+//!
+//! The database given to [`serialize`] and [`deserialize`] must be of the type
+//! that the structs' jar uses (e.g., `&dyn crate::Db`).
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+};
+
+use ::serde::{de::Error as _, ser::Error as _};
+pub use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use rustc_hash::FxHashMap;
+
+use crate::{AsId, Id};
+
+/// Serializes `value`, writing the field values of the salsa structs it contains.
+pub fn serialize<Db, T, S>(db: &Db, value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    Db: ?Sized + 'static,
+    T: ?Sized + Serialize,
+    S: Serializer,
+{
+    let db: *const Db = db;
+    let _scope = Scope::enter(Context::new(db as *mut Db, false));
+    value.serialize(serializer)
+}
+
+/// Deserializes a value written by [`serialize`], creating the salsa structs
+/// it contains in `db`.
+pub fn deserialize<'de, Db, T, D>(db: &mut Db, deserializer: D) -> Result<T, D::Error>
+where
+    Db: ?Sized + 'static,
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let _scope = Scope::enter(Context::new(db as *mut Db, true));
+    T::deserialize(deserializer)
+}
+
+/// How a salsa struct is written: `Def(id, fields)` for its first occurrence
+/// and `Ref(id)` afterwards. The ids are only used to match the two up.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SalsaStruct")]
+enum Repr<F> {
+    Def(u32, F),
+    Ref(u32),
+}
+
+/// Serializes the salsa struct `id` within [`serialize`]; `fields` is given
+/// the database and serializes the field values with [`FieldsSerializer::fields`].
+///
+/// Used by the code generated for the `serde` option.
+#[doc(hidden)]
+pub fn serialize_struct<Db, K, S>(
+    id: K,
+    serializer: S,
+    fields: impl FnOnce(&Db, FieldsSerializer<S>) -> Result<S::Ok, S::Error>,
+) -> Result<S::Ok, S::Error>
+where
+    Db: ?Sized + 'static,
+    K: AsId + 'static,
+    S: Serializer,
+{
+    let id = id.as_id();
+    let db = with_context::<Db, _>(|context| {
+        let first = context.ids.insert((TypeId::of::<K>(), id), id).is_none();
+        first.then_some(context.db)
+    })
+    .map_err(S::Error::custom)?;
+
+    let id = id.as_u32();
+    match db {
+        Some(db) => {
+            // SAFETY: `db` was created from a reference in `serialize` and
+            // stays valid until it returns.
+            let db = unsafe { &*(db as *const Db) };
+            fields(db, FieldsSerializer { id, serializer })
+        }
+        None => Repr::<()>::Ref(id).serialize(serializer),
+    }
+}
+
+/// See [`serialize_struct`].
+#[doc(hidden)]
+pub struct FieldsSerializer<S> {
+    id: u32,
+    serializer: S,
+}
+
+impl<S: Serializer> FieldsSerializer<S> {
+    pub fn fields<F: Serialize>(self, fields: F) -> Result<S::Ok, S::Error> {
+        Repr::Def(self.id, fields).serialize(self.serializer)
+    }
+}
+
+/// Deserializes a salsa struct of type `K` within [`deserialize`]. The first time,
+/// the struct is created from its field values with `create`; afterwards, the
+/// same struct is returned.
+///
+/// Used by the code generated for the `serde` option.
+#[doc(hidden)]
+pub fn deserialize_struct<'de, Db, K, F, D>(
+    deserializer: D,
+    create: impl FnOnce(&mut Db, F) -> K,
+) -> Result<K, D::Error>
+where
+    Db: ?Sized + 'static,
+    K: AsId + 'static,
+    F: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    // Deserializing the fields may create other structs, so do that first.
+    let repr = Repr::<F>::deserialize(deserializer)?;
+    let db = with_context::<Db, _>(|context| {
+        if !context.mutable {
+            return Err(
+                "salsa structs can only be deserialized within `salsa::serde::deserialize`"
+                    .to_string(),
+            );
+        }
+        Ok(context.db)
+    })
+    .and_then(|db| db)
+    .map_err(D::Error::custom)?;
+
+    let key = |id: u32| (TypeId::of::<K>(), Id::from_u32(id));
+    match repr {
+        Repr::Def(id, fields) => {
+            // SAFETY: `db` was created from a mutable reference in `deserialize`
+            // and stays valid until it returns; no other reference to it is live.
+            let db = unsafe { &mut *db };
+            let new_id = create(db, fields);
+            with_context::<Db, _>(|context| context.ids.insert(key(id), new_id.as_id()))
+                .map_err(D::Error::custom)?;
+            Ok(new_id)
+        }
+        Repr::Ref(id) => {
+            let new_id = with_context::<Db, _>(|context| context.ids.get(&key(id)).copied())
+                .map_err(D::Error::custom)?;
+            match new_id {
+                Some(new_id) => Ok(K::from_id(new_id)),
+                None => Err(D::Error::custom(format!(
+                    "reference to a `{}` that was not written before",
+                    std::any::type_name::<K>()
+                ))),
+            }
+        }
+    }
+}
+
+struct Context {
+    /// The `*mut Db` given to `serialize` (then `mutable` is false)
+    /// or to `deserialize` (then `mutable` is true).
+    db: Box<dyn Any>,
+    db_type_name: &'static str,
+    mutable: bool,
+
+    /// Maps the struct types and ids read so far to their new ids
+    /// (when serializing, to themselves).
+    ids: FxHashMap<(TypeId, Id), Id>,
+}
+
+impl Context {
+    fn new<Db: ?Sized + 'static>(db: *mut Db, mutable: bool) -> Self {
+        Context {
+            db: Box::new(db),
+            db_type_name: std::any::type_name::<Db>(),
+            mutable,
+            ids: Default::default(),
+        }
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<Context>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Invokes `op` with the innermost context, which must be for a database of type `Db`.
+fn with_context<Db, R>(op: impl FnOnce(&mut ContextFor<'_, Db>) -> R) -> Result<R, String>
+where
+    Db: ?Sized + 'static,
+{
+    CONTEXT.with(|contexts| {
+        let mut contexts = contexts.borrow_mut();
+        let Some(context) = contexts.last_mut() else {
+            return Err(
+                "salsa structs can only be serialized within `salsa::serde::serialize` \
+                 or `salsa::serde::deserialize`"
+                    .to_string(),
+            );
+        };
+        let Some(&db) = context.db.downcast_ref::<*mut Db>() else {
+            return Err(format!(
+                "salsa structs of this type require a database of type `{}`, not `{}`",
+                std::any::type_name::<Db>(),
+                context.db_type_name,
+            ));
+        };
+        Ok(op(&mut ContextFor {
+            db,
+            mutable: context.mutable,
+            ids: &mut context.ids,
+        }))
+    })
+}
+
+/// A [`Context`] whose database has been checked to be of type `Db`.
+struct ContextFor<'c, Db: ?Sized> {
+    db: *mut Db,
+    mutable: bool,
+    ids: &'c mut FxHashMap<(TypeId, Id), Id>,
+}
+
+/// Pushes a context on entry and pops it on exit (even when unwinding).
+struct Scope;
+
+impl Scope {
+    fn enter(context: Context) -> Self {
+        CONTEXT.with(|contexts| contexts.borrow_mut().push(context));
+        Scope
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        CONTEXT.with(|contexts| contexts.borrow_mut().pop());
+    }
+}
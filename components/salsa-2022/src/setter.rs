@@ -27,7 +27,15 @@ where
         }
     }
 
+    /// Overrides the durability of the new value. By default, this is the durability
+    /// given for the field (or the input struct), and `Durability::LOW` if there is none.
+    ///
+    /// # Panics
+    ///
+    /// If `durability` exceeds the number of durability levels of the database.
+    #[track_caller]
     pub fn with_durability(self, durability: Durability) -> Self {
+        self.runtime.assert_valid_durability(durability);
         Setter { durability, ..self }
     }
 
@@ -1,14 +1,17 @@
-use std::{fmt, sync::Arc};
+use std::{any::Any, fmt, io, sync::Arc};
 
-use parking_lot::Condvar;
+use parking_lot::{Condvar, Mutex};
 
 use crate::cycle::CycleRecoveryStrategy;
+use crate::debug::Dump;
+use crate::hash::FxHashSet;
 use crate::ingredient::Ingredient;
+use crate::interned::SharedInterners;
 use crate::jar::Jar;
 use crate::key::DependencyIndex;
 use crate::runtime::local_state::QueryOrigin;
 use crate::runtime::Runtime;
-use crate::{Database, DatabaseKeyIndex, Id, IngredientIndex};
+use crate::{Database, DatabaseKeyIndex, Durability, EventKind, EventKinds, Id, IngredientIndex};
 
 use super::routes::Routes;
 use super::{ParallelDatabase, Revision};
@@ -24,6 +27,11 @@ pub struct Storage<DB: HasJars> {
     /// by using `Arc::get_mut`. This is only possible when all parallel snapshots have been dropped.
     shared: Arc<Shared<DB>>,
 
+    /// Used to wake up a writer blocked in [`Storage::cancel_other_workers`].
+    /// This must be declared after `shared`: it notifies the writer when dropped,
+    /// and the writer must by then be able to observe that our reference to `shared` is gone.
+    coordinate: CoordinateHandle,
+
     /// The "ingredients" structure stores the information about how to find each ingredient in the database.
     /// It allows us to take the [`IngredientIndex`] assigned to a particular ingredient
     /// and get back a [`dyn Ingredient`][`Ingredient`] for the struct that stores its data.
@@ -44,47 +52,168 @@ struct Shared<DB: HasJars> {
     /// Each jar stores its own structs in there that ultimately contain ingredients
     /// (types that implement the [`Ingredient`] trait, like [`crate::function::FunctionIngredient`]).
     jars: DB::Jars,
+}
+
+/// Coordination variables between the handles of a database.
+#[derive(Default)]
+struct Coordinate {
+    /// Held by the writer while it checks whether it has unique access to the jars,
+    /// so that a snapshot being dropped cannot notify it before it starts waiting.
+    mutex: Mutex<()>,
 
     /// Conditional variable that is used to coordinate cancellation.
     /// When the main thread writes to the database, it blocks until each of the snapshots can be cancelled.
     cvar: Condvar,
 }
 
+/// Reference to the [`Coordinate`] owned by each handle; notifies the writer when dropped.
+struct CoordinateHandle(Arc<Coordinate>);
+
+impl Drop for CoordinateHandle {
+    fn drop(&mut self) {
+        let _guard = self.0.mutex.lock();
+        self.0.cvar.notify_all();
+    }
+}
+
 // ANCHOR: default
 impl<DB> Default for Storage<DB>
 where
-    DB: HasJars,
+    DB: HasJars + Database,
 {
     fn default() -> Self {
-        let mut routes = Routes::new();
+        Self::with_durabilities(DB::DURABILITIES)
+    }
+}
+// ANCHOR_END: default
+
+impl<DB> Storage<DB>
+where
+    DB: HasJars + Database,
+{
+    /// Creates the storage for a database with `durabilities` levels of durability,
+    /// rather than the number given by `#[salsa::db(durabilities = N)]` (3 by default).
+    ///
+    /// # Panics
+    ///
+    /// If `durabilities` is less than 3 or not less than 255.
+    pub fn with_durabilities(durabilities: usize) -> Self {
+        Self::new(durabilities, SharedInterners::global().clone())
+    }
+
+    /// Creates the storage for a database whose `shared` interned structs
+    /// use the tables in `interners` rather than the global ones.
+    /// Use [`Self::new`] to also choose the number of durability levels.
+    ///
+    /// Interned structs are shared by value, so a shared interned struct must not
+    /// contain the ids of inputs, tracked structs, or interned structs that are not shared:
+    /// those ids are only meaningful in the database that created them,
+    /// and other databases use the same ids for different values.
+    pub fn with_shared_interners(interners: SharedInterners) -> Self {
+        Self::new(DB::DURABILITIES, interners)
+    }
+
+    /// Creates the storage for a database with `durabilities` levels of durability
+    /// (see [`Self::with_durabilities`]) whose `shared` interned structs use the tables
+    /// in `shared_interners` (see [`Self::with_shared_interners`]).
+    ///
+    /// # Panics
+    ///
+    /// If `durabilities` is less than 3 or not less than 255.
+    pub fn new(durabilities: usize, shared_interners: SharedInterners) -> Self {
+        let mut routes = Routes::new(shared_interners);
         let jars = DB::create_jars(&mut routes);
         Self {
-            shared: Arc::new(Shared {
-                jars,
-                cvar: Default::default(),
-            }),
+            shared: Arc::new(Shared { jars }),
+            coordinate: CoordinateHandle(Default::default()),
             routes: Arc::new(routes),
-            runtime: Runtime::default(),
+            runtime: Runtime::new(durabilities, DB::salsa_event_kinds(), DB::NO_SNAPSHOTS),
         }
     }
 }
-// ANCHOR_END: default
 
 impl<DB> Storage<DB>
 where
     DB: HasJars,
 {
+    /// Creates a snapshot of this database for use on another thread.
+    ///
+    /// Not available for databases declared with `#[salsa::db(no_snapshots)]`,
+    /// which do not implement [`AllowsSnapshots`].
     pub fn snapshot(&self) -> Storage<DB>
     where
-        DB: ParallelDatabase,
+        DB: ParallelDatabase + AllowsSnapshots,
     {
         Self {
             shared: self.shared.clone(),
+            coordinate: CoordinateHandle(self.coordinate.0.clone()),
             routes: self.routes.clone(),
             runtime: self.runtime.snapshot(),
         }
     }
 
+    /// Creates a snapshot for verifying memoized values on another thread;
+    /// see [`crate::Database::snapshot_for_verification`].
+    pub fn verification_snapshot(&self) -> Storage<DB>
+    where
+        DB: ParallelDatabase + AllowsSnapshots,
+    {
+        Self {
+            shared: self.shared.clone(),
+            coordinate: CoordinateHandle(self.coordinate.0.clone()),
+            routes: self.routes.clone(),
+            runtime: self.runtime.verification_snapshot(),
+        }
+    }
+
+    /// Creates a fork of this database, e.g. to try out hypothetical edits.
+    ///
+    /// The fork starts out with the same inputs, interned values, and memoized results,
+    /// sharing the memoized values themselves rather than copying them.
+    /// From then on it is independent: writes to the fork start new revisions of its own,
+    /// they neither affect this database nor cancel its snapshots, and the fork can
+    /// simply be dropped when it is no longer needed. Conversely, writes to this
+    /// database are not visible in the fork.
+    ///
+    /// Forking does not copy the interned values, tracked structs, or input values:
+    /// the fork shares them with this database, and the values of an input field are only
+    /// copied when either database first writes to that field. The memoized values are shared
+    /// too, but each database keeps its own table of them, so forking takes time proportional
+    /// to the number of memoized values (rather than to their size).
+    ///
+    /// Forking requires all ingredients to be clonable, which means that
+    /// the fields of all input structs must implement `Clone`.
+    pub fn fork(&self) -> Storage<DB>
+    where
+        DB::Jars: Clone,
+    {
+        self.fork_with_runtime(self.runtime.fork())
+    }
+
+    /// Like [`Self::fork`], but also legal while a query is executing: used to implement
+    /// [`Database::fork_for_differential_verification`](`crate::Database::fork_for_differential_verification`),
+    /// which forks the database in the middle of the fetch being checked.
+    pub fn differential_fork(&self) -> Storage<DB>
+    where
+        DB::Jars: Clone,
+    {
+        self.fork_with_runtime(self.runtime.differential_fork())
+    }
+
+    fn fork_with_runtime(&self, runtime: Runtime) -> Storage<DB>
+    where
+        DB::Jars: Clone,
+    {
+        Self {
+            shared: Arc::new(Shared {
+                jars: self.shared.jars.clone(),
+            }),
+            coordinate: CoordinateHandle(Default::default()),
+            routes: self.routes.clone(),
+            runtime,
+        }
+    }
+
     pub fn jars(&self) -> (&DB::Jars, &Runtime) {
         (&self.shared.jars, &self.runtime)
     }
@@ -93,17 +222,92 @@ where
         &self.runtime
     }
 
+    /// Sets the number of worker threads used to verify the dependencies of
+    /// memoized values in parallel. Zero (the default) verifies them sequentially.
+    ///
+    /// The threads are started here and reused by every verification; they are shared
+    /// with the snapshots and forks of this database, and exit once all of them are dropped.
+    /// Only memos with enough dependencies to keep a worker busy are verified in parallel.
+    ///
+    /// Parallel verification also requires the database to implement
+    /// [`crate::Database::snapshot_for_verification`].
+    pub fn set_verification_threads(&mut self, threads: usize) {
+        self.runtime.set_verification_threads(threads);
+    }
+
+    /// Sets how long a runtime may be blocked on a query executing in another
+    /// runtime before it reports [`crate::EventKind::StillBlockedOn`]
+    /// (once per wait). `None`, the default, disables the warning.
+    ///
+    /// The setting is shared with all snapshots of this database.
+    pub fn set_stall_warning_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.runtime.set_stall_warning_timeout(timeout);
+    }
+
+    /// Sets the number of writes kept in the [change log](`Runtime::change_log`)
+    /// (1024 by default). Zero disables the change log.
+    ///
+    /// The change log is shared with all snapshots of this database.
+    pub fn set_change_log_capacity(&mut self, capacity: usize) {
+        self.runtime.set_change_log_capacity(capacity);
+    }
+
+    /// Starts a new revision in which some input of durability `durability` is considered changed,
+    /// so that memoized values that read an input of that durability (or lower) are re-validated.
+    /// The write is recorded in the [change log](`Runtime::change_log`) without a key.
+    pub fn synthetic_write(&mut self, durability: Durability) {
+        self.runtime.declare_write_durability(durability);
+        self.new_revision();
+        self.runtime.synthetic_write(durability);
+    }
+
+    /// Enables differential verification, a debugging aid for finding bugs in incremental
+    /// computation such as missed dependencies or impure tracked functions.
+    ///
+    /// One in `one_in` fetches that return a memoized or backdated value is checked:
+    /// the tracked function is executed again from scratch, without using any memoized values
+    /// of tracked functions, and the result is compared with the returned value using
+    /// the function's `Eq` impl (or custom `eq` function). Zero (the default) disables it;
+    /// one checks every such fetch.
+    ///
+    /// If the values differ, the fetch panics, reporting the key and both values.
+    /// Values that cannot be compared (those of `no_eq` functions) and executions
+    /// that run into a cycle are not checked. While executing from scratch, values
+    /// given with `specify` (including the fields of tracked structs) are kept aside
+    /// with the values computed from scratch, accumulated values are discarded,
+    /// and no events are reported.
+    ///
+    /// Each check executes from scratch in a fork of the database, created with
+    /// [`Database::fork_for_differential_verification`](`crate::Database::fork_for_differential_verification`)
+    /// and dropped afterwards: the values it interns, the tracked structs it creates, and
+    /// the values it computes do not outlive the check, so checking leaves this database unchanged
+    /// (except for interned structs declared `shared`, whose tables the fork shares).
+    /// As each check forks the database, it takes time proportional to the number of memoized values.
+    ///
+    /// The setting is shared with all snapshots of this database.
+    pub fn set_differential_verification(&mut self, one_in: usize) {
+        self.runtime.set_differential_verification(one_in);
+    }
+
     // ANCHOR: jars_mut
     /// Gets mutable access to the jars. This will trigger a new revision
     /// and it will also cancel any ongoing work in the current revision.
     /// Any actual writes that occur to data in a jar should use
     /// [`Runtime::report_tracked_write`].
     pub fn jars_mut(&mut self) -> (&mut DB::Jars, &mut Runtime) {
+        self.new_revision();
+        self.jars_mut_in_current_revision()
+    }
+
+    /// Starts a new revision, cancelling any ongoing work in the current revision,
+    /// and returns it. Databases report [`EventKind::DidStartRevision`](`crate::EventKind::DidStartRevision`)
+    /// once this returns.
+    pub fn new_revision(&mut self) -> Revision {
         // Wait for all snapshots to be dropped.
         self.cancel_other_workers();
 
         // Increment revision counter.
-        self.runtime.new_revision();
+        let revision = self.runtime.new_revision();
 
         // Acquire `&mut` access to `self.shared` -- this is only possible because
         // the snapshots have all been dropped, so we hold the only handle to the `Arc`.
@@ -116,11 +320,102 @@ where
             route(&mut shared.jars).reset_for_new_revision();
         }
 
-        // Return mut ref to jars + runtime.
-        (&mut shared.jars, &mut self.runtime)
+        revision
     }
     // ANCHOR_END: jars_mut
 
+    /// Gets mutable access to the jars in the revision started by [`Self::new_revision`].
+    /// Used by the code generated for `#[salsa::db]`, which reports the new revision in between.
+    #[doc(hidden)]
+    pub fn jars_mut_in_current_revision(&mut self) -> (&mut DB::Jars, &mut Runtime) {
+        // Snapshots created since the revision started would still see the old values.
+        let shared = Arc::get_mut(&mut self.shared)
+            .expect("cannot modify the database while a snapshot of it exists");
+        (&mut shared.jars, &mut self.runtime)
+    }
+
+    /// Reports [`EventKind::DidStartRevision`](`crate::EventKind::DidStartRevision`) to `db`,
+    /// the database that owns this storage.
+    #[doc(hidden)]
+    pub fn report_new_revision(&self, db: &DB, revision: Revision)
+    where
+        DB: Database,
+    {
+        self.runtime
+            .report_event(db, EventKinds::DID_START_REVISION, || {
+                EventKind::DidStartRevision { revision }
+            });
+    }
+
+    /// Registers the jar `J`, one of the jars listed as `registered(...)` in `#[salsa::db]`:
+    /// creates its ingredients, which are given the next free ingredient indices.
+    /// Until then, using the jar (e.g., calling one of its tracked functions) panics.
+    ///
+    /// Like any write, this starts a new revision (cancelling and waiting for snapshots);
+    /// memoized values of the other jars remain valid. Registering a jar twice does nothing.
+    /// Forks created before the jar was registered do not have it.
+    pub fn register_jar<J>(&mut self)
+    where
+        DB: HasRegisteredJar<J> + DbWithJar<J>,
+        J: for<'db> Jar<'db>,
+    {
+        let (jars, _) = self.jars_mut();
+        if DB::registered_jar(jars).is_some() {
+            return;
+        }
+
+        let routes = Arc::make_mut(&mut self.routes);
+        let jar = J::create_jar(routes);
+        let jars = &mut Arc::get_mut(&mut self.shared).unwrap().jars;
+        *DB::registered_jar(jars) = Some(jar);
+    }
+
+    /// True if the inputs can be [rolled back](`Self::rollback_to`) to the values they had in `revision`.
+    pub fn can_rollback_to(&self, revision: Revision) -> bool {
+        revision <= self.runtime.current_revision()
+            && self
+                .routes
+                .all_routes()
+                .all(|route| route(&self.shared.jars).can_rollback_to(revision))
+    }
+
+    /// Restores every input to the value it had in `revision`, an earlier revision of this database.
+    ///
+    /// This starts a new revision, in which the restored inputs carry the revision stamps they had in `revision`:
+    /// memoized values that were verified in `revision` (or before) are therefore still valid
+    /// and will not be re-executed. Memoized values that read one of the newer input values are discarded.
+    ///
+    /// Inputs that were changed after `revision` can only be restored if their input struct keeps
+    /// a history of prior values (`#[salsa::input(history = N)]`) that reaches back far enough.
+    ///
+    /// # Panics
+    ///
+    /// If [`Self::can_rollback_to`] returns false.
+    pub fn rollback_to(&mut self, revision: Revision) {
+        assert!(
+            self.can_rollback_to(revision),
+            "cannot roll back to {:?}: the history of some inputs does not go back that far",
+            revision
+        );
+
+        let routes = self.routes.clone();
+        let (jars, runtime) = self.jars_mut();
+
+        let mut restored = vec![];
+        for route in routes.all_mut_routes() {
+            route(jars).rollback_inputs(revision, &mut restored);
+        }
+
+        let restored_keys: FxHashSet<_> = restored.iter().map(|&(key, _)| key).collect();
+        for route in routes.all_mut_routes() {
+            route(jars).rollback_memos(revision, &restored_keys);
+        }
+
+        for (key, durability) in restored {
+            runtime.report_tracked_write(key.try_into().unwrap(), durability);
+        }
+    }
+
     // ANCHOR: cancel_other_workers
     /// Sets cancellation flag and blocks until all other workers with access
     /// to this storage have completed.
@@ -128,8 +423,13 @@ where
     /// This could deadlock if there is a single worker with two handles to the
     /// same database!
     fn cancel_other_workers(&mut self) {
+        // Only snapshots that may have read the overwritten values are cancelled;
+        // other snapshots run to completion.
+        let write_durability = self.runtime.take_write_durability();
+        let coordinate = self.coordinate.0.clone();
+        let mut guard = coordinate.mutex.lock();
         loop {
-            self.runtime.set_cancellation_flag();
+            self.runtime.set_cancellation_flag(write_durability);
 
             // If we have unique access to the jars, we are done.
             if Arc::get_mut(&mut self.shared).is_some() {
@@ -137,14 +437,9 @@ where
             }
 
             // Otherwise, wait until some other storage entites have dropped.
-            // We create a mutex here because the cvar api requires it, but we
-            // don't really need one as the data being protected is actually
-            // the jars above.
-            //
-            // The cvar `self.shared.cvar` is notified by the `Drop` impl.
-            let mutex = parking_lot::Mutex::new(());
-            let mut guard = mutex.lock();
-            self.shared.cvar.wait(&mut guard);
+            // The cvar is notified by the `Drop` impl of `CoordinateHandle`,
+            // which runs after the other handle has released `shared`.
+            coordinate.cvar.wait(&mut guard);
         }
     }
     // ANCHOR_END: cancel_other_workers
@@ -153,20 +448,32 @@ where
         let route = self.routes.route(ingredient_index);
         route(&self.shared.jars)
     }
-}
 
-impl<DB> Drop for Shared<DB>
-where
-    DB: HasJars,
-{
-    fn drop(&mut self) {
-        self.cvar.notify_all();
+    /// Dumps every ingredient to `out`; see [`crate::debug::dump`].
+    pub fn dump(&self, db: &DB, out: &mut dyn io::Write) -> io::Result<()>
+    where
+        DB: Database,
+    {
+        let mut dump = Dump::new(db, out);
+        for (index, route) in self.routes.all_routes().enumerate() {
+            dump.ingredient_index = index;
+            route(&self.shared.jars).dump(&mut dump)?;
+        }
+        Ok(())
     }
 }
 
 pub trait HasJars: HasJarsDyn + Sized {
     type Jars;
 
+    /// The number of durability levels the database has by default.
+    const DURABILITIES: usize = Durability::LEN;
+
+    /// True if the database was declared with `#[salsa::db(no_snapshots)]`, in which case
+    /// it cannot be snapshotted, its values need not be `Send + Sync`, and claiming a query
+    /// for execution takes a fast path (see [`Runtime::snapshots_disabled`]).
+    const NO_SNAPSHOTS: bool = false;
+
     fn jars(&self) -> (&Self::Jars, &Runtime);
 
     /// Gets mutable access to the jars. This will trigger a new revision
@@ -176,6 +483,32 @@ pub trait HasJars: HasJarsDyn + Sized {
     fn create_jars(routes: &mut Routes<Self>) -> Self::Jars;
 }
 
+/// Implemented by `#[salsa::db]` for every database not declared with `no_snapshots`;
+/// required to [snapshot](`Storage::snapshot`) the database's storage.
+///
+/// Snapshots are meant to be used on other threads, so the jars of such a database must be
+/// `Send + Sync`: the values of its inputs, tracked structs, interned structs and tracked functions
+/// must be `Send + Sync` too. Databases declared `no_snapshots` may use other values, such as `Rc<str>`.
+pub trait AllowsSnapshots: HasJars<Jars: Send + Sync> {}
+
+/// Implemented for each jar listed as `registered(...)` in `#[salsa::db]`,
+/// which is only created when it is [registered](`Storage::register_jar`).
+pub trait HasRegisteredJar<J>: JarFromJars<J> {
+    /// The place for the jar in `jars`, `None` until the jar is registered.
+    fn registered_jar(jars: &mut Self::Jars) -> &mut Option<J>;
+}
+
+/// Reports the use of a jar before it was [registered](`Storage::register_jar`).
+/// Used by the code generated for `registered(...)` jars.
+#[doc(hidden)]
+#[track_caller]
+pub fn unregistered_jar<J>() -> ! {
+    panic!(
+        "jar `{}` has not been registered: call `Storage::register_jar` first",
+        std::any::type_name::<J>()
+    )
+}
+
 pub trait DbWithJar<J>: HasJar<J> + Database {
     fn as_jar_db<'db>(&'db self) -> &<J as Jar<'db>>::DynDb
     where
@@ -205,6 +538,11 @@ pub trait HasJarsDyn {
 
     fn origin(&self, input: DatabaseKeyIndex) -> Option<QueryOrigin>;
 
+    fn subtree_executed_at(&self, input: DatabaseKeyIndex) -> Option<Revision>;
+
+    /// Routes to [`Ingredient::verify_from_scratch`] on the ingredient for `input`.
+    fn verify_from_scratch(&self, input: DatabaseKeyIndex, value: &dyn Any);
+
     fn mark_validated_output(&self, executor: DatabaseKeyIndex, output: DependencyIndex);
 
     /// Invoked when `executor` used to output `stale_output` but no longer does.
@@ -222,6 +560,15 @@ pub trait HasJarsDyn {
     fn salsa_struct_deleted(&self, ingredient: IngredientIndex, id: Id);
 
     fn fmt_index(&self, index: DependencyIndex, fmt: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Formats `index` including the data its key refers to; see [`Ingredient::fmt_key`].
+    fn fmt_key(&self, index: DependencyIndex, fmt: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// The debug name of the ingredient with the given index.
+    fn debug_name(&self, ingredient: IngredientIndex) -> &'static str;
+
+    /// Writes the contents of every ingredient to `out`; see [`crate::debug::dump`].
+    fn dump(&self, out: &mut dyn io::Write) -> io::Result<()>;
 }
 // ANCHOR_END: HasJarsDyn
 
@@ -0,0 +1,52 @@
+use crate::{hash::FxDashMap, id::AsId, Revision};
+
+/// Records the revision in which tracked struct ids were deleted, so that
+/// using a stale id panics with a message naming the struct and that revision,
+/// rather than failing in some confusing way.
+///
+/// Ids are never reused (they are allocated by a counter that only grows, in
+/// `InternedIngredient::intern_with`), so the id itself identifies the "generation" of an entry.
+/// The records are only kept when the `stale-id-checks` feature is enabled;
+/// otherwise this type is empty and its methods do nothing.
+#[derive(Clone)]
+pub(crate) struct Tombstones<K: AsId> {
+    #[cfg(feature = "stale-id-checks")]
+    deleted_at: FxDashMap<K, Revision>,
+
+    #[cfg(not(feature = "stale-id-checks"))]
+    phantom: std::marker::PhantomData<FxDashMap<K, Revision>>,
+}
+
+impl<K: AsId> Default for Tombstones<K> {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "stale-id-checks")]
+            deleted_at: Default::default(),
+
+            #[cfg(not(feature = "stale-id-checks"))]
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<K: AsId> Tombstones<K> {
+    /// Records that `key` was deleted in `revision`.
+    #[allow(unused_variables)]
+    pub(crate) fn record(&self, key: K, revision: Revision) {
+        #[cfg(feature = "stale-id-checks")]
+        self.deleted_at.insert(key, revision);
+    }
+
+    /// Panics if `key` was deleted.
+    #[track_caller]
+    #[allow(unused_variables)]
+    pub(crate) fn check(&self, key: K) {
+        #[cfg(feature = "stale-id-checks")]
+        if let Some(revision) = self.deleted_at.get(&key) {
+            panic!(
+                "stale id: `{:?}` was deleted in revision {:?}",
+                key, *revision
+            );
+        }
+    }
+}
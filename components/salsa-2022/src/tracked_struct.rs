@@ -1,14 +1,16 @@
-use std::fmt;
+use std::{fmt, io};
 
 use crate::{
     cycle::CycleRecoveryStrategy,
+    debug::{dump_opaque, Dump, DumpFmt, DumpValue},
     ingredient::{fmt_index, Ingredient, IngredientRequiresReset},
     ingredient_list::IngredientList,
     interned::{InternedData, InternedId, InternedIngredient},
     key::{DatabaseKeyIndex, DependencyIndex},
     runtime::{local_state::QueryOrigin, Runtime},
     salsa_struct::SalsaStructInDb,
-    Database, Event, IngredientIndex, Revision,
+    tombstones::Tombstones,
+    Database, DebugWithDb, EventKind, EventKinds, IngredientIndex, Revision,
 };
 
 pub trait TrackedStructId: InternedId {}
@@ -30,6 +32,7 @@ pub trait TrackedStructInDb<DB: ?Sized + Database>: SalsaStructInDb<DB> {
 /// Unlike normal interners, tracked struct indices can be deleted and reused aggressively:
 /// when a tracked function re-executes,
 /// any tracked structs that it created before but did not create this time can be deleted.
+#[derive(Clone)]
 pub struct TrackedStructIngredient<Id, Data>
 where
     Id: TrackedStructId,
@@ -45,6 +48,12 @@ where
     /// so they can remove any data tied to that instance.
     dependent_fns: IngredientList,
 
+    /// Records deleted ids (with the `stale-id-checks` feature).
+    tombstones: Tombstones<Id>,
+
+    /// Formats the id fields for [`crate::debug::dump`].
+    fmt_data: DumpFmt<Data>,
+
     debug_name: &'static str,
 }
 
@@ -67,10 +76,27 @@ where
         Self {
             interned: InternedIngredient::new(index, debug_name),
             dependent_fns: IngredientList::new(),
+            tombstones: Default::default(),
+            fmt_data: dump_opaque,
             debug_name,
         }
     }
 
+    /// Sets the function used to format the id fields for [`crate::debug::dump`].
+    pub fn with_dump_fmt(self, fmt_data: DumpFmt<Data>) -> Self {
+        Self { fmt_data, ..self }
+    }
+
+    /// Makes queries that read the id fields of a struct depend on just that struct,
+    /// rather than on every struct of this type;
+    /// see [`InternedIngredient::with_per_entry_deps`].
+    pub fn with_per_entry_deps(self) -> Self {
+        Self {
+            interned: self.interned.with_per_entry_deps(),
+            ..self
+        }
+    }
+
     pub fn database_key_index(&self, id: Id) -> DatabaseKeyIndex {
         DatabaseKeyIndex {
             ingredient_index: self.interned.ingredient_index(),
@@ -78,7 +104,10 @@ where
         }
     }
 
-    pub fn new_struct(&self, runtime: &Runtime, data: Data) -> Id {
+    /// Creates (or re-creates) the tracked struct with the given id fields
+    /// on behalf of the active query.
+    pub fn new_struct<DB: ?Sized + Database>(&self, db: &DB, data: Data) -> Id {
+        let runtime = db.salsa_runtime();
         let data_hash = crate::hash::hash(&data);
         let (query_key, disambiguator) = runtime.disambiguate_entity(
             self.interned.ingredient_index(),
@@ -91,11 +120,25 @@ where
             data,
         };
         let result = self.interned.intern(runtime, entity_key);
-        runtime.add_output(self.database_key_index(result).into());
+        let key = self.database_key_index(result);
+        runtime.add_output(key.into());
+        runtime.report_event(db, EventKinds::DID_CREATE_TRACKED_STRUCT, || {
+            EventKind::DidCreateTrackedStruct {
+                key,
+                created_by: query_key,
+            }
+        });
         result
     }
 
+    /// True if `id` was created by the active query during its current execution.
+    pub fn is_output_of_active_query(&self, runtime: &Runtime, id: Id) -> bool {
+        runtime.is_output_of_active_query(self.database_key_index(id))
+    }
+
+    #[track_caller]
     pub fn tracked_struct_data<'db>(&'db self, runtime: &'db Runtime, id: Id) -> &'db Data {
+        self.tombstones.check(id);
         &self.interned.data(runtime, id).data
     }
 
@@ -108,16 +151,17 @@ where
     ///
     /// Using this method on an entity id that MAY be used in the current revision will lead to
     /// unspecified results (but not UB). See [`InternedIngredient::delete_index`] for more
-    /// discussion and important considerations.
+    /// discussion and important considerations. With the `stale-id-checks` feature,
+    /// later uses of the id panic instead.
     pub(crate) fn delete_entity(&self, db: &dyn crate::Database, id: Id) {
-        db.salsa_event(Event {
-            runtime_id: db.salsa_runtime().id(),
-            kind: crate::EventKind::DidDiscard {
+        db.salsa_runtime()
+            .report_event(db, EventKinds::DID_DISCARD, || EventKind::DidDiscard {
                 key: self.database_key_index(id),
-            },
-        });
+            });
 
         self.interned.delete_index(id);
+        self.tombstones
+            .record(id, db.salsa_runtime().current_revision());
         for dependent_fn in self.dependent_fns.iter() {
             db.salsa_struct_deleted(dependent_fn, id.as_id());
         }
@@ -181,9 +225,33 @@ where
         panic!("unexpected call: interned ingredients do not register for salsa struct deletion events");
     }
 
+    fn debug_name(&self) -> &'static str {
+        self.debug_name
+    }
+
     fn fmt_index(&self, index: Option<crate::Id>, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt_index(self.debug_name, index, fmt)
     }
+
+    fn dump(&self, dump: &mut Dump<'_>) -> io::Result<()> {
+        dump.ingredient("tracked struct", self.debug_name)?;
+        let db = dump.db();
+        self.interned.for_each_sorted(|id, key| {
+            let created_by = match key.query_key {
+                Some(query_key) => format!("{:?}", query_key.debug(db)),
+                None => "none".to_string(),
+            };
+            dump.entry(
+                self.database_key_index(id),
+                format_args!(
+                    "created_by={}, disambiguator={}, id_fields={:?}",
+                    created_by,
+                    key.disambiguator.0,
+                    DumpValue(&key.data, self.fmt_data)
+                ),
+            )
+        })
+    }
 }
 
 impl<Id, Data> IngredientRequiresReset for TrackedStructIngredient<Id, Data>
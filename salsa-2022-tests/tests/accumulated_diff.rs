@@ -0,0 +1,303 @@
+//! Test `accumulated_diff`, which returns the values added to and removed from
+//! an accumulator since the last call with the same tracker.
+
+use salsa::accumulator::{AccumulatedDiff, AccumulatedTracker};
+use salsa::Durability;
+use salsa_2022_tests::{HasLogger, Logger};
+
+use expect_test::expect;
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(Project, File, Diagnostic, check_project, check_file);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct Project {
+    files: Vec<File>,
+}
+
+#[salsa::input]
+struct File {
+    text: String,
+}
+
+#[salsa::accumulator]
+struct Diagnostic(String);
+
+#[salsa::tracked]
+#[allow(dead_code)]
+fn check_project(db: &dyn Db, project: Project) {
+    for file in project.files(db) {
+        check_file(db, file);
+    }
+}
+
+/// Reports the lines that are longer than 10 characters.
+/// Always returns `()`, so a re-execution is always backdated.
+#[salsa::tracked]
+fn check_file(db: &dyn Db, file: File) {
+    db.push_log(format!("check_file({:?})", file.text(db)));
+    for line in file.text(db).lines().filter(|line| line.len() > 10) {
+        Diagnostic::push(db, format!("long line: {line}"));
+    }
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+fn diff(
+    db: &Database,
+    project: Project,
+    tracker: &mut AccumulatedTracker<String>,
+) -> AccumulatedDiff<String> {
+    check_project::accumulated_diff::<Diagnostic>(db, project, tracker)
+}
+
+#[test]
+fn first_diff_adds_everything() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "a very long line".to_string());
+    let b = File::new(&mut db, "short\nanother long line".to_string());
+    let project = Project::new(&mut db, vec![a, b]);
+
+    let mut tracker = AccumulatedTracker::new();
+    let diff = diff(&db, project, &mut tracker);
+    expect![[r#"
+        AccumulatedDiff {
+            added: [
+                "long line: another long line",
+                "long line: a very long line",
+            ],
+            removed: [],
+        }
+    "#]]
+    .assert_debug_eq(&diff);
+    assert_eq!(
+        diff.added,
+        check_project::accumulated::<Diagnostic>(&db, project)
+    );
+
+    // Nothing changed.
+    assert!(self::diff(&db, project, &mut tracker).is_empty());
+}
+
+#[test]
+fn backdated_query_is_compared() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "a very long line".to_string());
+    let b = File::new(&mut db, "short".to_string());
+    let project = Project::new(&mut db, vec![a, b]);
+
+    let mut tracker = AccumulatedTracker::new();
+    diff(&db, project, &mut tracker);
+    db.assert_logs(expect![[r#"
+        [
+            "check_file(\"a very long line\")",
+            "check_file(\"short\")",
+        ]"#]]);
+
+    // `check_file` is re-executed and backdated, so `check_project` is not re-executed,
+    // but the diagnostics of `b` still change.
+    b.set_text(&mut db).to("now a long line".to_string());
+    expect![[r#"
+        AccumulatedDiff {
+            added: [
+                "long line: now a long line",
+            ],
+            removed: [],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+    db.assert_logs(expect![[r#"
+        [
+            "check_file(\"now a long line\")",
+        ]"#]]);
+
+    a.set_text(&mut db).to("short again".to_string());
+    expect![[r#"
+        AccumulatedDiff {
+            added: [
+                "long line: short again",
+            ],
+            removed: [
+                "long line: a very long line",
+            ],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+
+    a.set_text(&mut db).to("short".to_string());
+    expect![[r#"
+        AccumulatedDiff {
+            added: [],
+            removed: [
+                "long line: short again",
+            ],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+}
+
+#[test]
+fn edit_with_same_diagnostics_is_no_change() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "a very long line".to_string());
+    let b = File::new(&mut db, "short".to_string());
+    let project = Project::new(&mut db, vec![a, b]);
+
+    let mut tracker = AccumulatedTracker::new();
+    assert_eq!(diff(&db, project, &mut tracker).added.len(), 1);
+    db.assert_logs(expect![[r#"
+        [
+            "check_file(\"a very long line\")",
+            "check_file(\"short\")",
+        ]"#]]);
+
+    // `check_file(a)` executes again and accumulates the same diagnostic.
+    a.set_text(&mut db)
+        .to("a very long line\nshort".to_string());
+    assert!(diff(&db, project, &mut tracker).is_empty());
+    db.assert_logs(expect![[r#"
+        [
+            "check_file(\"a very long line\\nshort\")",
+        ]"#]]);
+
+    // A diagnostic that moves from one file to another is no change either.
+    a.set_text(&mut db).to("short".to_string());
+    b.set_text(&mut db).to("a very long line".to_string());
+    assert!(diff(&db, project, &mut tracker).is_empty());
+}
+
+#[test]
+fn changes_verified_before_the_diff_are_reported() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "a very long line".to_string());
+    let b = File::new(&mut db, "short".to_string());
+    let project = Project::new(&mut db, vec![a, b]);
+
+    let mut tracker = AccumulatedTracker::new();
+    assert_eq!(diff(&db, project, &mut tracker).added.len(), 1);
+
+    // `check_file(b)` executes again while `check_project` is verified,
+    // one revision before the next diff.
+    b.set_text(&mut db).to("now a long line".to_string());
+    check_project(&db, project);
+    File::new(&mut db, "unrelated".to_string());
+
+    expect![[r#"
+        AccumulatedDiff {
+            added: [
+                "long line: now a long line",
+            ],
+            removed: [],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+    assert!(diff(&db, project, &mut tracker).is_empty());
+}
+
+#[test]
+fn queries_no_longer_depended_upon_are_removed() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "a very long line".to_string());
+    let b = File::new(&mut db, "another long line".to_string());
+    let project = Project::new(&mut db, vec![a, b]);
+
+    let mut tracker = AccumulatedTracker::new();
+    assert_eq!(diff(&db, project, &mut tracker).added.len(), 2);
+
+    project.set_files(&mut db).to(vec![b]);
+    expect![[r#"
+        AccumulatedDiff {
+            added: [],
+            removed: [
+                "long line: a very long line",
+            ],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+
+    project.set_files(&mut db).to(vec![b, a]);
+    expect![[r#"
+        AccumulatedDiff {
+            added: [
+                "long line: a very long line",
+            ],
+            removed: [],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+}
+
+#[test]
+fn durable_subtrees_are_skipped() {
+    let mut db = Database::default();
+    let library = File::new(&mut db, "a long library line".to_string());
+    library
+        .set_text(&mut db)
+        .with_durability(Durability::HIGH)
+        .to("a long library line".to_string());
+    let project = Project::new(&mut db, vec![library]);
+
+    let mut tracker = AccumulatedTracker::new();
+    assert_eq!(diff(&db, project, &mut tracker).added.len(), 1);
+
+    // Only low durability inputs changed, so the library's subtree is skipped.
+    let other = File::new(&mut db, "short".to_string());
+    assert!(diff(&db, project, &mut tracker).is_empty());
+
+    project.set_files(&mut db).to(vec![library, other]);
+    assert!(diff(&db, project, &mut tracker).is_empty());
+    expect![[r#"
+        [
+            "long line: a long library line",
+        ]
+    "#]]
+    .assert_debug_eq(&check_project::accumulated::<Diagnostic>(&db, project));
+
+    library
+        .set_text(&mut db)
+        .with_durability(Durability::HIGH)
+        .to("short".to_string());
+    expect![[r#"
+        AccumulatedDiff {
+            added: [],
+            removed: [
+                "long line: a long library line",
+            ],
+        }
+    "#]]
+    .assert_debug_eq(&diff(&db, project, &mut tracker));
+}
+
+#[test]
+#[should_panic(expected = "accumulated values of a different query are already tracked")]
+fn tracker_is_bound_to_its_root() {
+    let mut db = Database::default();
+    let project1 = Project::new(&mut db, vec![]);
+    let project2 = Project::new(&mut db, vec![]);
+
+    let mut tracker = AccumulatedTracker::new();
+    diff(&db, project1, &mut tracker);
+    diff(&db, project2, &mut tracker);
+}
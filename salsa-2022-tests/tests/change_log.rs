@@ -0,0 +1,128 @@
+//! Test the change log of the runtime, which records the inputs
+//! written in each revision.
+
+use expect_test::expect;
+use salsa::{DebugWithDb, Durability};
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+    count: u32,
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+fn change_log(db: &Database) -> Vec<String> {
+    db.storage
+        .runtime()
+        .change_log()
+        .map(|(revision, key, durability)| {
+            format!(
+                "{:?}: {:?}, {:?}",
+                revision,
+                key.map(|key| format!("{:?}", key.debug(db))),
+                durability
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn records_writes() {
+    let mut db = Database::default();
+    let a = MyInput::new(&mut db, "a".to_string(), 0);
+    let b = MyInput::new(&mut db, "b".to_string(), 0);
+    a.set_text(&mut db).to("aa".to_string());
+    b.set_count(&mut db).with_durability(Durability::HIGH).to(1);
+    b.set_count(&mut db).to(2);
+
+    // The initial values are not recorded; the durability is that of the overwritten value.
+    expect![[r#"
+        [
+            "R4: Some(\"text(0)\"), Durability(0)",
+            "R5: Some(\"count(1)\"), Durability(0)",
+            "R6: Some(\"count(1)\"), Durability(2)",
+        ]
+    "#]]
+    .assert_debug_eq(&change_log(&db));
+
+    let revision = db.storage.runtime().current_revision();
+    let changes = db.storage.runtime().changes_in(revision).unwrap();
+    expect![[r#"
+        [
+            "count(1)",
+        ]
+    "#]]
+    .assert_debug_eq(
+        &changes
+            .iter()
+            .map(|(key, _)| format!("{:?}", key.unwrap().debug(&db)))
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn synthetic_writes() {
+    let mut db = Database::default();
+    let before = db.storage.runtime().current_revision();
+    db.storage.synthetic_write(Durability::MEDIUM);
+    assert!(db.storage.runtime().current_revision() > before);
+    expect![[r#"
+        [
+            "R2: None, Durability(1)",
+        ]
+    "#]]
+    .assert_debug_eq(&change_log(&db));
+}
+
+#[test]
+fn bounded_capacity() {
+    let mut db = Database::default();
+    db.storage.set_change_log_capacity(2);
+    let input = MyInput::new(&mut db, "a".to_string(), 0);
+    input.set_count(&mut db).to(1);
+    let first_write = db.storage.runtime().current_revision();
+    for count in 2..=3 {
+        input.set_count(&mut db).to(count);
+    }
+    input.set_text(&mut db).to("b".to_string());
+
+    expect![[r#"
+        [
+            "R5: Some(\"count(0)\"), Durability(0)",
+            "R6: Some(\"text(0)\"), Durability(0)",
+        ]
+    "#]]
+    .assert_debug_eq(&change_log(&db));
+
+    // The writes of the first revision were dropped.
+    let runtime = db.storage.runtime();
+    assert_eq!(runtime.changes_in(first_write), None);
+    assert_eq!(
+        runtime
+            .changes_in(runtime.current_revision())
+            .unwrap()
+            .len(),
+        1
+    );
+
+    db.storage.set_change_log_capacity(0);
+    assert!(change_log(&db).is_empty());
+}
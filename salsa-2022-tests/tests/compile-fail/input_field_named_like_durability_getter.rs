@@ -0,0 +1,12 @@
+#[salsa::jar(db = Db)]
+struct Jar(MyInput);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input(jar = Jar)]
+struct MyInput {
+    text: String,
+    text_durability: u32,
+}
+
+fn main() {}
@@ -0,0 +1,145 @@
+//! Test the `eq = path` option on tracked functions and the `#[eq(path)]`
+//! attribute on tracked struct fields: values that are equal according to
+//! the given function are backdated.
+
+use expect_test::expect;
+use salsa_2022_tests::{HasLogger, Logger};
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    MyTracked,
+    parse,
+    parse_len,
+    create_tracked,
+    tracked_len,
+);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+}
+
+/// A value whose `offset` does not matter for early cutoff.
+#[derive(Clone, Debug)]
+struct Parsed {
+    words: Vec<String>,
+    #[allow(dead_code)]
+    offset: usize,
+}
+
+fn same_words(old: &Parsed, new: &Parsed) -> bool {
+    old.words == new.words
+}
+
+fn parse_text(text: &str) -> Parsed {
+    Parsed {
+        words: text.split_whitespace().map(str::to_string).collect(),
+        offset: text.len() - text.trim_start().len(),
+    }
+}
+
+#[salsa::tracked(eq = same_words)]
+fn parse(db: &dyn Db, input: MyInput) -> Parsed {
+    parse_text(&input.text(db))
+}
+
+#[salsa::tracked]
+fn parse_len(db: &dyn Db, input: MyInput) -> usize {
+    db.push_log("parse_len".to_string());
+    parse(db, input).words.len()
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    #[eq(same_words)]
+    parsed: Parsed,
+}
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    // Does not go through `parse`, so that the field itself is compared.
+    db.push_log("create_tracked".to_string());
+    MyTracked::new(db, parse_text(&input.text(db)))
+}
+
+#[salsa::tracked]
+fn tracked_len(db: &dyn Db, input: MyInput) -> usize {
+    db.push_log("tracked_len".to_string());
+    create_tracked(db, input).parsed(db).words.len()
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn tracked_fn_eq() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b".to_string());
+    assert_eq!(parse_len(&db, input), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "parse_len",
+        ]"#]]);
+
+    // Only the offset changes, so `parse` is backdated.
+    input.set_text(&mut db).to("  a b".to_string());
+    assert_eq!(parse_len(&db, input), 2);
+    db.assert_logs(expect!["[]"]);
+
+    // The words change, so it is not.
+    input.set_text(&mut db).to("a b c".to_string());
+    assert_eq!(parse_len(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "parse_len",
+        ]"#]]);
+}
+
+#[test]
+fn tracked_struct_field_eq() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b".to_string());
+    assert_eq!(tracked_len(&db, input), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "tracked_len",
+            "create_tracked",
+        ]"#]]);
+
+    input.set_text(&mut db).to("  a b".to_string());
+    assert_eq!(tracked_len(&db, input), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "create_tracked",
+        ]"#]]);
+
+    input.set_text(&mut db).to("a".to_string());
+    assert_eq!(tracked_len(&db, input), 1);
+    db.assert_logs(expect![[r#"
+        [
+            "create_tracked",
+            "tracked_len",
+        ]"#]]);
+}
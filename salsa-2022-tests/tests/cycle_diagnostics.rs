@@ -0,0 +1,113 @@
+//! Test that unexpected cycles report structured information about
+//! their participants and where the cycle was closed.
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+use expect_test::expect;
+use salsa::cycle::CycleRecoveryStrategy;
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, cycle_a, cycle_b);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl RefUnwindSafe for Database {}
+
+#[salsa::input(jar = Jar, debug)]
+struct MyInput {
+    field: u32,
+}
+
+#[salsa::tracked(jar = Jar)]
+fn cycle_a(db: &dyn Db, input: MyInput) -> u32 {
+    cycle_b(db, input, "hello".to_string())
+}
+
+#[salsa::tracked(jar = Jar)]
+fn cycle_b(db: &dyn Db, input: MyInput, name: String) -> u32 {
+    let _ = name;
+    cycle_a(db, input) // closes the cycle
+}
+
+fn extract_cycle(f: impl FnOnce() + UnwindSafe) -> salsa::Cycle {
+    let v = std::panic::catch_unwind(f);
+    if let Err(d) = &v {
+        if let Some(cycle) = d.downcast_ref::<salsa::Cycle>() {
+            return cycle.clone();
+        }
+    }
+    panic!("unexpected value: {:?}", v)
+}
+
+#[test]
+fn participants() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 22);
+    let cycle = extract_cycle(|| {
+        cycle_a(&db, input);
+    });
+
+    let participants = cycle.participants(&db);
+    let names: Vec<_> = participants.iter().map(|p| p.function_name).collect();
+    assert_eq!(names, ["cycle_a", "cycle_b"]);
+    assert!(participants
+        .iter()
+        .all(|p| p.recovery_strategy == CycleRecoveryStrategy::Panic));
+
+    let rendered: Vec<_> = participants.iter().map(|p| p.to_string()).collect();
+    let expected = expect![[r#"
+        [
+            "cycle_a(MyInput { [salsa id]: 0, field: 22 })",
+            "cycle_b(MyInput { [salsa id]: 0, field: 22 }, \"hello\")",
+        ]
+    "#]];
+    expected.assert_debug_eq(&rendered);
+}
+
+#[test]
+fn location_and_display() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 22);
+    let cycle = extract_cycle(|| {
+        cycle_a(&db, input);
+    });
+
+    // The cycle is closed by the call to `cycle_a` within `cycle_b`.
+    let location = cycle.location();
+    assert!(location.file().ends_with("cycle_diagnostics.rs"));
+
+    let expected = expect![[r#"
+        cycle_a(MyInput { [salsa id]: 0, field: 22 }) -> cycle_b(MyInput { [salsa id]: 0, field: 22 }, "hello") -> cycle_a(MyInput { [salsa id]: 0, field: 22 })"#]];
+    let prefix = format!("cycle detected at {location}: ");
+    expected.assert_eq(cycle.to_string().strip_prefix(&prefix).unwrap());
+}
+
+#[test]
+fn equality_ignores_location() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 22);
+    let from_a = extract_cycle(|| {
+        cycle_a(&db, input);
+    });
+    let from_b = extract_cycle(|| {
+        cycle_b(&db, input, "hello".to_string());
+    });
+
+    // The same cycle, closed by a different call.
+    assert_ne!(from_a.location(), from_b.location());
+    assert_eq!(from_a, from_b);
+}
@@ -0,0 +1,303 @@
+//! Test differential verification, which checks memoized values
+//! against executions from scratch.
+
+use std::panic::AssertUnwindSafe;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use expect_test::expect;
+use salsa_2022_tests::{HasLogger, Logger};
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    MyTracked,
+    Log,
+    total,
+    doubled,
+    impure,
+    create_tracked,
+    tracked_value,
+    no_eq_len,
+    create_impure,
+    impure_tracked_value,
+    impure_tracked_sum,
+);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {
+    /// State that is read without salsa knowing about it.
+    fn untracked_offset(&self) -> u32;
+}
+
+#[salsa::input]
+struct MyInput {
+    field: u32,
+    other: u32,
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    value: u32,
+}
+
+#[salsa::accumulator]
+struct Log(String);
+
+#[salsa::tracked]
+fn total(db: &dyn Db, input: MyInput) -> u32 {
+    db.push_log(format!("total({})", input.field(db)));
+    doubled(db, input) + 1
+}
+
+#[salsa::tracked]
+fn doubled(db: &dyn Db, input: MyInput) -> u32 {
+    db.push_log(format!("doubled({})", input.field(db)));
+    input.field(db) / 2 * 2
+}
+
+/// Misses the dependency on the offset.
+#[salsa::tracked]
+fn impure(db: &dyn Db, input: MyInput) -> u32 {
+    input.field(db) + db.untracked_offset()
+}
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    Log::push(db, format!("create_tracked({})", input.field(db)));
+    MyTracked::new(db, input.field(db))
+}
+
+#[salsa::tracked]
+fn tracked_value(db: &dyn Db, input: MyInput) -> u32 {
+    create_tracked(db, input).value(db)
+}
+
+#[salsa::tracked(no_eq)]
+fn no_eq_len(db: &dyn Db, input: MyInput) -> u32 {
+    input.field(db) + db.untracked_offset()
+}
+
+/// Misses the dependency on the offset, which only shows in the field of the tracked struct.
+#[salsa::tracked]
+fn create_impure(db: &dyn Db, input: MyInput) -> MyTracked {
+    MyTracked::new(db, input.field(db) + db.untracked_offset())
+}
+
+#[salsa::tracked]
+fn impure_tracked_value(db: &dyn Db, input: MyInput) -> u32 {
+    create_impure(db, input).value(db)
+}
+
+/// Misses the dependency on the offset, which determines how many tracked structs are created.
+#[salsa::tracked]
+fn impure_tracked_sum(db: &dyn Db, input: MyInput) -> u32 {
+    (0..=db.untracked_offset())
+        .map(|value| MyTracked::new(db, input.field(db) + value).value(db))
+        .sum()
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Arc<Logger>,
+    offset: AtomicU32,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+
+    fn fork_for_differential_verification(&self) -> Option<Box<dyn salsa::Database>> {
+        Some(Box::new(Database {
+            storage: self.storage.differential_fork(),
+            logger: self.logger.clone(),
+            offset: AtomicU32::new(self.untracked_offset()),
+        }))
+    }
+}
+
+impl Db for Database {
+    fn untracked_offset(&self) -> u32 {
+        self.offset.load(Ordering::SeqCst)
+    }
+}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn memoized_and_backdated_values_are_checked() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 2, 0);
+
+    // Values that are computed in the current revision are not checked.
+    assert_eq!(total(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "total(2)",
+            "doubled(2)",
+        ]"#]]);
+
+    // Both functions are re-executed and backdated, so both are executed from scratch,
+    // each in a fork of its own: `total` executes `doubled` from scratch again.
+    input.set_field(&mut db).to(3);
+    assert_eq!(total(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "total(3)",
+            "doubled(3)",
+            "doubled(3)",
+            "total(3)",
+            "doubled(3)",
+        ]"#]]);
+
+    // Each check starts from scratch, even within a revision.
+    assert_eq!(total(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "total(3)",
+            "doubled(3)",
+        ]"#]]);
+}
+
+#[test]
+fn sampling() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(2);
+    let input = MyInput::new(&mut db, 2, 0);
+    assert_eq!(doubled(&db, input), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "doubled(2)",
+        ]"#]]);
+
+    for other in 1..=4 {
+        input.set_other(&mut db).to(other);
+        assert_eq!(doubled(&db, input), 2);
+    }
+
+    // `doubled` is memoized in each revision, but only checked in every other one.
+    db.assert_logs(expect![[r#"
+        [
+            "doubled(2)",
+            "doubled(2)",
+        ]"#]]);
+}
+
+#[test]
+#[should_panic(
+    expected = "differential verification failed for `impure(0)`: the memoized value is `1`, but executing from scratch yields `2`"
+)]
+fn missed_dependency() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 1, 0);
+    assert_eq!(impure(&db, input), 1);
+
+    db.offset.store(1, Ordering::SeqCst);
+    input.set_other(&mut db).to(1);
+    impure(&db, input);
+}
+
+#[test]
+fn values_of_no_eq_functions_are_not_checked() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 1, 0);
+    assert_eq!(no_eq_len(&db, input), 1);
+
+    db.offset.store(1, Ordering::SeqCst);
+    input.set_other(&mut db).to(1);
+    assert_eq!(no_eq_len(&db, input), 1);
+}
+
+#[test]
+fn tracked_structs_and_accumulated_values() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 1, 0);
+    assert_eq!(tracked_value(&db, input), 1);
+    let tracked = create_tracked(&db, input);
+
+    input.set_other(&mut db).to(1);
+    assert_eq!(tracked_value(&db, input), 1);
+
+    // Executing `create_tracked` from scratch creates the same struct
+    // and does not accumulate its value a second time.
+    assert_eq!(create_tracked(&db, input), tracked);
+    expect![[r#"
+        [
+            "create_tracked(1)",
+        ]
+    "#]]
+    .assert_debug_eq(&create_tracked::accumulated::<Log>(&db, input));
+}
+
+#[test]
+#[should_panic(
+    expected = "differential verification failed for `impure_tracked_value(0)`: the memoized value is `1`, but executing from scratch yields `2`"
+)]
+fn missed_dependency_in_tracked_struct_field() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 1, 0);
+    assert_eq!(impure_tracked_value(&db, input), 1);
+
+    // The memoized struct keeps its field, but executing from scratch assigns a new one.
+    db.offset.store(1, Ordering::SeqCst);
+    input.set_other(&mut db).to(1);
+    impure_tracked_value(&db, input);
+}
+
+#[test]
+#[should_panic(
+    expected = "differential verification failed for `impure_tracked_sum(0)`: the memoized value is `1`, but executing from scratch yields `3`"
+)]
+fn missed_dependency_creating_tracked_struct() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 1, 0);
+    assert_eq!(impure_tracked_sum(&db, input), 1);
+
+    // Executing from scratch creates a struct that has no memoized fields.
+    db.offset.store(1, Ordering::SeqCst);
+    input.set_other(&mut db).to(1);
+    impure_tracked_sum(&db, input);
+}
+
+#[test]
+fn checks_leave_the_database_unchanged() {
+    let mut db = Database::default();
+    db.storage.set_differential_verification(1);
+    let input = MyInput::new(&mut db, 1, 0);
+    assert_eq!(impure_tracked_sum(&db, input), 1);
+
+    // Verify the memoized value in the new revision before taking the dump.
+    db.offset.store(1, Ordering::SeqCst);
+    input.set_other(&mut db).to(1);
+    db.storage.set_differential_verification(0);
+    assert_eq!(impure_tracked_sum(&db, input), 1);
+    let dump_before = dump(&db);
+
+    db.storage.set_differential_verification(1);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| impure_tracked_sum(&db, input)));
+    assert!(result.is_err());
+
+    // The structs created and the values computed from scratch were discarded with the fork.
+    assert_eq!(dump(&db), dump_before);
+}
+
+fn dump(db: &Database) -> String {
+    let mut dump = vec![];
+    salsa::debug::dump(db, &mut dump).unwrap();
+    String::from_utf8(dump).unwrap()
+}
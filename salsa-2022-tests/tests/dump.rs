@@ -0,0 +1,129 @@
+//! Test `salsa::debug::dump`, which writes the contents of every ingredient.
+
+use expect_test::expect;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    Name,
+    MyTracked,
+    Diagnostic,
+    create_tracked,
+    text_len,
+    NoDebug,
+);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+}
+
+#[salsa::interned]
+struct Name {
+    name: String,
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    #[id]
+    name: Name,
+    len: usize,
+}
+
+#[salsa::accumulator]
+struct Diagnostic(String);
+
+/// Values without a `Debug` impl are shown as `..`.
+#[salsa::interned]
+struct NoDebug {
+    value: NotDebug,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct NotDebug;
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    let name = Name::new(db, input.text(db));
+    MyTracked::new(db, name, input.text(db).len())
+}
+
+#[salsa::tracked]
+fn text_len(db: &dyn Db, input: MyInput) -> usize {
+    let len = create_tracked(db, input).len(db);
+    if len == 0 {
+        Diagnostic::push(db, "empty".to_string());
+    }
+    len
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+fn dump(db: &Database) -> String {
+    let mut out = vec![];
+    salsa::debug::dump(db, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn dump_after_edit() {
+    let mut db = Database::default();
+    NoDebug::new(&db, NotDebug);
+    let input = MyInput::new(&mut db, "abc".to_string());
+    assert_eq!(text_len(&db, input), 3);
+
+    input.set_text(&mut db).to(String::new());
+    assert_eq!(text_len(&db, input), 0);
+
+    expect![[r#"
+        [0] input field text
+            text(0): value="", durability=Durability(0), changed_at=R3
+        [1] input MyInput
+            MyInput(0)
+        [2] interned Name
+            Name(0): Name { name: "abc" }
+            Name(1): Name { name: "" }
+        [3] function name
+            name(1): value=0, verified_at=R3, changed_at=R3, durability=Durability(0), origin=Field, deps=[]
+        [4] tracked struct MyTracked
+            MyTracked(1): created_by=create_tracked(0), disambiguator=0, id_fields=MyTracked { name: Name(Id { value: 2 }) }
+        [5] accumulator Diagnostic
+            text_len(0): produced_at=R3, values=["empty"]
+        [6] function create_tracked
+            create_tracked(0): value=MyTracked(Id { value: 2 }), verified_at=R3, changed_at=R3, durability=Durability(0), origin=Derived, deps=[text(0), Name(), MyTracked()]
+        [7] function text_len
+            text_len(0): value=0, verified_at=R3, changed_at=R3, durability=Durability(0), origin=Derived, deps=[create_tracked(0), name(1)]
+        [8] interned NoDebug
+            NoDebug(0): NoDebug { value: .. }
+    "#]]
+    .assert_eq(&dump(&db));
+}
+
+#[test]
+fn dump_is_stable() {
+    let run = || {
+        let mut db = Database::default();
+        let inputs: Vec<_> = (0..10)
+            .map(|i| MyInput::new(&mut db, "x".repeat(i)))
+            .collect();
+        for &input in &inputs {
+            text_len(&db, input);
+        }
+        dump(&db)
+    };
+    assert_eq!(run(), run());
+}
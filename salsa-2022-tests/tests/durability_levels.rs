@@ -0,0 +1,164 @@
+//! Test a database configured with more than the default
+//! three durability levels.
+
+use expect_test::expect;
+use salsa::{DebugWithDb, Durability};
+use salsa_2022_tests::{HasLogger, Logger};
+use test_log::test;
+
+const TOOLCHAIN: Durability = Durability::new(4);
+const DEPENDENCIES: Durability = Durability::new(3);
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, Pinned, Misconfigured, outer, inner);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    toolchain: u32,
+    dependencies: u32,
+}
+
+/// Uses a level and an expression as default durabilities.
+#[salsa::input(durability = 3)]
+struct Pinned {
+    #[durability(TOOLCHAIN)]
+    toolchain: u32,
+    dependencies: u32,
+}
+
+/// Defaults to a level that the database does not have.
+#[salsa::input(durability = 5)]
+struct Misconfigured {
+    value: u32,
+}
+
+#[salsa::tracked]
+fn outer(db: &dyn Db, input: MyInput) -> u32 {
+    inner(db, input) + 1
+}
+
+#[salsa::tracked]
+fn inner(db: &dyn Db, input: MyInput) -> u32 {
+    db.push_log(format!("inner({:?})", input.toolchain(db)));
+    input.toolchain(db)
+}
+
+#[salsa::db(Jar, durabilities = 5)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+
+    fn salsa_event(&self, event: salsa::Event) {
+        if let salsa::EventKind::DidValidateMemoizedValue { .. } = event.kind {
+            self.push_log(format!("{:?}", event.debug(self)));
+        }
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn durability_above_high() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1, 1);
+    input
+        .set_toolchain(&mut db)
+        .with_durability(TOOLCHAIN)
+        .to(1);
+    input
+        .set_dependencies(&mut db)
+        .with_durability(DEPENDENCIES)
+        .to(1);
+    assert_eq!(outer(&db, input), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "inner(1)",
+        ]"#]]);
+
+    // `outer` only depends on toolchain inputs, so changing a dependency
+    // validates it without walking its inputs.
+    input
+        .set_dependencies(&mut db)
+        .with_durability(DEPENDENCIES)
+        .to(2);
+    assert_eq!(outer(&db, input), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "Event { runtime_id: RuntimeId { counter: 0 }, kind: DidValidateMemoizedValue { database_key: outer(0) } }",
+        ]"#]]);
+
+    // Changing the toolchain is noticed.
+    input
+        .set_toolchain(&mut db)
+        .with_durability(TOOLCHAIN)
+        .to(2);
+    assert_eq!(outer(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "inner(2)",
+        ]"#]]);
+}
+
+#[test]
+#[should_panic(expected = "the database has 5 durability levels")]
+fn durability_out_of_range() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1, 1);
+    // The level is checked when it is given to the setter, before any value is written.
+    let _setter = input
+        .set_toolchain(&mut db)
+        .with_durability(Durability::new(5));
+}
+
+#[test]
+fn default_durability_levels() {
+    let mut db = Database::default();
+    let pinned = Pinned::new(&mut db, 1, 1);
+    assert_eq!(pinned.toolchain_durability(&db), TOOLCHAIN);
+    assert_eq!(pinned.dependencies_durability(&db), DEPENDENCIES);
+
+    pinned.set_dependencies(&mut db).to(2);
+    assert_eq!(pinned.dependencies_durability(&db), DEPENDENCIES);
+}
+
+#[test]
+#[should_panic(expected = "the database has 5 durability levels")]
+fn default_durability_out_of_range() {
+    let mut db = Database::default();
+    Misconfigured::new(&mut db, 1);
+}
+
+#[test]
+fn storage_with_durabilities() {
+    let mut db = Database {
+        storage: salsa::Storage::with_durabilities(6),
+        logger: Default::default(),
+    };
+    let input = MyInput::new(&mut db, 1, 1);
+    input
+        .set_toolchain(&mut db)
+        .with_durability(Durability::new(5))
+        .to(2);
+    assert_eq!(outer(&db, input), 3);
+}
+
+#[test]
+#[should_panic(expected = "the number of durability levels must be at least 3")]
+fn too_few_durabilities() {
+    let _storage = salsa::Storage::<Database>::with_durabilities(2);
+}
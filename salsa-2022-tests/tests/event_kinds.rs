@@ -0,0 +1,170 @@
+//! Test `Database::salsa_event_kinds`, which filters the events passed to
+//! `salsa_event`, and the events reported for writes and new structs.
+
+use salsa::{DebugWithDb, EventKind, EventKinds};
+use salsa_2022_tests::{HasLogger, Logger};
+
+use expect_test::expect;
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, MyTracked, Name, create_tracked);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    field: u32,
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    field: u32,
+}
+
+#[salsa::interned]
+struct Name {
+    text: String,
+}
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    Name::new(db, "name".to_string());
+    MyTracked::new(db, input.field(db))
+}
+
+/// Reports everything but the (very frequent) cancellation checks.
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_event(&self, event: salsa::Event) {
+        assert!(!matches!(event.kind, EventKind::WillCheckCancellation));
+        self.push_log(format!("{:?}", event.kind.debug(self)));
+    }
+
+    fn salsa_event_kinds() -> EventKinds {
+        EventKinds::ALL.difference(EventKinds::WILL_CHECK_CANCELLATION)
+    }
+
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+/// Only wants executions.
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct ExecuteDatabase {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for ExecuteDatabase {
+    fn salsa_event(&self, event: salsa::Event) {
+        self.push_log(format!("{:?}", event.kind.debug(self)));
+    }
+
+    fn salsa_event_kinds() -> EventKinds {
+        EventKinds::WILL_EXECUTE
+    }
+
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for ExecuteDatabase {}
+
+impl HasLogger for ExecuteDatabase {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn new_event_kinds() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1);
+    db.assert_logs(expect![[r#"
+        [
+            "DidStartRevision { revision: R2 }",
+        ]"#]]);
+
+    create_tracked(&db, input);
+    db.assert_logs(expect![[r#"
+        [
+            "WillExecute { database_key: create_tracked(0) }",
+            "DidInternValue { key: Name(0) }",
+            "DidCreateTrackedStruct { key: MyTracked(0), created_by: create_tracked(0) }",
+        ]"#]]);
+
+    input.set_field(&mut db).to(2);
+    db.assert_logs(expect![[r#"
+        [
+            "WillSetInput { input: field(0) }",
+            "DidStartRevision { revision: R3 }",
+        ]"#]]);
+
+    // The value is already interned, and the tracked struct keeps its id.
+    create_tracked(&db, input);
+    db.assert_logs(expect![[r#"
+        [
+            "WillExecute { database_key: create_tracked(0) }",
+            "DidCreateTrackedStruct { key: MyTracked(0), created_by: create_tracked(0) }",
+        ]"#]]);
+}
+
+#[test]
+fn filtered() {
+    let mut db = ExecuteDatabase::default();
+    let input = MyInput::new(&mut db, 1);
+    create_tracked(&db, input);
+    create_tracked(&db, input);
+    input.set_field(&mut db).to(2);
+    create_tracked(&db, input);
+    db.assert_logs(expect![[r#"
+        [
+            "WillExecute { database_key: create_tracked(0) }",
+            "WillExecute { database_key: create_tracked(0) }",
+        ]"#]]);
+}
+
+#[test]
+fn event_kinds_set() {
+    let kinds = EventKinds::WILL_EXECUTE | EventKinds::DID_DISCARD;
+    assert!(kinds.contains(EventKinds::WILL_EXECUTE));
+    assert!(!kinds.contains(EventKinds::WILL_EXECUTE | EventKinds::WILL_BLOCK_ON));
+    assert!(kinds.intersects(EventKinds::WILL_EXECUTE | EventKinds::WILL_BLOCK_ON));
+    assert!(EventKinds::ALL.contains(kinds));
+    assert!(!EventKinds::NONE.intersects(EventKinds::ALL));
+    assert_eq!(
+        EventKinds::of(&EventKind::WillCheckCancellation),
+        EventKinds::WILL_CHECK_CANCELLATION
+    );
+    expect![[r#"{"WillExecute", "DidDiscard"}"#]].assert_eq(&format!("{:?}", kinds));
+}
+
+#[test]
+fn new_event_kinds_are_opt_in() {
+    assert_eq!(EventKinds::default(), EventKinds::DEFAULT);
+    expect![[
+        r#"{"DidStartRevision", "WillSetInput", "DidCreateTrackedStruct", "DidInternValue"}"#
+    ]]
+    .assert_eq(&format!(
+        "{:?}",
+        EventKinds::ALL.difference(EventKinds::DEFAULT)
+    ));
+}
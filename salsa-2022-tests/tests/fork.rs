@@ -0,0 +1,334 @@
+//! Test that a fork of the database shares the memoized results of
+//! its parent, but that edits to either one do not affect the other.
+
+use std::sync::Arc;
+
+use expect_test::expect;
+use salsa::{Database as _, Durability, ParallelDatabase};
+use salsa_2022_tests::{HasLogger, Logger};
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    MyTracked,
+    Word,
+    Interned,
+    Text,
+    Token,
+    create_tracked,
+    words,
+    word_count,
+    first_word,
+    token,
+);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    len: usize,
+}
+
+#[salsa::tracked]
+struct Word {
+    #[id]
+    text: String,
+}
+
+#[salsa::interned]
+struct Interned {
+    text: String,
+}
+
+#[salsa::input]
+struct Text {
+    text: Arc<str>,
+}
+
+#[salsa::tracked]
+struct Token {
+    #[id]
+    text: Arc<str>,
+}
+
+/// Deliberately does not implement `Clone`: memoized values are shared
+/// with forks, not copied.
+#[derive(Debug, PartialEq, Eq)]
+struct Words(Vec<String>);
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    db.push_log(format!("create_tracked({:?})", input));
+    MyTracked::new(db, input.text(db).len())
+}
+
+#[salsa::tracked(return_ref)]
+fn words(db: &dyn Db, input: MyInput) -> Words {
+    db.push_log(format!("words({:?})", input));
+    Words(input.text(db).split(' ').map(str::to_string).collect())
+}
+
+#[salsa::tracked]
+fn word_count(db: &dyn Db, input: MyInput) -> usize {
+    db.push_log(format!("word_count({:?})", input));
+    words(db, input).0.len()
+}
+
+#[salsa::tracked]
+fn first_word(db: &dyn Db, input: MyInput) -> Option<Word> {
+    let text = input.text(db);
+    let word = text.split(' ').next().filter(|word| !word.is_empty())?;
+    Some(Word::new(db, word.to_string()))
+}
+
+#[salsa::tracked]
+fn token(db: &dyn Db, text: Text) -> Token {
+    Token::new(db, text.text(db))
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl Database {
+    fn fork(&self) -> Self {
+        Database {
+            storage: self.storage.fork(),
+            logger: Default::default(),
+        }
+    }
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl ParallelDatabase for Database {
+    fn snapshot(&self) -> salsa::Snapshot<Self> {
+        salsa::Snapshot::new(Database {
+            storage: self.storage.snapshot(),
+            logger: Default::default(),
+        })
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn fork_reuses_memos() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b c".to_string());
+    assert_eq!(word_count(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "word_count(MyInput(Id { value: 1 }))",
+            "words(MyInput(Id { value: 1 }))",
+        ]"#]]);
+
+    let mut fork = db.fork();
+    assert_eq!(word_count(&fork, input), 3);
+    assert_eq!(words(&fork, input), words(&db, input));
+    fork.assert_logs(expect!["[]"]);
+}
+
+#[test]
+fn fork_edits_are_private() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b c".to_string());
+    assert_eq!(word_count(&db, input), 3);
+    db.assert_logs_len(2);
+
+    let mut fork = db.fork();
+    input.set_text(&mut fork).to("a b c d".to_string());
+    assert_eq!(word_count(&fork, input), 4);
+    fork.assert_logs(expect![[r#"
+        [
+            "words(MyInput(Id { value: 1 }))",
+            "word_count(MyInput(Id { value: 1 }))",
+        ]"#]]);
+
+    // The parent still sees the old value and has not started a new revision,
+    // so its memos are still valid.
+    assert_eq!(input.text(&db), "a b c");
+    assert_eq!(word_count(&db, input), 3);
+    db.assert_logs(expect!["[]"]);
+
+    // Edits to the parent are likewise not seen by the fork.
+    input.set_text(&mut db).to("a".to_string());
+    assert_eq!(word_count(&db, input), 1);
+    assert_eq!(word_count(&fork, input), 4);
+    fork.assert_logs(expect!["[]"]);
+}
+
+#[test]
+fn fork_tracked_structs_and_interned_ids() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string());
+    let tracked = create_tracked(&db, input);
+    assert_eq!(tracked.len(&db), 3);
+
+    let mut fork = db.fork();
+    assert_eq!(create_tracked(&fork, input), tracked);
+    assert_eq!(tracked.len(&fork), 3);
+
+    // Creating new entities in the fork does not create them in the parent.
+    let other = MyInput::new(&mut fork, "abcd".to_string());
+    assert_eq!(create_tracked(&fork, other).len(&fork), 4);
+    let other_in_db = MyInput::new(&mut db, "xy".to_string());
+    assert_eq!(other_in_db, other);
+    assert_eq!(create_tracked(&db, other_in_db).len(&db), 2);
+}
+
+#[test]
+fn fork_does_not_cancel_snapshots() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b c".to_string());
+
+    let snapshot = db.snapshot();
+    let token = snapshot.salsa_runtime().cancellation_token();
+
+    // Writing to the fork would block (or cancel) if it shared
+    // the parent's storage with the snapshot.
+    let mut fork = db.fork();
+    input.set_text(&mut fork).to("a b".to_string());
+    assert_eq!(word_count(&fork, input), 2);
+
+    assert!(!token.is_cancelled());
+    assert_eq!(word_count(&*snapshot, input), 3);
+}
+
+#[test]
+fn fork_verifies_shared_memos_independently() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b c".to_string());
+    let other = MyInput::new(&mut db, "x".to_string());
+    assert_eq!(word_count(&db, input), 3);
+    db.assert_logs_len(2);
+
+    // Both databases start a revision with the same number but different inputs.
+    // The parent verifies the shared memo in its revision; that must not make
+    // the memo count as verified in the fork's revision.
+    let mut fork = db.fork();
+    other.set_text(&mut db).to("y".to_string());
+    input.set_text(&mut fork).to("a b c d".to_string());
+
+    assert_eq!(word_count(&db, input), 3);
+    db.assert_logs(expect!["[]"]);
+
+    assert_eq!(word_count(&fork, input), 4);
+    fork.assert_logs(expect![[r#"
+        [
+            "words(MyInput(Id { value: 1 }))",
+            "word_count(MyInput(Id { value: 1 }))",
+        ]"#]]);
+
+    // Conversely, verifying a shared memo in a fork does not affect the parent.
+    let mut fork = db.fork();
+    other.set_text(&mut fork).to("z".to_string());
+    input.set_text(&mut db).to("a".to_string());
+
+    assert_eq!(word_count(&fork, input), 3);
+    fork.assert_logs(expect!["[]"]);
+
+    assert_eq!(word_count(&db, input), 1);
+    db.assert_logs(expect![[r#"
+        [
+            "words(MyInput(Id { value: 1 }))",
+            "word_count(MyInput(Id { value: 1 }))",
+        ]"#]]);
+}
+
+#[test]
+fn fork_keeps_tracked_structs_deleted_in_parent() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b".to_string());
+    let a = first_word(&db, input).unwrap();
+    assert_eq!(a.text(&db), "a");
+
+    // Re-executing `first_word` in the parent deletes `a` there, but not in the fork.
+    let mut fork = db.fork();
+    input.set_text(&mut db).to("c".to_string());
+    let c = first_word(&db, input).unwrap();
+    assert_eq!(c.text(&db), "c");
+    assert_eq!(first_word(&fork, input), Some(a));
+    assert_eq!(a.text(&fork), "a");
+
+    // A fork of the fork starts out from the fork's own edits.
+    input.set_text(&mut fork).to("d".to_string());
+    let d = first_word(&fork, input).unwrap();
+    assert_eq!(d.text(&fork), "d");
+    let fork_of_fork = fork.fork();
+    assert_eq!(first_word(&fork_of_fork, input), Some(d));
+    assert_eq!(d.text(&fork_of_fork), "d");
+
+    // Interning the deleted word again allocates a new id.
+    input.set_text(&mut db).to("a".to_string());
+    let a_again = first_word(&db, input).unwrap();
+    assert_ne!(a_again, a);
+    assert_eq!(a_again.text(&db), "a");
+}
+
+#[test]
+fn fork_interns_independently() {
+    let db = Database::default();
+    let before = Interned::new(&db, "before".to_string());
+
+    let fork = db.fork();
+    assert_eq!(Interned::new(&fork, "before".to_string()), before);
+
+    // Both allocate the next id, each for its own value.
+    let in_db = Interned::new(&db, "in db".to_string());
+    let in_fork = Interned::new(&fork, "in fork".to_string());
+    assert_eq!(in_db.text(&db), "in db");
+    assert_eq!(in_fork.text(&fork), "in fork");
+    assert_eq!(
+        Interned::new(&fork, "in db".to_string()).text(&fork),
+        "in db"
+    );
+
+    let fork_of_fork = fork.fork();
+    assert_eq!(Interned::new(&fork_of_fork, "before".to_string()), before);
+    assert_eq!(Interned::new(&fork_of_fork, "in fork".to_string()), in_fork);
+    assert_eq!(before.text(&fork_of_fork), "before");
+}
+
+#[test]
+fn dropped_forks_let_the_parent_free_deleted_tracked_structs() {
+    /// Deletes a token in a database that was forked (or not) when the token was created,
+    /// and returns the number of references to the token's text that are left.
+    fn references_left(fork: bool) -> usize {
+        let a: Arc<str> = Arc::from("a");
+        let mut db = Database::default();
+        let input = Text::new(&mut db, a.clone());
+        assert_eq!(*token(&db, input).text(&db), *"a");
+        if fork {
+            drop(db.fork());
+        }
+
+        // Re-executing `token` deletes the token for "a", which is freed in the next revision.
+        input.set_text(&mut db).to(Arc::from("b"));
+        assert_eq!(*token(&db, input).text(&db), *"b");
+        db.storage.synthetic_write(Durability::LOW);
+        Arc::strong_count(&a)
+    }
+
+    // The fork was dropped, so the parent frees the token as if it had never been forked.
+    assert_eq!(references_left(true), references_left(false));
+}
@@ -0,0 +1,81 @@
+//! Test the default durability of inputs, given for the
+//! whole struct or for individual fields.
+
+use salsa::Durability;
+
+#[salsa::jar(db = Db)]
+struct Jar(Config, File, config_name);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input(durability = HIGH)]
+struct Config {
+    name: String,
+    #[durability(MEDIUM)]
+    version: u32,
+}
+
+#[salsa::input]
+struct File {
+    text: String,
+}
+
+#[salsa::tracked]
+fn config_name(db: &dyn Db, config: Config) -> String {
+    config.name(db)
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+#[test]
+fn constructor_uses_default_durability() {
+    let mut db = Database::default();
+    let config = Config::new(&mut db, "config".to_string(), 1);
+    let file = File::new(&mut db, "text".to_string());
+
+    assert_eq!(config.name_durability(&db), Durability::HIGH);
+    assert_eq!(config.version_durability(&db), Durability::MEDIUM);
+    assert_eq!(file.text_durability(&db), Durability::LOW);
+}
+
+#[test]
+fn setter_uses_default_durability() {
+    let mut db = Database::default();
+    let config = Config::new(&mut db, "config".to_string(), 1);
+
+    config.set_name(&mut db).to("other".to_string());
+    assert_eq!(config.name_durability(&db), Durability::HIGH);
+
+    config.set_version(&mut db).to(2);
+    assert_eq!(config.version_durability(&db), Durability::MEDIUM);
+
+    config
+        .set_version(&mut db)
+        .with_durability(Durability::LOW)
+        .to(3);
+    assert_eq!(config.version_durability(&db), Durability::LOW);
+}
+
+#[test]
+fn overwriting_high_durability_value() {
+    let mut db = Database::default();
+    let config = Config::new(&mut db, "config".to_string(), 1);
+    assert_eq!(config_name(&db, config), "config");
+
+    // The memoized value depends only on high-durability inputs,
+    // so it must not be reused after one of them was overwritten.
+    config.set_name(&mut db).to("other".to_string());
+    assert_eq!(config_name(&db, config), "other");
+}
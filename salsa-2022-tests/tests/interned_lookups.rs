@@ -0,0 +1,85 @@
+//! Test looking up the data of many interned values, whose ids span
+//! several chunks of the table, including from several threads at once.
+
+use salsa::interned::SharedInterners;
+
+#[salsa::jar(db = Db)]
+struct Jar(Name, SharedName);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::interned]
+struct Name {
+    text: String,
+}
+
+#[salsa::interned(shared)]
+struct SharedName {
+    text: String,
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+#[test]
+fn many_values() {
+    let db = Database::default();
+    let names: Vec<Name> = (0..10_000)
+        .map(|i| Name::new(&db, format!("name{i}")))
+        .collect();
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(name.text(&db), format!("name{i}"));
+        assert_eq!(Name::new(&db, format!("name{i}")), *name);
+    }
+
+    // A fork starts out with the same values.
+    let fork = Database {
+        storage: db.storage.fork(),
+    };
+    assert_eq!(names[9_999].text(&fork), "name9999");
+    let new_name = Name::new(&fork, "new".to_string());
+    assert_eq!(new_name.text(&fork), "new");
+}
+
+#[test]
+fn concurrent_interning() {
+    let interners = SharedInterners::new();
+    let threads: Vec<_> = (0..4)
+        .map(|thread| {
+            let interners = interners.clone();
+            std::thread::spawn(move || {
+                let db = Database {
+                    storage: salsa::Storage::with_shared_interners(interners),
+                };
+                (0..2_000)
+                    .map(|i| {
+                        let text = format!("name{}", (i * 7 + thread) % 1_000);
+                        let name = SharedName::new(&db, text.clone());
+                        assert_eq!(name.text(&db), text);
+                        (name, text)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let db = Database {
+        storage: salsa::Storage::with_shared_interners(interners),
+    };
+    for thread in threads {
+        for (name, text) in thread.join().unwrap() {
+            assert_eq!(name.text(&db), text);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Test databases declared with `#[salsa::db(no_snapshots)]`,
+//! which claim queries without recording the claims in the sync map
+//! and accept values that are not `Send + Sync`.
+//! Snapshotting them is rejected at compile time (see `compile-fail/snapshot_no_snapshots_db.rs`).
+
+use std::panic::{AssertUnwindSafe, UnwindSafe};
+use std::rc::Rc;
+
+use salsa_2022_tests::{HasLogger, Logger};
+
+use expect_test::expect;
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    Source,
+    Word,
+    first_word,
+    Cyclic,
+    recovering_a,
+    recovering_b,
+    panicking_a,
+    panicking_b,
+);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct Source {
+    text: String,
+}
+
+#[salsa::interned]
+struct Word {
+    text: String,
+}
+
+#[salsa::tracked]
+fn first_word(db: &dyn Db, source: Source) -> Word {
+    db.push_log(format!("first_word({:?})", source.text(db)));
+    let text = source.text(db);
+    Word::new(db, text.split(' ').next().unwrap_or_default().to_string())
+}
+
+#[salsa::input]
+struct Cyclic {
+    cyclic: bool,
+}
+
+#[salsa::tracked(recovery_fn = recover_a)]
+fn recovering_a(db: &dyn Db, input: Cyclic) -> u32 {
+    if input.cyclic(db) {
+        recovering_b(db, input) + 1
+    } else {
+        0
+    }
+}
+
+fn recover_a(_db: &dyn Db, _cycle: &salsa::Cycle, _input: Cyclic) -> u32 {
+    10
+}
+
+#[salsa::tracked(recovery_fn = recover_b)]
+fn recovering_b(db: &dyn Db, input: Cyclic) -> u32 {
+    recovering_a(db, input) + 1
+}
+
+fn recover_b(_db: &dyn Db, _cycle: &salsa::Cycle, _input: Cyclic) -> u32 {
+    20
+}
+
+#[salsa::tracked]
+fn panicking_a(db: &dyn Db, input: Cyclic) -> u32 {
+    panicking_b(db, input)
+}
+
+#[salsa::tracked]
+fn panicking_b(db: &dyn Db, input: Cyclic) -> u32 {
+    panicking_a(db, input)
+}
+
+/// Values that are not `Send`, which only `no_snapshots` databases accept:
+/// listing this jar in `MultiThreadedDatabase` would not compile.
+#[salsa::jar(db = LocalDb)]
+struct LocalJar(Document, title);
+
+trait LocalDb: salsa::DbWithJar<LocalJar> {}
+
+#[salsa::input(jar = LocalJar)]
+struct Document {
+    text: Rc<str>,
+}
+
+#[salsa::tracked(jar = LocalJar)]
+fn title(db: &dyn LocalDb, document: Document) -> Rc<str> {
+    let text = document.text(db);
+    Rc::from(text.lines().next().unwrap_or_default())
+}
+
+#[salsa::db(Jar, LocalJar, no_snapshots)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl LocalDb for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+/// The same database, but without `no_snapshots`.
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct MultiThreadedDatabase {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for MultiThreadedDatabase {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for MultiThreadedDatabase {}
+
+impl HasLogger for MultiThreadedDatabase {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn execute_and_reuse() {
+    let mut db = Database::default();
+    assert!(db.storage.runtime().snapshots_disabled());
+    let source = Source::new(&mut db, "hello world".to_string());
+    assert_eq!(first_word(&db, source).text(&db), "hello");
+    assert_eq!(first_word(&db, source).text(&db), "hello");
+
+    source.set_text(&mut db).to("goodbye world".to_string());
+    assert_eq!(first_word(&db, source).text(&db), "goodbye");
+    db.assert_logs(expect![[r#"
+        [
+            "first_word(\"hello world\")",
+            "first_word(\"goodbye world\")",
+        ]"#]]);
+}
+
+#[test]
+fn non_send_values() {
+    let mut db = Database::default();
+    let document = Document::new(&mut db, Rc::from("Title\nBody"));
+    let first = title(&db, document);
+    assert_eq!(&*first, "Title");
+    assert!(Rc::ptr_eq(&first, &title(&db, document)));
+
+    document.set_text(&mut db).to(Rc::from("New title\nBody"));
+    assert_eq!(&*title(&db, document), "New title");
+}
+
+#[test]
+fn cycle_recovery() {
+    let mut db = Database::default();
+    let input = Cyclic::new(&mut db, true);
+    let mut multi_threaded_db = MultiThreadedDatabase::default();
+    let multi_threaded_input = Cyclic::new(&mut multi_threaded_db, true);
+
+    // Recovery is the same as in a multi-threaded database.
+    let result = (recovering_a(&db, input), recovering_b(&db, input));
+    assert_eq!(
+        result,
+        (
+            recovering_a(&multi_threaded_db, multi_threaded_input),
+            recovering_b(&multi_threaded_db, multi_threaded_input)
+        )
+    );
+    expect!["(10, 20)"].assert_eq(&format!("{:?}", result));
+
+    input.set_cyclic(&mut db).to(false);
+    assert_eq!((recovering_a(&db, input), recovering_b(&db, input)), (0, 1));
+}
+
+#[track_caller]
+fn extract_cycle(f: impl FnOnce() + UnwindSafe) -> salsa::Cycle {
+    let v = std::panic::catch_unwind(f);
+    if let Err(d) = &v {
+        if let Some(cycle) = d.downcast_ref::<salsa::Cycle>() {
+            return cycle.clone();
+        }
+    }
+    panic!("unexpected value: {:?}", v)
+}
+
+#[test]
+fn cycle_without_recovery() {
+    let mut db = Database::default();
+    let input = Cyclic::new(&mut db, true);
+    let cycle = extract_cycle(AssertUnwindSafe(|| {
+        panicking_a(&db, input);
+    }));
+    expect![[r#"
+        [
+            "panicking_a(0)",
+            "panicking_b(0)",
+        ]
+    "#]]
+    .assert_debug_eq(&cycle.all_participants(&db));
+}
@@ -1,7 +1,9 @@
 mod setup;
 
+mod parallel_cancellation;
 mod parallel_cycle_all_recover;
 mod parallel_cycle_mid_recover;
 mod parallel_cycle_none_recover;
 mod parallel_cycle_one_recover;
+mod parallel_stall;
 mod signal;
@@ -0,0 +1,178 @@
+//! Test for cancellation of individual snapshots, either explicitly
+//! through a `CancellationToken` or by a write whose durability is
+//! low enough to affect what the snapshot has read.
+
+use std::time::Duration;
+
+use crate::setup::Database;
+use crate::setup::Knobs;
+use salsa::{Cancelled, Database as _, Durability, ParallelDatabase};
+
+pub(crate) trait Db: salsa::DbWithJar<Jar> + Knobs {}
+
+impl<T: salsa::DbWithJar<Jar> + Knobs> Db for T {}
+
+#[salsa::jar(db = Db)]
+pub(crate) struct Jar(
+    MyInput,
+    read_low,
+    read_high,
+    poll_until_cancelled,
+    poll_a_while,
+);
+
+#[salsa::input(jar = Jar)]
+pub(crate) struct MyInput {
+    low: u32,
+    high: u32,
+}
+
+#[salsa::tracked(jar = Jar)]
+pub(crate) fn read_low(db: &dyn Db, input: MyInput) -> u32 {
+    let low = input.low(db);
+    poll_until_cancelled(db, input);
+    low
+}
+
+#[salsa::tracked(jar = Jar)]
+pub(crate) fn read_high(db: &dyn Db, input: MyInput) -> u32 {
+    let high = input.high(db);
+    poll_a_while(db, input);
+    high
+}
+
+/// Signals stage 1 and then loops until the runtime is cancelled.
+#[salsa::tracked(jar = Jar)]
+pub(crate) fn poll_until_cancelled(db: &dyn Db, _input: MyInput) {
+    db.signal(1);
+    loop {
+        db.salsa_runtime().unwind_if_revision_cancelled(db);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Signals stage 1 and then checks for cancellation for a while,
+/// giving a concurrent write the chance to cancel us.
+#[salsa::tracked(jar = Jar)]
+pub(crate) fn poll_a_while(db: &dyn Db, _input: MyInput) {
+    db.signal(1);
+    for _ in 0..50 {
+        db.salsa_runtime().unwind_if_revision_cancelled(db);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn expect_cancelled<T: std::fmt::Debug>(
+    result: std::thread::Result<Result<T, Cancelled>>,
+) -> Cancelled {
+    match result.unwrap() {
+        Ok(value) => panic!("expected cancellation, got {:?}", value),
+        Err(cancelled) => cancelled,
+    }
+}
+
+#[test]
+fn cancel_snapshot_with_token() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1, 2);
+
+    let snapshot = db.snapshot();
+    let token = snapshot.salsa_runtime().cancellation_token();
+    let thread = std::thread::spawn(move || {
+        Cancelled::catch(std::panic::AssertUnwindSafe(|| read_low(&*snapshot, input)))
+    });
+
+    db.wait_for(1);
+    token.cancel();
+
+    let cancelled = expect_cancelled(thread.join());
+    assert!(
+        matches!(cancelled, Cancelled::Requested { .. }),
+        "{:?}",
+        cancelled
+    );
+
+    // The main database was not affected.
+    assert!(!db.salsa_runtime().cancellation_token().is_cancelled());
+}
+
+#[test]
+fn low_durability_write_cancels_low_durability_reader() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1, 2);
+    input
+        .set_high(&mut db)
+        .with_durability(Durability::HIGH)
+        .to(2);
+
+    let thread = std::thread::spawn({
+        let db = db.snapshot();
+        move || Cancelled::catch(std::panic::AssertUnwindSafe(|| read_low(&*db, input)))
+    });
+
+    db.wait_for(1);
+    input.set_low(&mut db).to(10);
+
+    let cancelled = expect_cancelled(thread.join());
+    assert!(
+        matches!(cancelled, Cancelled::PendingWrite { .. }),
+        "{:?}",
+        cancelled
+    );
+    assert_eq!(input.low(&db), 10);
+}
+
+#[test]
+fn low_durability_write_does_not_cancel_high_durability_reader() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1, 2);
+    input
+        .set_high(&mut db)
+        .with_durability(Durability::HIGH)
+        .to(2);
+
+    let thread = std::thread::spawn({
+        let db = db.snapshot();
+        move || Cancelled::catch(std::panic::AssertUnwindSafe(|| read_high(&*db, input)))
+    });
+
+    db.wait_for(1);
+
+    // Blocks until the snapshot has completed, but does not cancel it.
+    input.set_low(&mut db).to(10);
+
+    assert_eq!(thread.join().unwrap().unwrap(), 2);
+    assert_eq!(input.low(&db), 10);
+}
+
+#[test]
+fn high_durability_write_cancels_high_durability_reader() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 1, 2);
+    input
+        .set_high(&mut db)
+        .with_durability(Durability::HIGH)
+        .to(2);
+
+    let thread = std::thread::spawn({
+        let db = db.snapshot();
+        move || Cancelled::catch(std::panic::AssertUnwindSafe(|| read_high(&*db, input)))
+    });
+
+    db.wait_for(1);
+
+    // Overwriting a high durability value may affect every snapshot,
+    // even if the new value is given a low durability.
+    input
+        .set_high(&mut db)
+        .with_durability(Durability::LOW)
+        .to(20);
+
+    let cancelled = expect_cancelled(thread.join());
+    assert!(
+        matches!(cancelled, Cancelled::PendingWrite { .. }),
+        "{:?}",
+        cancelled
+    );
+    assert_eq!(input.high(&db), 20);
+}
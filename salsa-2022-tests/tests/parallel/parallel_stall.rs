@@ -0,0 +1,142 @@
+//! Test inspecting blocked and running runtimes with a `StallMonitor` and the
+//! `StillBlockedOn` event reported after the stall warning timeout.
+
+use std::time::Duration;
+
+use crate::setup::Database;
+use crate::setup::Knobs;
+use expect_test::expect;
+use salsa::{DebugWithDb, ParallelDatabase};
+
+pub(crate) trait Db: salsa::DbWithJar<Jar> + Knobs {}
+
+impl<T: salsa::DbWithJar<Jar> + Knobs> Db for T {}
+
+#[salsa::jar(db = Db)]
+pub(crate) struct Jar(MyInput, outer, slow);
+
+#[salsa::input(jar = Jar)]
+pub(crate) struct MyInput {
+    field: i32,
+}
+
+#[salsa::tracked(jar = Jar)]
+pub(crate) fn outer(db: &dyn Db, input: MyInput) -> i32 {
+    // Wait until thread A is executing `slow`.
+    db.wait_for(1);
+
+    slow(db, input) * 2
+}
+
+#[salsa::tracked(jar = Jar)]
+pub(crate) fn slow(db: &dyn Db, input: MyInput) -> i32 {
+    db.signal(1);
+
+    // Wait until the main thread has inspected the blocked runtimes.
+    db.wait_for(4);
+
+    input.field(db)
+}
+
+#[test]
+fn execute() {
+    let mut db = Database::default();
+    db.storage
+        .set_stall_warning_timeout(Some(Duration::from_millis(10)));
+    db.knobs().signal_on_still_blocked.set(2);
+    db.knobs().wait_on_still_blocked.set(3);
+
+    let input = MyInput::new(&mut db, 21);
+    let monitor = db.storage.runtime().stall_monitor();
+    assert!(monitor.wait_graph().is_empty());
+
+    let thread_a = std::thread::spawn({
+        let db = db.snapshot();
+        move || slow(&*db, input)
+    });
+
+    let thread_b = std::thread::spawn({
+        let db = db.snapshot();
+        move || outer(&*db, input)
+    });
+
+    // Thread B reports that it is still blocked on thread A. While it does,
+    // it is not waiting, and its query stack is published as running.
+    db.wait_for(2);
+    let graph = std::thread::spawn({
+        let monitor = monitor.clone();
+        move || monitor.wait_graph()
+    })
+    .join()
+    .unwrap();
+    expect![[r#"
+        WaitGraph {
+            blocked: [],
+            running: [
+                RunningRuntime {
+                    runtime_id: RuntimeId {
+                        counter: 1,
+                    },
+                    query_stack: [
+                        slow(0),
+                    ],
+                },
+                RunningRuntime {
+                    runtime_id: RuntimeId {
+                        counter: 2,
+                    },
+                    query_stack: [
+                        outer(0),
+                    ],
+                },
+            ],
+        }
+    "#]]
+    .assert_eq(&format!("{:#?}\n", graph.debug(&db)));
+
+    // Thread B then blocks on thread A again.
+    db.signal(3);
+    let graph = std::thread::spawn(move || loop {
+        let graph = monitor.wait_graph();
+        if !graph.blocked.is_empty() {
+            break graph;
+        }
+        std::thread::yield_now();
+    })
+    .join()
+    .unwrap();
+    expect![[r#"
+        WaitGraph {
+            blocked: [
+                BlockedRuntime {
+                    runtime_id: RuntimeId {
+                        counter: 2,
+                    },
+                    query_stack: [
+                        outer(0),
+                    ],
+                    blocked_on_key: slow(0),
+                    blocked_on_runtime: RuntimeId {
+                        counter: 1,
+                    },
+                },
+            ],
+            running: [
+                RunningRuntime {
+                    runtime_id: RuntimeId {
+                        counter: 1,
+                    },
+                    query_stack: [
+                        slow(0),
+                    ],
+                },
+            ],
+        }
+    "#]]
+    .assert_eq(&format!("{:#?}\n", graph.debug(&db)));
+
+    db.signal(4);
+    assert_eq!(thread_a.join().unwrap(), 21);
+    assert_eq!(thread_b.join().unwrap(), 42);
+    assert!(db.storage.runtime().stall_monitor().wait_graph().is_empty());
+}
@@ -23,17 +23,26 @@ pub(crate) struct KnobsStruct {
 
     /// When this database is about to block, send a signal.
     pub(crate) signal_on_will_block: Cell<usize>,
+
+    /// When this database reports that it is still blocked, send a signal.
+    pub(crate) signal_on_still_blocked: Cell<usize>,
+
+    /// When this database reports that it is still blocked, wait for a signal
+    /// (after sending `signal_on_still_blocked`) before blocking again.
+    pub(crate) wait_on_still_blocked: Cell<usize>,
 }
 
 #[salsa::db(
+    crate::parallel_cancellation::Jar,
     crate::parallel_cycle_one_recover::Jar,
     crate::parallel_cycle_none_recover::Jar,
     crate::parallel_cycle_mid_recover::Jar,
-    crate::parallel_cycle_all_recover::Jar
+    crate::parallel_cycle_all_recover::Jar,
+    crate::parallel_stall::Jar
 )]
 #[derive(Default)]
 pub(crate) struct Database {
-    storage: salsa::Storage<Self>,
+    pub(crate) storage: salsa::Storage<Self>,
     knobs: KnobsStruct,
 }
 
@@ -43,8 +52,15 @@ impl salsa::Database for Database {
     }
 
     fn salsa_event(&self, event: salsa::Event) {
-        if let salsa::EventKind::WillBlockOn { .. } = event.kind {
-            self.signal(self.knobs().signal_on_will_block.get());
+        match event.kind {
+            salsa::EventKind::WillBlockOn { .. } => {
+                self.signal(self.knobs().signal_on_will_block.get());
+            }
+            salsa::EventKind::StillBlockedOn { .. } => {
+                self.signal(self.knobs().signal_on_still_blocked.get());
+                self.wait_for(self.knobs().wait_on_still_blocked.get());
+            }
+            _ => {}
         }
     }
 }
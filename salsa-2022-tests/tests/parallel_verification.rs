@@ -0,0 +1,219 @@
+//! Test verifying the dependencies of a memo on several threads
+//! (see `salsa::Database::snapshot_for_verification`).
+
+use std::sync::{Arc, Mutex};
+
+use salsa::DebugWithDb;
+
+#[salsa::jar(db = Db)]
+struct Jar(Items, Item, Other, sum, double);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input]
+struct Items {
+    items: Vec<Item>,
+}
+
+#[salsa::input]
+struct Item {
+    value: u32,
+}
+
+#[salsa::input]
+struct Other {
+    value: u32,
+}
+
+#[salsa::tracked]
+fn sum(db: &dyn Db, items: Items) -> u32 {
+    items.items(db).iter().map(|&item| double(db, item)).sum()
+}
+
+#[salsa::tracked]
+fn double(db: &dyn Db, item: Item) -> u32 {
+    item.value(db) * 2
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Logged {
+    Validated { key: String, on_worker: bool },
+    Executed { key: String },
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    log: Arc<Mutex<Vec<Logged>>>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+
+    fn salsa_event(&self, event: salsa::Event) {
+        let on_worker = format!("{:?}", event.runtime_id) != "RuntimeId { counter: 0 }";
+        match event.kind {
+            salsa::EventKind::DidValidateMemoizedValue { database_key } => {
+                if on_worker {
+                    // Workers run on the threads started by `set_verification_threads`.
+                    let thread = std::thread::current();
+                    assert!(thread.name().unwrap().starts_with("salsa-verification-"));
+                }
+                let key = format!("{:?}", database_key.debug(self));
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(Logged::Validated { key, on_worker });
+                if !on_worker {
+                    // Give the workers a chance to get ahead of us.
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+            }
+            salsa::EventKind::WillExecute { database_key } => {
+                assert!(!on_worker, "verification workers must not execute queries");
+                let key = format!("{:?}", database_key.debug(self));
+                self.log.lock().unwrap().push(Logged::Executed { key });
+            }
+            _ => {}
+        }
+    }
+
+    fn snapshot_for_verification(&self) -> Option<Box<dyn salsa::Database + Send>> {
+        Some(Box::new(Database {
+            storage: self.storage.verification_snapshot(),
+            log: self.log.clone(),
+        }))
+    }
+}
+
+impl salsa::ParallelDatabase for Database {
+    fn snapshot(&self) -> salsa::Snapshot<Self> {
+        salsa::Snapshot::new(Database {
+            storage: self.storage.snapshot(),
+            log: self.log.clone(),
+        })
+    }
+}
+
+impl Db for Database {}
+
+impl Database {
+    fn take_log(&self) -> Vec<Logged> {
+        std::mem::take(&mut *self.log.lock().unwrap())
+    }
+}
+
+/// Computes `sum` over 8 items, then changes an unrelated input,
+/// so that the next call to `sum` has to verify each of its dependencies.
+fn setup(threads: usize) -> (Database, Items, Vec<Item>) {
+    setup_with_items(threads, 8)
+}
+
+fn setup_with_items(threads: usize, count: u32) -> (Database, Items, Vec<Item>) {
+    let mut db = Database::default();
+    db.storage.set_verification_threads(threads);
+    let items: Vec<Item> = (0..count).map(|i| Item::new(&mut db, i)).collect();
+    let list = Items::new(&mut db, items.clone());
+    let other = Other::new(&mut db, 0);
+    assert_eq!(sum(&db, list), count * (count - 1));
+
+    other.set_value(&mut db).to(1);
+    db.take_log();
+    (db, list, items)
+}
+
+fn validated_keys(log: &[Logged]) -> Vec<&str> {
+    let mut keys: Vec<&str> = log
+        .iter()
+        .filter_map(|l| match l {
+            Logged::Validated { key, .. } => Some(&key[..]),
+            Logged::Executed { .. } => None,
+        })
+        .collect();
+    keys.sort();
+    keys
+}
+
+#[test]
+fn sequential_by_default() {
+    let (db, list, _) = setup(0);
+    assert_eq!(sum(&db, list), 56);
+    let log = db.take_log();
+    assert_eq!(validated_keys(&log).len(), 9);
+    assert!(log.iter().all(|l| matches!(
+        l,
+        Logged::Validated {
+            on_worker: false,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn dependencies_verified_by_workers_once() {
+    let (db, list, _) = setup(4);
+    assert_eq!(sum(&db, list), 56);
+    let log = db.take_log();
+
+    // Each memo is verified exactly once, some of them by workers.
+    let keys = validated_keys(&log);
+    let mut deduped = keys.clone();
+    deduped.dedup();
+    assert_eq!(keys, deduped);
+    assert_eq!(keys.len(), 9);
+    assert!(log.iter().any(|l| matches!(
+        l,
+        Logged::Validated {
+            on_worker: true,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn workers_reused_across_verifications() {
+    let (mut db, list, _) = setup(4);
+    let other = Other::new(&mut db, 0);
+    for i in 1..=3 {
+        other.set_value(&mut db).to(i);
+        db.take_log();
+        assert_eq!(sum(&db, list), 56);
+        assert_eq!(validated_keys(&db.take_log()).len(), 9);
+    }
+}
+
+#[test]
+fn few_dependencies_verified_sequentially() {
+    // `sum` has 4 inputs, too few to hand any of them to a worker.
+    let (db, list, _) = setup_with_items(4, 3);
+    assert_eq!(sum(&db, list), 6);
+    let log = db.take_log();
+    assert_eq!(validated_keys(&log).len(), 4);
+    assert!(log.iter().all(|l| matches!(
+        l,
+        Logged::Validated {
+            on_worker: false,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn changed_dependencies_executed_on_main_thread() {
+    let (mut db, list, items) = setup(4);
+    items[5].set_value(&mut db).to(10);
+    db.take_log();
+
+    assert_eq!(sum(&db, list), 66);
+    let log = db.take_log();
+    assert!(log.contains(&Logged::Executed {
+        key: "double(5)".to_string()
+    }));
+    assert!(log.contains(&Logged::Executed {
+        key: "sum(0)".to_string()
+    }));
+    assert!(!validated_keys(&log).contains(&"double(5)"));
+}
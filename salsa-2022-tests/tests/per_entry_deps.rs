@@ -0,0 +1,131 @@
+//! Test the `per_entry_deps` option, with which reading an interned or
+//! tracked struct adds a dependency on just that struct rather than its table.
+
+use expect_test::expect;
+use salsa_2022_tests::{HasLogger, Logger};
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    Name,
+    PlainName,
+    MyTracked,
+    create_tracked,
+    name_len,
+);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+    other: u32,
+}
+
+#[salsa::interned(per_entry_deps)]
+struct Name {
+    text: String,
+}
+
+#[salsa::interned]
+struct PlainName {
+    text: String,
+}
+
+#[salsa::tracked(per_entry_deps)]
+struct MyTracked {
+    #[id]
+    name: Name,
+}
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    db.push_log(format!("create_tracked({:?})", input.text(db)));
+    PlainName::new(db, input.text(db));
+    MyTracked::new(db, Name::new(db, input.text(db)))
+}
+
+#[salsa::tracked]
+fn name_len(db: &dyn Db, input: MyInput) -> usize {
+    db.push_log(format!("name_len({:?})", input.text(db)));
+    let tracked = create_tracked(db, input);
+    tracked.name(db).text(db).len()
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+fn dump(db: &Database) -> String {
+    let mut out = vec![];
+    salsa::debug::dump(db, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn dependencies_on_entries() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 0);
+    assert_eq!(name_len(&db, input), 3);
+
+    // `PlainName` is a dependency on the whole table, `Name(0)` and `MyTracked(0)` on single entries.
+    expect![[r#"
+        [0] input field text
+            text(0): value="abc", durability=Durability(0), changed_at=R2
+        [1] input field other
+            other(0): value=0, durability=Durability(0), changed_at=R2
+        [2] input MyInput
+            MyInput(0)
+        [3] interned Name
+            Name(0): Name { text: "abc" }
+        [4] interned PlainName
+            PlainName(0): PlainName { text: "abc" }
+        [5] tracked struct MyTracked
+            MyTracked(0): created_by=create_tracked(0), disambiguator=0, id_fields=MyTracked { name: Name(Id { value: 1 }) }
+        [6] function create_tracked
+            create_tracked(0): value=MyTracked(Id { value: 1 }), verified_at=R2, changed_at=R2, durability=Durability(0), origin=Derived, deps=[text(0), PlainName(), Name(0), MyTracked(), MyTracked(0)]
+        [7] function name_len
+            name_len(0): value=3, verified_at=R2, changed_at=R2, durability=Durability(0), origin=Derived, deps=[text(0), create_tracked(0), MyTracked(0), Name(0)]
+    "#]].assert_eq(&dump(&db));
+}
+
+#[test]
+fn entries_are_still_valid_in_new_revisions() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 0);
+    assert_eq!(name_len(&db, input), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "name_len(\"abc\")",
+            "create_tracked(\"abc\")",
+        ]"#]]);
+
+    input.set_other(&mut db).to(1);
+    assert_eq!(name_len(&db, input), 3);
+    db.assert_logs(expect!["[]"]);
+
+    input.set_text(&mut db).to("abcd".to_string());
+    assert_eq!(name_len(&db, input), 4);
+    db.assert_logs(expect![[r#"
+        [
+            "name_len(\"abcd\")",
+            "create_tracked(\"abcd\")",
+        ]"#]]);
+}
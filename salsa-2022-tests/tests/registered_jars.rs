@@ -0,0 +1,125 @@
+//! Test jars listed as `registered(...)` in `#[salsa::db]`,
+//! which are only created by `Storage::register_jar`.
+
+use expect_test::expect;
+use salsa_2022_tests::{HasLogger, Logger};
+
+#[salsa::jar(db = Db)]
+struct Jar(Text, text_len);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct Text {
+    text: String,
+}
+
+#[salsa::tracked]
+fn text_len(db: &dyn Db, text: Text) -> usize {
+    db.push_log(format!("text_len({:?})", text.text(db)));
+    text.text(db).len()
+}
+
+#[salsa::jar(db = PluginDb)]
+struct PluginJar(word_count);
+
+trait PluginDb: salsa::DbWithJar<PluginJar> + Db {}
+
+#[salsa::tracked(jar = PluginJar)]
+fn word_count(db: &dyn PluginDb, text: Text) -> usize {
+    db.push_log(format!("word_count({:?})", text.text(db)));
+    text.text(db).split_whitespace().count()
+}
+
+#[salsa::db(Jar, registered(PluginJar))]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl PluginDb for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+fn dump(db: &Database) -> String {
+    let mut out = vec![];
+    salsa::debug::dump(db, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn register_after_first_use() {
+    let mut db = Database::default();
+    let text = Text::new(&mut db, "a b c".to_string());
+    assert_eq!(text_len(&db, text), 5);
+    db.assert_logs(expect![[r#"
+        [
+            "text_len(\"a b c\")",
+        ]"#]]);
+
+    db.storage.register_jar::<PluginJar>();
+    assert_eq!(word_count(&db, text), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "word_count(\"a b c\")",
+        ]"#]]);
+
+    // Registering starts a new revision, but existing memos are still valid.
+    assert_eq!(text_len(&db, text), 5);
+    db.assert_logs(expect!["[]"]);
+
+    // The ingredients of the jar come after the others.
+    expect![[r#"
+        [0] input field text
+            text(0): value="a b c", durability=Durability(0), changed_at=R2
+        [1] input Text
+            Text(0)
+        [2] function text_len
+            text_len(0): value=5, verified_at=R3, changed_at=R2, durability=Durability(0), origin=Derived, deps=[text(0)]
+        [3] function word_count
+            word_count(0): value=3, verified_at=R3, changed_at=R2, durability=Durability(0), origin=Derived, deps=[text(0)]
+    "#]]
+    .assert_eq(&dump(&db));
+
+    text.set_text(&mut db).to("a b".to_string());
+    assert_eq!(word_count(&db, text), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "word_count(\"a b\")",
+        ]"#]]);
+}
+
+#[test]
+fn register_twice() {
+    let mut db = Database::default();
+    let text = Text::new(&mut db, "a b".to_string());
+    db.storage.register_jar::<PluginJar>();
+    assert_eq!(word_count(&db, text), 2);
+    db.storage.register_jar::<PluginJar>();
+    assert_eq!(word_count(&db, text), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "word_count(\"a b\")",
+        ]"#]]);
+}
+
+#[test]
+#[should_panic(expected = "has not been registered")]
+fn unregistered_jar() {
+    let mut db = Database::default();
+    let text = Text::new(&mut db, "a b".to_string());
+    word_count(&db, text);
+}
@@ -0,0 +1,142 @@
+//! Test the `returns(<mode>)` option on tracked functions and the
+//! `#[returns(<mode>)]` attribute on the fields of salsa structs.
+
+use salsa::DebugWithDb;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    MyTracked,
+    MyInterned,
+    words,
+    first_word,
+    word_count,
+    shared_words,
+    create_tracked,
+);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input(debug)]
+struct MyInput {
+    #[returns(deref)]
+    text: String,
+    #[returns(as_ref)]
+    label: Option<String>,
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    #[id]
+    #[returns(deref)]
+    name: String,
+    #[returns(as_ref)]
+    parent: Option<String>,
+    #[returns(clone)]
+    words: Vec<String>,
+}
+
+#[salsa::interned]
+struct MyInterned {
+    #[returns(deref)]
+    words: Vec<String>,
+}
+
+#[salsa::tracked(returns(deref))]
+fn words(db: &dyn Db, input: MyInput) -> Vec<String> {
+    input
+        .text(db)
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+#[salsa::tracked(returns(as_ref))]
+fn first_word(db: &dyn Db, input: MyInput) -> Option<String> {
+    words(db, input).first().cloned()
+}
+
+#[salsa::tracked(returns(clone))]
+fn word_count(db: &dyn Db, input: MyInput) -> usize {
+    words(db, input).len()
+}
+
+#[salsa::tracked(returns(ref))]
+fn shared_words(db: &dyn Db, input: MyInput) -> MyInterned {
+    MyInterned::new(db, words(db, input).to_vec())
+}
+
+#[salsa::tracked]
+fn create_tracked(db: &dyn Db, input: MyInput) -> MyTracked {
+    MyTracked::new(
+        db,
+        input.text(db).to_string(),
+        input.label(db).cloned(),
+        words(db, input).to_vec(),
+    )
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+#[test]
+fn tracked_fn_return_modes() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "hello world".to_string(), None);
+
+    let w: &[String] = words(&db, input);
+    assert_eq!(w, ["hello", "world"]);
+    let first: Option<&String> = first_word(&db, input);
+    assert_eq!(first.map(String::as_str), Some("hello"));
+    let count: usize = word_count(&db, input);
+    assert_eq!(count, 2);
+    let interned: &MyInterned = shared_words(&db, input);
+    assert_eq!(interned.words(&db).len(), 2);
+
+    input.set_text(&mut db).to(String::new());
+    assert_eq!(first_word(&db, input), None);
+}
+
+#[test]
+fn field_return_modes() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a b".to_string(), Some("label".to_string()));
+
+    let text: &str = input.text(&db);
+    assert_eq!(text, "a b");
+    let label: Option<&String> = input.label(&db);
+    assert_eq!(label.map(String::as_str), Some("label"));
+
+    let tracked = create_tracked(&db, input);
+    let name: &str = tracked.name(&db);
+    assert_eq!(name, "a b");
+    let parent: Option<&String> = tracked.parent(&db);
+    assert_eq!(parent.map(String::as_str), Some("label"));
+    let tracked_words: Vec<String> = tracked.words(&db);
+    assert_eq!(tracked_words, ["a", "b"]);
+
+    let interned = MyInterned::new(&db, tracked_words);
+    let interned_words: &[String] = interned.words(&db);
+    assert_eq!(interned_words, ["a", "b"]);
+}
+
+#[test]
+fn debug_shows_returned_values() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "a".to_string(), None);
+    assert_eq!(
+        format!("{:?}", input.debug(&db)),
+        r#"MyInput { [salsa id]: 0, text: "a", label: None }"#
+    );
+}
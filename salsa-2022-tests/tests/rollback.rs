@@ -0,0 +1,142 @@
+//! Test rolling the database back to the inputs of an earlier revision
+//! using `#[salsa::input(history = N)]`.
+
+use expect_test::expect;
+use salsa::Database as _;
+use salsa_2022_tests::{HasLogger, Logger};
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, Plain, text_len, plain_len);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input(history = 2)]
+struct MyInput {
+    text: String,
+}
+
+/// An input without history.
+#[salsa::input]
+struct Plain {
+    text: String,
+}
+
+#[salsa::tracked]
+fn text_len(db: &dyn Db, input: MyInput) -> usize {
+    db.push_log(format!("text_len({})", input.text(db)));
+    input.text(db).len()
+}
+
+#[salsa::tracked]
+fn plain_len(db: &dyn Db, input: Plain) -> usize {
+    db.push_log(format!("plain_len({})", input.text(db)));
+    input.text(db).len()
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn rollback_reuses_memos_from_revision() {
+    let mut db = Database::default();
+    let a = MyInput::new(&mut db, "a".to_string());
+    let b = MyInput::new(&mut db, "b".to_string());
+    assert_eq!(text_len(&db, a), 1);
+    assert_eq!(text_len(&db, b), 1);
+    db.assert_logs_len(2);
+    let revision = db.salsa_runtime().current_revision();
+
+    a.set_text(&mut db).to("aa".to_string());
+    a.set_text(&mut db).to("aaa".to_string());
+    assert_eq!(text_len(&db, b), 1);
+    db.assert_logs(expect!["[]"]);
+
+    db.storage.rollback_to(revision);
+    assert!(db.salsa_runtime().current_revision() > revision);
+    assert_eq!(a.text(&db), "a");
+
+    // Neither memo read a newer value, so both are still valid.
+    assert_eq!(text_len(&db, a), 1);
+    assert_eq!(text_len(&db, b), 1);
+    db.assert_logs(expect!["[]"]);
+}
+
+#[test]
+fn rollback_discards_memos_of_newer_values() {
+    let mut db = Database::default();
+    let a = MyInput::new(&mut db, "a".to_string());
+    let revision = db.salsa_runtime().current_revision();
+
+    a.set_text(&mut db).to("aa".to_string());
+    assert_eq!(text_len(&db, a), 2);
+    db.assert_logs(expect![[r#"
+        [
+            "text_len(aa)",
+        ]"#]]);
+
+    db.storage.rollback_to(revision);
+    assert_eq!(text_len(&db, a), 1);
+    db.assert_logs(expect![[r#"
+        [
+            "text_len(a)",
+        ]"#]]);
+
+    // The value from before the rollback can be set again.
+    a.set_text(&mut db).to("aa".to_string());
+    assert_eq!(text_len(&db, a), 2);
+}
+
+#[test]
+fn rollback_keeps_inputs_created_later() {
+    let mut db = Database::default();
+    let a = MyInput::new(&mut db, "a".to_string());
+    let revision = db.salsa_runtime().current_revision();
+
+    let b = MyInput::new(&mut db, "b".to_string());
+    b.set_text(&mut db).to("bb".to_string());
+    a.set_text(&mut db).to("aa".to_string());
+
+    db.storage.rollback_to(revision);
+    assert_eq!(a.text(&db), "a");
+    assert_eq!(b.text(&db), "bb");
+}
+
+#[test]
+fn rollback_requires_history() {
+    let mut db = Database::default();
+    let a = MyInput::new(&mut db, "a".to_string());
+    let revision = db.salsa_runtime().current_revision();
+
+    // Only two prior values are kept.
+    a.set_text(&mut db).to("aa".to_string());
+    a.set_text(&mut db).to("aaa".to_string());
+    assert!(db.storage.can_rollback_to(revision));
+    a.set_text(&mut db).to("aaaa".to_string());
+    assert!(!db.storage.can_rollback_to(revision));
+
+    // Inputs without history cannot be rolled back at all.
+    let revision = db.salsa_runtime().current_revision();
+    assert!(db.storage.can_rollback_to(revision));
+    let p = Plain::new(&mut db, "p".to_string());
+    assert!(!db.storage.can_rollback_to(revision));
+    assert_eq!(plain_len(&db, p), 1);
+}
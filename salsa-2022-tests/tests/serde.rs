@@ -0,0 +1,146 @@
+//! Test serializing input and interned structs through the database
+//! (see `salsa::serde`) and re-creating them in another database.
+
+#![cfg(feature = "serde")]
+
+use expect_test::expect;
+
+#[salsa::jar(db = Db)]
+struct Jar(Workspace, File, Name, total_len);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input(serde)]
+struct Workspace {
+    name: Name,
+    #[return_ref]
+    files: Vec<File>,
+    main: Option<File>,
+}
+
+#[salsa::input(serde)]
+struct File {
+    name: Name,
+    #[return_ref]
+    contents: String,
+}
+
+#[salsa::interned(serde)]
+struct Name {
+    #[return_ref]
+    text: String,
+}
+
+#[salsa::tracked]
+fn total_len(db: &dyn Db, workspace: Workspace) -> usize {
+    workspace
+        .files(db)
+        .iter()
+        .map(|file| file.contents(db).len())
+        .sum()
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+fn export(db: &Database, workspace: Workspace) -> String {
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    salsa::serde::serialize::<dyn Db, _, _>(db, &workspace, &mut serializer).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn import(db: &mut Database, json: &str) -> serde_json::Result<Workspace> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    salsa::serde::deserialize::<dyn Db, _, _>(db, &mut deserializer)
+}
+
+fn workspace(db: &mut Database) -> Workspace {
+    let name = Name::new(db, "lib.rs".to_string());
+    let lib = File::new(db, name, "fn lib() {}".to_string());
+    let name = Name::new(db, "main.rs".to_string());
+    let main = File::new(db, name, "fn main() {}".to_string());
+    let workspace_name = Name::new(db, "ws".to_string());
+    Workspace::new(db, workspace_name, vec![lib, main], Some(main))
+}
+
+#[test]
+fn writes_field_values_once() {
+    let mut db = Database::default();
+    let workspace = workspace(&mut db);
+    expect![[r#"{"Def":[0,[{"Def":[2,["ws"]]},[{"Def":[0,[{"Def":[0,["lib.rs"]]},"fn lib() {}"]]},{"Def":[1,[{"Def":[1,["main.rs"]]},"fn main() {}"]]}],{"Ref":1}]]}"#]]
+    .assert_eq(&export(&db, workspace));
+}
+
+#[test]
+fn round_trip_remaps_ids() {
+    let mut db = Database::default();
+    let workspace = workspace(&mut db);
+    let json = export(&db, workspace);
+    let lib = workspace.files(&db)[0];
+
+    // Create some structs first, so that the ids differ.
+    let mut other_db = Database::default();
+    let name = Name::new(&other_db, "x".to_string());
+    let other = File::new(&mut other_db, name, String::new());
+    let imported = import(&mut other_db, &json).unwrap();
+
+    let db = &other_db;
+    assert_eq!(imported.name(db).text(db), "ws");
+    let files = imported.files(db);
+    assert_eq!(files.len(), 2);
+    assert!(!files.contains(&other));
+    assert_ne!(files[0], lib);
+    assert_eq!(files[0].name(db).text(db), "lib.rs");
+    assert_eq!(files[1].contents(db), "fn main() {}");
+    assert_eq!(imported.main(db), Some(files[1]));
+    assert_eq!(total_len(db, imported), 23);
+
+    // Interned names are re-interned rather than re-created.
+    assert_eq!(files[0].name(db), Name::new(db, "lib.rs".to_string()));
+}
+
+#[test]
+fn imported_inputs_can_be_set() {
+    let mut db = Database::default();
+    let workspace = workspace(&mut db);
+    let json = export(&db, workspace);
+    let mut db = Database::default();
+    let imported = import(&mut db, &json).unwrap();
+    assert_eq!(total_len(&db, imported), 23);
+    let main = imported.main(&db).unwrap();
+    main.set_contents(&mut db).to(String::new());
+    assert_eq!(total_len(&db, imported), 11);
+}
+
+#[test]
+fn dangling_reference() {
+    let mut db = Database::default();
+    let err = import(&mut db, r#"{"Ref":0}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("reference to a `serde::Workspace` that was not written before"),
+        "{err}"
+    );
+}
+
+#[test]
+fn serialize_outside_of_database() {
+    let mut db = Database::default();
+    let workspace = workspace(&mut db);
+    let err = serde_json::to_string(&workspace).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("salsa structs can only be serialized within `salsa::serde::serialize`"));
+}
@@ -0,0 +1,165 @@
+//! Test interned structs declared as `shared`, whose table is shared
+//! by several databases.
+
+use expect_test::expect;
+use salsa::interned::SharedInterners;
+use salsa_2022_tests::{HasLogger, Logger};
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, Name, Local, name_of);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+}
+
+#[salsa::interned(shared)]
+struct Name {
+    text: String,
+}
+
+#[salsa::interned]
+struct Local {
+    text: String,
+}
+
+#[salsa::tracked]
+fn name_of(db: &dyn Db, input: MyInput) -> Name {
+    db.push_log(format!("name_of({:?})", input.text(db)));
+    Name::new(db, input.text(db))
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl Database {
+    fn with_shared_interners(interners: SharedInterners) -> Self {
+        Self {
+            storage: salsa::Storage::with_shared_interners(interners),
+            logger: Default::default(),
+        }
+    }
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn ids_are_comparable_across_databases() {
+    let interners = SharedInterners::new();
+    let db1 = Database::with_shared_interners(interners.clone());
+    let db2 = Database::with_shared_interners(interners);
+
+    let a1 = Name::new(&db1, "a".to_string());
+    let b2 = Name::new(&db2, "b".to_string());
+    let a2 = Name::new(&db2, "a".to_string());
+    assert_eq!(a1, a2);
+    assert_ne!(a1, b2);
+
+    // Either database can read values interned by the other.
+    assert_eq!(b2.text(&db1), "b");
+
+    // Structs that are not shared are interned per database.
+    let local1 = Local::new(&db1, "b".to_string());
+    let local2 = Local::new(&db2, "a".to_string());
+    assert_eq!(local1, local2);
+}
+
+#[test]
+fn separate_interners_are_independent() {
+    let db1 = Database::with_shared_interners(SharedInterners::new());
+    let db2 = Database::with_shared_interners(SharedInterners::new());
+
+    let a1 = Name::new(&db1, "a".to_string());
+    let b2 = Name::new(&db2, "b".to_string());
+    assert_eq!(a1, b2);
+    assert_eq!(a1.text(&db1), "a");
+    assert_eq!(b2.text(&db2), "b");
+}
+
+#[test]
+fn shared_interners_with_durabilities() {
+    let interners = SharedInterners::new();
+    let mut db1 = Database {
+        storage: salsa::Storage::new(6, interners.clone()),
+        logger: Default::default(),
+    };
+    let db2 = Database::with_shared_interners(interners);
+
+    let input = MyInput::new(&mut db1, "a".to_string());
+    input
+        .set_text(&mut db1)
+        .with_durability(salsa::Durability::new(5))
+        .to("b".to_string());
+    assert_eq!(name_of(&db1, input), Name::new(&db2, "b".to_string()));
+    db1.assert_logs(expect![[r#"
+        [
+            "name_of(\"b\")",
+        ]"#]]);
+}
+
+#[test]
+fn global_interners() {
+    let db1 = Database::default();
+    let db2 = Database::default();
+    let a1 = Name::new(&db1, "global".to_string());
+    let a2 = Name::new(&db2, "global".to_string());
+    assert_eq!(a1, a2);
+}
+
+#[test]
+fn dependencies_are_per_database() {
+    let interners = SharedInterners::new();
+    let mut db1 = Database::with_shared_interners(interners.clone());
+    let mut db2 = Database::with_shared_interners(interners);
+
+    let input1 = MyInput::new(&mut db1, "a".to_string());
+    let input2 = MyInput::new(&mut db2, "a".to_string());
+    assert_eq!(name_of(&db1, input1), name_of(&db2, input2));
+    db1.assert_logs(expect![[r#"
+        [
+            "name_of(\"a\")",
+        ]"#]]);
+    db2.assert_logs(expect![[r#"
+        [
+            "name_of(\"a\")",
+        ]"#]]);
+
+    // Changing the input in one database only re-executes the query there.
+    input1.set_text(&mut db1).to("b".to_string());
+    assert_eq!(name_of(&db1, input1).text(&db1), "b");
+    assert_eq!(name_of(&db2, input2).text(&db2), "a");
+    db1.assert_logs(expect![[r#"
+        [
+            "name_of(\"b\")",
+        ]"#]]);
+    db2.assert_logs(expect!["[]"]);
+}
+
+#[test]
+fn forks_share_the_table() {
+    let db = Database::with_shared_interners(SharedInterners::new());
+    let fork = Database {
+        storage: db.storage.fork(),
+        logger: Default::default(),
+    };
+    let a = Name::new(&fork, "a".to_string());
+    assert_eq!(Name::new(&db, "a".to_string()), a);
+}
@@ -0,0 +1,184 @@
+//! Test specifying the value of a tracked function keyed on an interned
+//! struct, from the query that owns it, and `try_specify`.
+
+use expect_test::expect;
+use salsa_2022_tests::{HasLogger, Logger};
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(
+    MyInput,
+    Name,
+    intern_and_specify,
+    intern_owned_other,
+    try_specify_other,
+    specify_other,
+    name_len,
+);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct MyInput {
+    text: String,
+    factor: usize,
+}
+
+#[salsa::interned]
+struct Name {
+    #[return_ref]
+    text: String,
+}
+
+#[salsa::tracked]
+fn intern_and_specify(db: &dyn Db, input: MyInput) -> Name {
+    db.push_log(format!("intern_and_specify({})", input.factor(db)));
+    let name = Name::new_owned(db, input.text(db).clone());
+    name_len::specify(db, name, name.text(db).len() * input.factor(db));
+    name
+}
+
+/// Tries to own the name interned by `intern_and_specify`.
+#[salsa::tracked]
+fn intern_owned_other(db: &dyn Db, input: MyInput) -> Name {
+    Name::new_owned(db, input.text(db).clone())
+}
+
+#[salsa::tracked]
+fn try_specify_other(db: &dyn Db, name: Name) -> Result<(), salsa::SpecifyError> {
+    name_len::try_specify(db, name, 0)
+}
+
+#[salsa::tracked]
+fn specify_other(db: &dyn Db, name: Name) {
+    name_len::specify(db, name, 0);
+}
+
+#[salsa::tracked(specify)]
+fn name_len(db: &dyn Db, name: Name) -> usize {
+    db.push_log(format!("name_len({})", name.text(db)));
+    name.text(db).len()
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+#[test]
+fn specify_interned_key() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 10);
+    let name = intern_and_specify(&db, input);
+    assert_eq!(name_len(&db, name), 30);
+    db.assert_logs(expect![[r#"
+        [
+            "intern_and_specify(10)",
+        ]"#]]);
+}
+
+#[test]
+fn value_is_specified_again() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 10);
+    intern_and_specify(&db, input);
+    db.assert_logs_len(1);
+
+    input.set_factor(&mut db).to(20);
+    let name = intern_and_specify(&db, input);
+    assert_eq!(name_len(&db, name), 60);
+    db.assert_logs(expect![[r#"
+        [
+            "intern_and_specify(20)",
+        ]"#]]);
+}
+
+#[test]
+fn unspecified_value_is_computed() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 10);
+    intern_and_specify(&db, input);
+    db.assert_logs_len(1);
+
+    // Like a tracked struct, an owned name should be obtained from its owner.
+    // If it is interned without executing the owner, the stale value is not used,
+    // but computed instead.
+    input.set_text(&mut db).to("abcd".to_string());
+    let name = Name::new(&db, "abc".to_string());
+    assert_eq!(name_len(&db, name), 3);
+    db.assert_logs(expect![[r#"
+        [
+            "name_len(abc)",
+        ]"#]]);
+}
+
+#[test]
+fn try_specify_requires_owner() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 10);
+    let name = intern_and_specify(&db, input);
+    assert_eq!(
+        try_specify_other(&db, name),
+        Err(salsa::SpecifyError::NotOwnedByActiveQuery)
+    );
+
+    // Names interned with the plain constructor have no owner.
+    let other = Name::new(&db, "other".to_string());
+    assert_eq!(
+        try_specify_other(&db, other),
+        Err(salsa::SpecifyError::NotOwnedByActiveQuery)
+    );
+}
+
+#[test]
+fn try_specify_requires_active_query() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 10);
+    let name = intern_and_specify(&db, input);
+    assert_eq!(
+        name_len::try_specify(&db, name, 0),
+        Err(salsa::SpecifyError::NoActiveQuery)
+    );
+}
+
+#[test]
+#[should_panic(expected = "can only use `set` on entities created during current query")]
+fn specify_panics_without_owner() {
+    let db = Database::default();
+    let name = Name::new(&db, "abc".to_string());
+    specify_other(&db, name);
+}
+
+#[test]
+#[should_panic(
+    expected = "`intern_owned_other(0)` cannot own `Name(0)`, which is already owned by `intern_and_specify(0)`"
+)]
+fn names_have_one_owner() {
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, "abc".to_string(), 10);
+    intern_and_specify(&db, input);
+    intern_owned_other(&db, input);
+}
+
+#[test]
+#[should_panic(expected = "can only intern an owned value with an active query")]
+fn owned_names_require_active_query() {
+    let db = Database::default();
+    Name::new_owned(&db, "abc".to_string());
+}
@@ -0,0 +1,97 @@
+//! Test that, with the `stale-id-checks` feature, using the id of a deleted
+//! tracked struct panics with a message naming the struct and the revision
+//! in which it was deleted.
+
+#![cfg(feature = "stale-id-checks")]
+
+use salsa::Database as _;
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, MyTracked, create_tracked_structs, double);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input]
+struct MyInput {
+    count: u32,
+}
+
+#[salsa::tracked]
+struct MyTracked {
+    #[id]
+    index: u32,
+    value: u32,
+}
+
+#[salsa::tracked]
+fn create_tracked_structs(db: &dyn Db, input: MyInput) -> Vec<MyTracked> {
+    (0..input.count(db))
+        .map(|i| MyTracked::new(db, i, i * 10))
+        .collect()
+}
+
+#[salsa::tracked]
+fn double(db: &dyn Db, tracked: MyTracked) -> u32 {
+    tracked.value(db) * 2
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+/// Creates two tracked structs, then deletes the second one in revision R3
+/// and returns it.
+fn deleted_struct(db: &mut Database) -> MyTracked {
+    let input = MyInput::new(db, 2);
+    let stale = create_tracked_structs(db, input)[1];
+    assert_eq!(double(db, stale), 20);
+
+    input.set_count(db).to(1);
+    assert_eq!(format!("{:?}", db.salsa_runtime().current_revision()), "R3");
+    assert_eq!(create_tracked_structs(db, input).len(), 1);
+    stale
+}
+
+#[test]
+#[should_panic(expected = "stale id: `MyTracked(Id { value: 2 })` was deleted in revision R3")]
+fn read_id_field_of_deleted_struct() {
+    let mut db = Database::default();
+    let stale = deleted_struct(&mut db);
+    stale.index(&db);
+}
+
+#[test]
+#[should_panic(expected = "stale id: `MyTracked(Id { value: 2 })` was deleted in revision R3")]
+fn read_value_field_of_deleted_struct() {
+    let mut db = Database::default();
+    let stale = deleted_struct(&mut db);
+    stale.value(&db);
+}
+
+#[test]
+#[should_panic(expected = "stale id: `MyTracked(Id { value: 2 })` was deleted in revision R3")]
+fn call_tracked_fn_on_deleted_struct() {
+    let mut db = Database::default();
+    let stale = deleted_struct(&mut db);
+    double(&db, stale);
+}
+
+#[test]
+fn live_structs_are_unaffected() {
+    let mut db = Database::default();
+    deleted_struct(&mut db);
+    let input = MyInput::new(&mut db, 1);
+    let live = create_tracked_structs(&db, input)[0];
+    assert_eq!(live.index(&db), 0);
+    assert_eq!(double(&db, live), 0);
+}
@@ -0,0 +1,166 @@
+//! Test subscriptions to the values of tracked functions:
+//! `subscribe`, `unsubscribe`, and `changed_subscriptions`.
+
+use salsa_2022_tests::{HasLogger, Logger};
+
+use expect_test::expect;
+use test_log::test;
+
+#[salsa::jar(db = Db)]
+struct Jar(File, diagnostics, line_length);
+
+trait Db: salsa::DbWithJar<Jar> + HasLogger {}
+
+#[salsa::input]
+struct File {
+    text: String,
+}
+
+/// The lines that are longer than 10 characters.
+#[salsa::tracked(return_ref)]
+fn diagnostics(db: &dyn Db, file: File) -> Vec<usize> {
+    db.push_log(format!("diagnostics({:?})", file.text(db)));
+    file.text(db)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.len() > 10)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[salsa::tracked]
+#[allow(dead_code)]
+fn line_length(db: &dyn Db, file: File, line: usize) -> usize {
+    db.push_log(format!("line_length({:?}, {})", file.text(db), line));
+    file.text(db).lines().nth(line).map_or(0, str::len)
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+    logger: Logger,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+impl HasLogger for Database {
+    fn logger(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+fn changed_diagnostics(db: &Database) -> Vec<(String, Vec<usize>)> {
+    diagnostics::changed_subscriptions(db)
+        .into_iter()
+        .map(|(file, diagnostics)| (file.text(db), diagnostics.clone()))
+        .collect()
+}
+
+#[test]
+fn subscribe() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "short".to_string());
+    let b = File::new(&mut db, "a long line".to_string());
+    diagnostics::subscribe(&db, a);
+    diagnostics::subscribe(&db, b);
+
+    // New subscriptions are always returned.
+    expect![[r#"
+        [
+            (
+                "short",
+                [],
+            ),
+            (
+                "a long line",
+                [
+                    0,
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&changed_diagnostics(&db));
+    db.assert_logs(expect![[r#"
+        [
+            "diagnostics(\"short\")",
+            "diagnostics(\"a long line\")",
+        ]"#]]);
+
+    // Nothing changed.
+    assert!(changed_diagnostics(&db).is_empty());
+    assert_eq!(diagnostics(&db, b), &[0]);
+    db.assert_logs(expect!["[]"]);
+
+    // Only the edited file is re-executed and returned.
+    a.set_text(&mut db).to("another long line".to_string());
+    expect![[r#"
+        [
+            (
+                "another long line",
+                [
+                    0,
+                ],
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&changed_diagnostics(&db));
+    db.assert_logs(expect![[r#"
+        [
+            "diagnostics(\"another long line\")",
+        ]"#]]);
+
+    // The value is recomputed, but it is equal to the last one, so it is not returned.
+    a.set_text(&mut db).to("yet another long line".to_string());
+    assert!(changed_diagnostics(&db).is_empty());
+    db.assert_logs(expect![[r#"
+        [
+            "diagnostics(\"yet another long line\")",
+        ]"#]]);
+}
+
+#[test]
+fn unsubscribe() {
+    let mut db = Database::default();
+    let a = File::new(&mut db, "short".to_string());
+    diagnostics::subscribe(&db, a);
+    assert_eq!(changed_diagnostics(&db).len(), 1);
+
+    diagnostics::unsubscribe(&db, a);
+    a.set_text(&mut db).to("another long line".to_string());
+    assert!(changed_diagnostics(&db).is_empty());
+    db.assert_logs(expect![[r#"
+        [
+            "diagnostics(\"short\")",
+        ]"#]]);
+
+    // Subscribing again returns the current value.
+    diagnostics::subscribe(&db, a);
+    assert_eq!(changed_diagnostics(&db).len(), 1);
+}
+
+#[test]
+fn subscribe_with_several_arguments() {
+    let mut db = Database::default();
+    let file = File::new(&mut db, "one\ntwo".to_string());
+    line_length::subscribe(&db, file, 0);
+    line_length::subscribe(&db, file, 1);
+    let changed: Vec<_> = line_length::changed_subscriptions(&db)
+        .into_iter()
+        .map(|((_, line), &len)| (line, len))
+        .collect();
+    assert_eq!(changed, [(0, 3), (1, 3)]);
+
+    file.set_text(&mut db).to("one\nthree".to_string());
+    let changed: Vec<_> = line_length::changed_subscriptions(&db)
+        .into_iter()
+        .map(|((_, line), &len)| (line, len))
+        .collect();
+    assert_eq!(changed, [(1, 5)]);
+}
@@ -0,0 +1,133 @@
+//! Test that, with the `tracing` feature, salsa opens a span
+//! for each query execution and deep verification.
+
+#![cfg(feature = "tracing")]
+
+use std::sync::{Arc, Mutex};
+
+use expect_test::expect;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+
+#[salsa::jar(db = Db)]
+struct Jar(MyInput, final_result, intermediate_result);
+
+trait Db: salsa::DbWithJar<Jar> {}
+
+#[salsa::input(jar = Jar)]
+struct MyInput {
+    field: u32,
+}
+
+#[salsa::tracked(jar = Jar)]
+fn final_result(db: &dyn Db, input: MyInput) -> u32 {
+    intermediate_result(db, input) * 2
+}
+
+#[salsa::tracked(jar = Jar)]
+fn intermediate_result(db: &dyn Db, input: MyInput) -> u32 {
+    input.field(db) / 2
+}
+
+#[salsa::db(Jar)]
+#[derive(Default)]
+struct Database {
+    storage: salsa::Storage<Self>,
+}
+
+impl salsa::Database for Database {
+    fn salsa_runtime(&self) -> &salsa::Runtime {
+        self.storage.runtime()
+    }
+}
+
+impl Db for Database {}
+
+/// Records each span that is created as `name(field = value, ...)`,
+/// indented by the number of spans that were entered at the time.
+#[derive(Clone, Default)]
+struct SpanRecorder {
+    spans: Arc<Mutex<Vec<String>>>,
+    depth: Arc<Mutex<usize>>,
+    next_id: Arc<Mutex<u64>>,
+}
+
+struct FieldsToString(Vec<String>);
+
+impl Visit for FieldsToString {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push(format!("{} = {:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+        let mut fields = FieldsToString(vec![]);
+        span.record(&mut fields);
+        let indent = "  ".repeat(*self.depth.lock().unwrap());
+        self.spans.lock().unwrap().push(format!(
+            "{}{}({})",
+            indent,
+            span.metadata().name(),
+            fields.0.join(", ")
+        ));
+        let mut next_id = self.next_id.lock().unwrap();
+        *next_id += 1;
+        span::Id::from_u64(*next_id)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &span::Id) {
+        *self.depth.lock().unwrap() += 1;
+    }
+
+    fn exit(&self, _span: &span::Id) {
+        *self.depth.lock().unwrap() -= 1;
+    }
+}
+
+impl SpanRecorder {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.spans.lock().unwrap())
+    }
+}
+
+#[test]
+fn execute_and_deep_verify_spans() {
+    let recorder = SpanRecorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let mut db = Database::default();
+    let input = MyInput::new(&mut db, 22);
+    assert_eq!(final_result(&db, input), 22);
+    expect![[r#"
+        [
+            "execute(ingredient = \"final_result\", key = 0)",
+            "  execute(ingredient = \"intermediate_result\", key = 0)",
+        ]
+    "#]]
+    .assert_debug_eq(&recorder.take());
+
+    input.set_field(&mut db).to(24);
+    assert_eq!(final_result(&db, input), 24);
+    expect![[r#"
+        [
+            "deep_verify(ingredient = \"final_result\", key = 0)",
+            "  deep_verify(ingredient = \"intermediate_result\", key = 0)",
+            "  execute(ingredient = \"intermediate_result\", key = 0)",
+            "execute(ingredient = \"final_result\", key = 0)",
+        ]
+    "#]]
+    .assert_debug_eq(&recorder.take());
+}